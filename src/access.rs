@@ -0,0 +1,140 @@
+use crate::{time::Timestamp, Request, RpcRouter};
+use http::{
+    header::{CONTENT_LENGTH, REFERER, USER_AGENT},
+    HeaderMap,
+};
+use std::net::SocketAddr;
+use warp::reply::Response;
+
+/// Format of the lines of [`RpcRouter::access_log`].
+///
+/// [`RpcRouter::access_log`]: ./struct.RpcRouter.html#method.access_log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The Common Log Format, followed by the quoted RPC method.
+    Common,
+    /// The Combined Log Format, which adds the `Referer` and `User-Agent`
+    /// headers to the Common Log Format, followed by the quoted RPC method.
+    Combined,
+}
+
+impl RpcRouter {
+    /// Emit a line of every call in the format of HTTP server access logs,
+    /// by `log` under the `warp_json_rpc::access` target.
+    ///
+    /// ```text
+    /// 127.0.0.1 - - [14/Oct/2026:13:55:36 +0000] "POST /rpc HTTP/1.1" 200 39 "add"
+    /// ```
+    ///
+    /// Lines are emitted for requests answered by the router, but not for
+    /// requests of unknown methods, which are rejected.
+    pub fn access_log(&mut self, format: LogFormat) -> &mut RpcRouter {
+        self.access_log = Some(format);
+        self
+    }
+}
+
+/// What an access log line is made of, taken before the request is
+/// dispatched.
+pub(crate) struct Entry {
+    format: LogFormat,
+    remote: Option<SocketAddr>,
+    time: Timestamp,
+    path: String,
+    method: String,
+    referer: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl Entry {
+    pub(crate) fn new(
+        format: LogFormat,
+        req: &Request,
+        headers: &HeaderMap,
+        remote: Option<SocketAddr>,
+        path: &str,
+    ) -> Entry {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Entry {
+            format,
+            remote,
+            time: Timestamp::now(),
+            path: path.to_string(),
+            method: req.method().to_string(),
+            referer: header(REFERER),
+            user_agent: header(USER_AGENT),
+        }
+    }
+
+    pub(crate) fn log(&self, response: &Response) {
+        let bytes = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("-");
+        log::info!(target: "warp_json_rpc::access", "{}", self.line(response.status().as_u16(), bytes));
+    }
+
+    fn line(&self, status: u16, bytes: &str) -> String {
+        let remote = self
+            .remote
+            .map_or_else(|| "-".to_string(), |remote| remote.ip().to_string());
+        let mut line = format!(
+            "{} - - [{}] \"POST {} HTTP/1.1\" {} {}",
+            remote,
+            self.time.to_common_log(),
+            quoted(&self.path),
+            status,
+            bytes
+        );
+        if self.format == LogFormat::Combined {
+            let field =
+                |value: &Option<String>| value.as_deref().map_or_else(|| "-".to_string(), quoted);
+            line.push_str(&format!(
+                " \"{}\" \"{}\"",
+                field(&self.referer),
+                field(&self.user_agent)
+            ));
+        }
+        line.push_str(&format!(" \"{}\"", quoted(&self.method)));
+        line
+    }
+}
+
+/// Escape `value` to be put in a quoted field.
+fn quoted(value: &str) -> String {
+    value.escape_default().to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn format_lines() {
+        let mut entry = Entry {
+            format: LogFormat::Common,
+            remote: Some(([127, 0, 0, 1], 8080).into()),
+            time: Timestamp(UNIX_EPOCH + Duration::from_secs(971_186_136)),
+            path: "/rpc".to_string(),
+            method: "say \"hi\"".to_string(),
+            referer: None,
+            user_agent: Some("curl/7.0".to_string()),
+        };
+        assert_eq!(
+            entry.line(200, "39"),
+            r#"127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "POST /rpc HTTP/1.1" 200 39 "say \"hi\"""#
+        );
+        entry.format = LogFormat::Combined;
+        assert_eq!(
+            entry.line(200, "39"),
+            r#"127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "POST /rpc HTTP/1.1" 200 39 "-" "curl/7.0" "say \"hi\"""#
+        );
+    }
+}
@@ -0,0 +1,610 @@
+use crate::{
+    redact::Sensitive,
+    report::{self, DigestKey},
+    req::Id,
+    time::Timestamp,
+    Context, RpcRouter,
+};
+use futures::future::{BoxFuture, FutureExt as _};
+use serde::{Serialize, Serializer};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead as _, BufReader, Write as _},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::Instant,
+};
+
+/// A finalized record of an RPC call, for compliance logging.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditRecord {
+    pub request_id: Id,
+    pub method: String,
+    /// Identity of the caller, decided by [`RpcRouter::audit_caller`].
+    ///
+    /// [`RpcRouter::audit_caller`]: ./struct.RpcRouter.html#method.audit_caller
+    pub caller: Option<String>,
+    /// `None` if the call succeeded.
+    pub error_code: Option<i64>,
+    #[serde(rename = "latency_ms", serialize_with = "serialize_millis")]
+    pub latency: Duration,
+    /// When the call was answered.
+    pub time: Timestamp,
+    /// Size of the JSON text of the RPC parameter.
+    pub params_bytes: usize,
+    /// JSON text of the RPC parameter if [`Retention::params`] is `Full`,
+    /// with sensitive values redacted.
+    ///
+    /// [`Retention::params`]: ./struct.Retention.html#structfield.params
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<String>,
+    /// Hex HMAC-SHA256 of the JSON text of the RPC parameter if
+    /// [`Retention::params`] is `Digest`, by the key set by
+    /// [`RpcRouter::params_digest_key`].
+    ///
+    /// [`Retention::params`]: ./struct.Retention.html#structfield.params
+    /// [`RpcRouter::params_digest_key`]: ./struct.RpcRouter.html#method.params_digest_key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params_digest: Option<String>,
+    /// Size of the response body.
+    pub response_bytes: usize,
+}
+
+fn serialize_millis<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+/// Receives batches of [`AuditRecord`]s.
+///
+/// Records are sent from a background task, so writing them never delays
+/// responses.
+///
+/// [`AuditRecord`]: ./struct.AuditRecord.html
+pub trait AuditSink: Send + Sync {
+    fn write(&self, records: Vec<AuditRecord>) -> BoxFuture<'_, ()>;
+
+    /// Remove records kept past `retention`, called after every write.
+    ///
+    /// Records older than `max_age` are never written, so a sink which does
+    /// not keep records itself need not implement this.
+    fn retain(&self, retention: &Retention) -> BoxFuture<'_, ()> {
+        let _ = retention;
+        futures::future::ready(()).boxed()
+    }
+}
+
+/// How long and in what form audit records are kept, set by
+/// [`RpcRouter::audit_retention`].
+///
+/// [`RpcRouter::audit_retention`]: ./struct.RpcRouter.html#method.audit_retention
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// Records older than this are dropped.
+    pub max_age: Option<Duration>,
+    /// The oldest records are dropped once a sink keeps more bytes than this.
+    pub max_bytes: Option<u64>,
+    pub params: ParamsRetention,
+}
+
+/// What of the RPC parameter an [`AuditRecord`] keeps.
+///
+/// [`AuditRecord`]: ./struct.AuditRecord.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamsRetention {
+    /// Only its size.
+    Omit,
+    /// Its size and digest, to tell calls of the same parameter apart
+    /// without keeping it.
+    Digest,
+    /// Its size and JSON text, with values marked by
+    /// `RpcRouter::sensitive` redacted.
+    Full,
+}
+
+impl Default for ParamsRetention {
+    fn default() -> ParamsRetention {
+        ParamsRetention::Omit
+    }
+}
+
+/// An `AuditSink` appending records to a file as JSON lines.
+///
+/// Records past the retention are dropped by rewriting the file. So the file
+/// is not rewritten after every batch, records expiring within an eighth of
+/// `max_age` are dropped along with the expired ones, and once the file is
+/// larger than `max_bytes` the oldest records are dropped until it is three
+/// quarters of it.
+#[derive(Debug, Clone)]
+pub struct JsonlSink {
+    path: PathBuf,
+    /// What the file keeps, once it is known.
+    kept: Arc<Mutex<Option<Kept>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Kept {
+    oldest: Option<SystemTime>,
+    bytes: u64,
+}
+
+impl JsonlSink {
+    pub fn new<P>(path: P) -> JsonlSink
+    where
+        P: Into<PathBuf>,
+    {
+        JsonlSink {
+            path: path.into(),
+            kept: Arc::default(),
+        }
+    }
+}
+
+impl AuditSink for JsonlSink {
+    fn write(&self, records: Vec<AuditRecord>) -> BoxFuture<'_, ()> {
+        let (path, kept) = (self.path.clone(), self.kept.clone());
+        let write = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+            let oldest = records.first().map(|record| record.time.into());
+            let mut lines = Vec::new();
+            for record in records {
+                serde_json::to_writer(&mut lines, &record)?;
+                lines.push(b'\n');
+            }
+            let mut kept = kept.lock().unwrap();
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?
+                .write_all(&lines)?;
+            if let Some(kept) = kept.as_mut() {
+                kept.oldest = kept.oldest.or(oldest);
+                kept.bytes += lines.len() as u64;
+            }
+            Ok(())
+        });
+        async move {
+            match write.await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    log::error!(target: "warp_json_rpc", "Failed to write audit records: {}", e)
+                }
+                Err(e) => {
+                    log::error!(target: "warp_json_rpc", "Failed to write audit records: {}", e)
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn retain(&self, retention: &Retention) -> BoxFuture<'_, ()> {
+        if retention.max_age.is_none() && retention.max_bytes.is_none() {
+            return futures::future::ready(()).boxed();
+        }
+        let (path, kept, retention) = (self.path.clone(), self.kept.clone(), retention.clone());
+        let retain = tokio::task::spawn_blocking(move || -> io::Result<()> {
+            let mut kept = kept.lock().unwrap();
+            let current = match *kept {
+                Some(current) => current,
+                None => kept_lines(&path)?,
+            };
+            // The file is only rewritten once the retention reaches records.
+            let old = expired(current.oldest, retention.max_age);
+            let large = matches!(retention.max_bytes, Some(max_bytes) if current.bytes > max_bytes);
+            *kept = Some(if old || large {
+                retain_lines(&path, &retention)?
+            } else {
+                current
+            });
+            Ok(())
+        });
+        async move {
+            match retain.await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    log::error!(target: "warp_json_rpc", "Failed to expire audit records: {}", e)
+                }
+                Err(e) => {
+                    log::error!(target: "warp_json_rpc", "Failed to expire audit records: {}", e)
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+/// What the JSON lines file at `path` keeps, reading its first line only.
+fn kept_lines(path: &Path) -> io::Result<Kept> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Kept {
+                oldest: None,
+                bytes: 0,
+            })
+        }
+        Err(e) => return Err(e),
+    };
+    let bytes = file.metadata()?.len();
+    let mut first = String::new();
+    BufReader::new(file).read_line(&mut first)?;
+    Ok(Kept {
+        oldest: written_at(first.trim_end()),
+        bytes,
+    })
+}
+
+/// Rewrite the JSON lines file at `path` without the records past
+/// `retention`, leaving room for more as told by `JsonlSink`.
+fn retain_lines(path: &Path, retention: &Retention) -> io::Result<Kept> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return kept_lines(path),
+        Err(e) => return Err(e),
+    };
+    let max_age = retention.max_age.map(|max_age| max_age - max_age / 8);
+    let mut lines = text
+        .lines()
+        .filter(|line| !expired(written_at(line), max_age))
+        .collect::<Vec<_>>();
+    let mut bytes = lines.iter().map(|line| line.len() as u64 + 1).sum::<u64>();
+    if let Some(max_bytes) = retention.max_bytes.filter(|max_bytes| bytes > *max_bytes) {
+        let target = max_bytes - max_bytes / 4;
+        let mut oldest = 0;
+        while bytes > target && oldest < lines.len() {
+            bytes -= lines[oldest].len() as u64 + 1;
+            oldest += 1;
+        }
+        lines.drain(..oldest);
+    }
+    let kept = Kept {
+        oldest: lines.first().and_then(|line| written_at(line)),
+        bytes,
+    };
+    if lines.len() == text.lines().count() {
+        return Ok(kept);
+    }
+    let mut text = lines.join("\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    // Replace the file at once, so a crash never leaves it half written.
+    let temporary = path.with_extension("retain");
+    fs::write(&temporary, text)?;
+    fs::rename(&temporary, path)?;
+    Ok(kept)
+}
+
+fn written_at(line: &str) -> Option<SystemTime> {
+    let record = serde_json::from_str::<serde_json::Value>(line).ok()?;
+    let time = Timestamp::parse_rfc3339(record.get("time")?.as_str()?).ok()?;
+    Some(time.into())
+}
+
+fn expired(time: Option<SystemTime>, max_age: Option<Duration>) -> bool {
+    match (time, max_age) {
+        (Some(time), Some(max_age)) => matches!(time.elapsed(), Ok(age) if age > max_age),
+        _ => false,
+    }
+}
+
+/// An `AuditSink` emitting each record by `log` under the
+/// `warp_json_rpc::audit` target.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl AuditSink for LogSink {
+    fn write(&self, records: Vec<AuditRecord>) -> BoxFuture<'_, ()> {
+        for record in records {
+            match serde_json::to_string(&record) {
+                Ok(line) => log::info!(target: "warp_json_rpc::audit", "{}", line),
+                Err(e) => {
+                    log::error!(target: "warp_json_rpc", "Failed to serialize audit record: {}", e)
+                }
+            }
+        }
+        futures::future::ready(()).boxed()
+    }
+}
+
+impl RpcRouter {
+    /// Send an [`AuditRecord`] of every call to `sink`.
+    ///
+    /// Records are sent in batches of up to 64, or whatever has been
+    /// collected one second after the first record of a batch.
+    ///
+    /// [`AuditRecord`]: ./struct.AuditRecord.html
+    pub fn audit<S>(&mut self, sink: S) -> &mut RpcRouter
+    where
+        S: AuditSink + 'static,
+    {
+        self.auditor = Some(Auditor::new(Arc::new(sink)));
+        self
+    }
+
+    /// Decide [`AuditRecord::caller`] of each call by `caller`.
+    ///
+    /// [`AuditRecord::caller`]: ./struct.AuditRecord.html#structfield.caller
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`audit`] is not called before.
+    ///
+    /// [`audit`]: #method.audit
+    pub fn audit_caller<F>(&mut self, caller: F) -> &mut RpcRouter
+    where
+        F: Fn(&Context) -> Option<String> + Send + Sync + 'static,
+    {
+        let auditor = self
+            .auditor
+            .as_mut()
+            .expect("`RpcRouter::audit` must be called before `audit_caller`");
+        auditor.caller = Some(Arc::new(caller));
+        self
+    }
+
+    /// Keep audit records as decided by `retention`, enforced before they
+    /// are sent to the sink and by [`AuditSink::retain`] after.
+    ///
+    /// [`AuditSink::retain`]: ./trait.AuditSink.html#method.retain
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`audit`] is not called before.
+    ///
+    /// [`audit`]: #method.audit
+    pub fn audit_retention(&mut self, retention: Retention) -> &mut RpcRouter {
+        let auditor = self
+            .auditor
+            .as_mut()
+            .expect("`RpcRouter::audit` must be called before `audit_retention`");
+        auditor.retention = Arc::new(retention);
+        self
+    }
+}
+
+const BATCH_SIZE: usize = 64;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+type Caller = Arc<dyn Fn(&Context) -> Option<String> + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct Auditor {
+    sink: Arc<dyn AuditSink>,
+    caller: Option<Caller>,
+    retention: Arc<Retention>,
+    tx: Arc<Mutex<Option<UnboundedSender<AuditRecord>>>>,
+}
+
+impl Auditor {
+    fn new(sink: Arc<dyn AuditSink>) -> Auditor {
+        Auditor {
+            sink,
+            caller: None,
+            retention: Arc::default(),
+            tx: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub(crate) fn caller(&self, ctx: &Context) -> Option<String> {
+        self.caller.as_ref().and_then(|caller| caller(ctx))
+    }
+
+    /// The parameter and its digest to record, as decided by the retention.
+    pub(crate) fn params(
+        &self,
+        raw: Option<&str>,
+        sensitive: &Sensitive,
+        key: &DigestKey,
+    ) -> (Option<String>, Option<String>) {
+        let raw = match raw {
+            Some(raw) => raw,
+            None => return (None, None),
+        };
+        match self.retention.params {
+            ParamsRetention::Omit => (None, None),
+            ParamsRetention::Digest => (None, report::params_digest(key, Some(raw))),
+            ParamsRetention::Full if sensitive.is_empty() => (Some(raw.to_string()), None),
+            ParamsRetention::Full => {
+                let params = serde_json::from_str(raw).unwrap_or(serde_json::Value::Null);
+                let mut document = serde_json::json!({ "params": params });
+                sensitive.redact(&mut document);
+                (Some(document["params"].to_string()), None)
+            }
+        }
+    }
+
+    pub(crate) fn record(&self, record: AuditRecord) {
+        let mut tx = self.tx.lock().unwrap();
+        // The background task is spawned lazily, since a runtime may not be
+        // running when the router is built.
+        let tx = tx.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            tokio::spawn(run(self.sink.clone(), self.retention.clone(), rx));
+            tx
+        });
+        if tx.send(record).is_err() {
+            log::error!(target: "warp_json_rpc", "Audit task is gone");
+        }
+    }
+}
+
+async fn run(
+    sink: Arc<dyn AuditSink>,
+    retention: Arc<Retention>,
+    mut rx: UnboundedReceiver<AuditRecord>,
+) {
+    let write = |mut batch: Vec<AuditRecord>| {
+        let (sink, retention) = (sink.clone(), retention.clone());
+        async move {
+            batch.retain(|record| !expired(Some(record.time.into()), retention.max_age));
+            if !batch.is_empty() {
+                sink.write(batch).await;
+            }
+            sink.retain(&retention).await;
+        }
+    };
+    let mut batch = Vec::new();
+    let mut deadline = Instant::now();
+    loop {
+        let received = if batch.is_empty() {
+            rx.recv().await
+        } else {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(received) => received,
+                Err(_) => {
+                    write(std::mem::take(&mut batch)).await;
+                    continue;
+                }
+            }
+        };
+
+        match received {
+            Some(record) => {
+                if batch.is_empty() {
+                    deadline = Instant::now() + FLUSH_INTERVAL;
+                }
+                batch.push(record);
+                if batch.len() >= BATCH_SIZE {
+                    write(std::mem::take(&mut batch)).await;
+                }
+            }
+            None => {
+                if !batch.is_empty() {
+                    write(batch).await;
+                }
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<AuditRecord>>);
+
+    impl AuditSink for Arc<Collect> {
+        fn write(&self, records: Vec<AuditRecord>) -> BoxFuture<'_, ()> {
+            self.0.lock().unwrap().extend(records);
+            futures::future::ready(()).boxed()
+        }
+    }
+
+    fn record(method: &str) -> AuditRecord {
+        AuditRecord {
+            request_id: Id::Number(1),
+            method: method.to_string(),
+            caller: None,
+            error_code: None,
+            latency: Duration::from_millis(3),
+            time: Timestamp::now(),
+            params_bytes: 0,
+            params: None,
+            params_digest: None,
+            response_bytes: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn records_are_flushed_after_interval() {
+        let collect = Arc::new(Collect::default());
+        let auditor = Auditor::new(Arc::new(collect.clone()));
+        auditor.record(record("a"));
+        auditor.record(record("b"));
+
+        tokio::time::sleep(FLUSH_INTERVAL / 2).await;
+        assert!(collect.0.lock().unwrap().is_empty());
+
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+        let methods = collect
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.method.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(methods, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn record_params_by_retention() {
+        let mut auditor = Auditor::new(Arc::new(LogSink));
+        let sensitive = Sensitive::default();
+        let key = DigestKey::default();
+        let raw = Some(r#"{"user":"a"}"#);
+        assert_eq!(auditor.params(raw, &sensitive, &key), (None, None));
+
+        auditor.retention = Arc::new(Retention {
+            params: ParamsRetention::Digest,
+            ..Retention::default()
+        });
+        let (params, digest) = auditor.params(raw, &sensitive, &key);
+        assert_eq!(params, None);
+        assert_eq!(digest.unwrap().len(), 64);
+
+        auditor.retention = Arc::new(Retention {
+            params: ParamsRetention::Full,
+            ..Retention::default()
+        });
+        assert_eq!(auditor.params(raw, &sensitive, &key).0.as_deref(), raw);
+    }
+
+    #[tokio::test]
+    async fn retain_lines_by_age_and_size() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", rand::random::<u64>()));
+        let line = |record: &AuditRecord| serde_json::to_string(record).unwrap();
+        let mut old = record("old");
+        old.time = Timestamp(SystemTime::now() - Duration::from_secs(3600));
+        // Whole seconds, so every line is as long.
+        let now = SystemTime::UNIX_EPOCH.elapsed().unwrap().as_secs();
+        let recent = |method| AuditRecord {
+            time: Timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(now)),
+            ..record(method)
+        };
+        let (a, b, c, d) = (recent("a"), recent("b"), recent("c"), recent("d"));
+        fs::write(
+            &path,
+            format!("{}\n{}\n{}\n", line(&old), line(&a), line(&b)),
+        )
+        .unwrap();
+
+        let sink = JsonlSink::new(&path);
+        let retention = Retention {
+            max_age: Some(Duration::from_secs(60)),
+            max_bytes: Some(3 * (line(&a).len() as u64 + 1)),
+            params: ParamsRetention::Omit,
+        };
+        sink.retain(&retention).await;
+        let kept = format!("{}\n{}\n", line(&a), line(&b));
+        assert_eq!(fs::read_to_string(&path).unwrap(), kept);
+
+        // Within the retention, nothing is dropped.
+        sink.write(vec![c.clone()]).await;
+        sink.retain(&retention).await;
+        let kept = format!("{}{}\n", kept, line(&c));
+        assert_eq!(fs::read_to_string(&path).unwrap(), kept);
+
+        sink.write(vec![d.clone()]).await;
+        sink.retain(&retention).await;
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n{}\n", line(&c), line(&d))
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn serialize_record() {
+        let value = serde_json::to_value(record("a")).unwrap();
+        assert_eq!(value["latency_ms"], 3.0);
+        assert_eq!(value["method"], "a");
+    }
+}
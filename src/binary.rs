@@ -0,0 +1,198 @@
+//! Binary parameters encoded as hex or base64 strings.
+//!
+//! `MAX` limits how many (decoded) bytes are accepted. A malformed or too
+//! long string fails deserialization, so it is answered by `INVALID_PARAMS`
+//! error describing the reason.
+//!
+//! ```
+//! use warp_json_rpc::{binary::HexBytes, Error, RpcRouter};
+//!
+//! let mut router = RpcRouter::new();
+//! router.method("send_raw", |(tx,): (HexBytes<4096>,)| async move {
+//!     Ok::<_, Error>(tx.len())
+//! });
+//! ```
+use bytes::Bytes;
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize, Serialize, Serializer,
+};
+use std::{fmt, ops::Deref};
+
+/// Bytes encoded as a hex string, optionally prefixed by `0x`.
+///
+/// Serialized as lowercase hex prefixed by `0x`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct HexBytes<const MAX: usize = { usize::MAX }>(pub Bytes);
+
+/// Bytes encoded as a (standard, padded) base64 string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Base64Bytes<const MAX: usize = { usize::MAX }>(pub Bytes);
+
+macro_rules! bytes_wrapper {
+    ($name:ident, $expecting:expr, $decode:expr, $encode:expr) => {
+        impl<const MAX: usize> $name<MAX> {
+            pub fn into_bytes(self) -> Bytes {
+                self.0
+            }
+        }
+
+        impl<const MAX: usize> Deref for $name<MAX> {
+            type Target = [u8];
+
+            fn deref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl<const MAX: usize> From<Bytes> for $name<MAX> {
+            fn from(bytes: Bytes) -> Self {
+                $name(bytes)
+            }
+        }
+
+        impl<const MAX: usize> Serialize for $name<MAX> {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                let encode: fn(&[u8]) -> String = $encode;
+                serializer.serialize_str(&encode(&self.0))
+            }
+        }
+
+        impl<'de, const MAX: usize> Deserialize<'de> for $name<MAX> {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                struct StrVisitor<const MAX: usize>;
+
+                impl<'de, const MAX: usize> Visitor<'de> for StrVisitor<MAX> {
+                    type Value = $name<MAX>;
+
+                    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        f.write_str($expecting)
+                    }
+
+                    fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+                    where
+                        E: de::Error,
+                    {
+                        let decode: fn(&str, usize) -> Result<Vec<u8>, String> = $decode;
+                        let bytes = decode(s, MAX).map_err(E::custom)?;
+                        Ok($name(Bytes::from(bytes)))
+                    }
+                }
+
+                deserializer.deserialize_str(StrVisitor::<MAX>)
+            }
+        }
+    };
+}
+
+bytes_wrapper!(HexBytes, "a hex string", decode_hex, encode_hex);
+bytes_wrapper!(Base64Bytes, "a base64 string", decode_base64, |bytes| {
+    base64::encode(bytes)
+});
+
+fn too_long(len: usize, max: usize) -> String {
+    format!("expected at most {} bytes, got {}", max, len)
+}
+
+fn decode_hex(s: &str, max: usize) -> Result<Vec<u8>, String> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    if digits.len() % 2 != 0 {
+        return Err("hex string has an odd number of digits".to_string());
+    }
+    if digits.len() / 2 > max {
+        return Err(too_long(digits.len() / 2, max));
+    }
+
+    let offset = s.len() - digits.len();
+    let digit = |i: usize| {
+        let c = digits.as_bytes()[i];
+        (c as char).to_digit(16).ok_or_else(|| {
+            format!(
+                "invalid hex character {:?} at position {}",
+                c as char,
+                offset + i
+            )
+        })
+    };
+    (0..digits.len() / 2)
+        .map(|i| Ok((digit(2 * i)? << 4 | digit(2 * i + 1)?) as u8))
+        .collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut s = String::with_capacity(2 + bytes.len() * 2);
+    s.push_str("0x");
+    for b in bytes {
+        s.push(DIGITS[(b >> 4) as usize] as char);
+        s.push(DIGITS[(b & 0xf) as usize] as char);
+    }
+    s
+}
+
+fn decode_base64(s: &str, max: usize) -> Result<Vec<u8>, String> {
+    // Checked before decoding, so oversized input is not decoded at all.
+    let len = s.len() / 4 * 3;
+    if len.saturating_sub(2) > max {
+        return Err(too_long(len, max));
+    }
+    let bytes = base64::decode(s).map_err(|e| format!("invalid base64: {}", e))?;
+    if bytes.len() > max {
+        return Err(too_long(bytes.len(), max));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse<T>(s: &str) -> Result<T, String>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        serde_json::from_str::<T>(s).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn hex_bytes() {
+        let bytes = parse::<HexBytes>(r#""0x00ff10""#).unwrap();
+        assert_eq!(&*bytes, &[0x00, 0xff, 0x10]);
+        assert_eq!(parse::<HexBytes>(r#""ABcd""#).unwrap().0, vec![0xab, 0xcd]);
+        assert_eq!(serde_json::to_string(&bytes).unwrap(), r#""0x00ff10""#);
+
+        assert!(parse::<HexBytes>(r#""0x0""#)
+            .unwrap_err()
+            .contains("odd number of digits"));
+        assert!(parse::<HexBytes>(r#""0x0g""#)
+            .unwrap_err()
+            .contains("invalid hex character 'g' at position 3"));
+        assert!(parse::<HexBytes<2>>(r#""0x000000""#)
+            .unwrap_err()
+            .contains("expected at most 2 bytes, got 3"));
+    }
+
+    #[test]
+    fn base64_bytes() {
+        let bytes = parse::<Base64Bytes>(r#""aGVsbG8=""#).unwrap();
+        assert_eq!(&*bytes, b"hello");
+        assert_eq!(serde_json::to_string(&bytes).unwrap(), r#""aGVsbG8=""#);
+
+        assert!(parse::<Base64Bytes>(r#""!!!!""#)
+            .unwrap_err()
+            .contains("invalid base64"));
+        assert!(parse::<Base64Bytes<4>>(r#""aGVsbG8=""#)
+            .unwrap_err()
+            .contains("expected at most 4 bytes, got 5"));
+        assert!(parse::<Base64Bytes<5>>(r#""aGVsbG8=""#).is_ok());
+    }
+}
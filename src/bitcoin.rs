@@ -0,0 +1,238 @@
+//! Serve a router as Bitcoin Core does its JSON RPC API.
+//!
+//! [`RpcRouter::into_bitcoin_filter`] differs from
+//! [`RpcRouter::into_filter`] in that
+//!
+//! - calls are authenticated by HTTP basic auth, if [`BasicAuth`] is given,
+//! - requests may be JSON RPC 1.0 ones, which have no `jsonrpc` field or
+//!   have `"1.0"`, and may omit `id`,
+//! - responses have both `result` and `error`, one of them `null`, and no
+//!   `jsonrpc` field,
+//! - errors are sent with `404 Not Found` for unknown methods, `400 Bad
+//!   Request` for malformed requests and `500 Internal Server Error` for the
+//!   others.
+//!
+//! ```
+//! use warp_json_rpc::{bitcoin::BasicAuth, Error, RpcRouter};
+//! use serde_json::Value;
+//! use warp::Filter as _;
+//!
+//! let mut router = RpcRouter::new();
+//! // Bitcoin clients send `[]` even when there is no parameter.
+//! router.method("getblockcount", |_: Vec<Value>| async move {
+//!     Ok::<_, Error>(800_000)
+//! });
+//! let standard = warp::path("rpc").and(router.clone().into_filter());
+//! let auth = BasicAuth::new("user", "pass");
+//! let bitcoin = warp::path::end().and(router.into_bitcoin_filter(Some(auth)));
+//! ```
+//!
+//! [`RpcRouter::into_bitcoin_filter`]: ../struct.RpcRouter.html#method.into_bitcoin_filter
+//! [`RpcRouter::into_filter`]: ../struct.RpcRouter.html#method.into_filter
+//! [`BasicAuth`]: ./struct.BasicAuth.html
+use crate::{req::Id, Builder, Error, Request, RpcRouter, StatusMapping};
+use http::{
+    header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, WWW_AUTHENTICATE},
+    HeaderMap, HeaderValue, StatusCode,
+};
+use hyper::Body;
+use serde_json::{Map, Value};
+use std::{net::SocketAddr, sync::Arc};
+use warp::{hyper::body::Bytes, reply::Response, Filter, Rejection};
+
+/// Credentials of HTTP basic auth, as set by `rpcuser` and `rpcpassword` of
+/// Bitcoin Core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicAuth {
+    user: String,
+    password: String,
+}
+
+impl BasicAuth {
+    pub fn new(user: impl Into<String>, password: impl Into<String>) -> BasicAuth {
+        BasicAuth {
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+
+    fn accepts(&self, headers: &HeaderMap) -> bool {
+        let credentials = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Basic "))
+            .and_then(|encoded| base64::decode(encoded.trim()).ok())
+            .and_then(|decoded| String::from_utf8(decoded).ok());
+        match credentials.as_deref().and_then(|c| c.split_once(':')) {
+            Some((user, password)) => user == self.user && password == self.password,
+            None => false,
+        }
+    }
+}
+
+/// The status mapping of Bitcoin Core.
+pub fn status_mapping() -> StatusMapping {
+    StatusMapping::new(|error: &Error| match error.code {
+        code if code == Error::METHOD_NOT_FOUND.code => StatusCode::NOT_FOUND,
+        code if code == Error::INVALID_REQUEST.code || code == Error::PARSE_ERROR.code => {
+            StatusCode::BAD_REQUEST
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    })
+}
+
+impl RpcRouter {
+    /// Create a `Filter` that dispatches requests in the conventions of
+    /// Bitcoin Core, described in the [`bitcoin`] module.
+    ///
+    /// [`bitcoin`]: ./bitcoin/index.html
+    pub fn into_bitcoin_filter(
+        mut self,
+        auth: Option<BasicAuth>,
+    ) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+        self.register_builtins();
+        let router = Arc::new(self);
+        let auth = Arc::new(auth);
+        warp::post()
+            .and(warp::header::headers_cloned())
+            .and(warp::addr::remote())
+            .and(warp::body::bytes())
+            .and_then(move |headers: HeaderMap, remote, body: Bytes| {
+                let (router, auth) = (router.clone(), auth.clone());
+                async move {
+                    if let Some(auth) = auth.as_ref() {
+                        if !auth.accepts(&headers) {
+                            return Ok::<_, Rejection>(unauthorized());
+                        }
+                    }
+                    Ok(dispatch(&router, &body, headers, remote).await)
+                }
+            })
+    }
+}
+
+fn unauthorized() -> Response {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response.headers_mut().insert(
+        WWW_AUTHENTICATE,
+        HeaderValue::from_static("Basic realm=\"jsonrpc\""),
+    );
+    response
+}
+
+async fn dispatch(
+    router: &Arc<RpcRouter>,
+    body: &[u8],
+    headers: HeaderMap,
+    remote: Option<SocketAddr>,
+) -> Response {
+    let mut req = match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Object(req)) => req,
+        Ok(_) => return error(Id::Null, Error::INVALID_REQUEST).await,
+        Err(_) => return error(Id::Null, Error::PARSE_ERROR).await,
+    };
+    match req.get("jsonrpc").and_then(Value::as_str) {
+        None | Some("1.0") | Some("2.0") => {}
+        Some(_) => return error(Id::Null, Error::INVALID_REQUEST).await,
+    }
+    req.insert("jsonrpc".to_string(), Value::from("2.0"));
+    req.entry("id").or_insert(Value::Null);
+    let id = serde_json::from_value::<Id>(req["id"].clone()).unwrap_or(Id::Null);
+    let req = match serde_json::from_str::<Request>(&Value::Object(req).to_string()) {
+        Ok(req) => req,
+        Err(_) => return error(id, Error::INVALID_REQUEST).await,
+    };
+
+    let res = Builder::new(id.clone(), status_mapping());
+    match router.dispatch(res, req, headers, remote).await {
+        Ok(response) => envelope(response).await,
+        Err(_) => error(id, Error::METHOD_NOT_FOUND).await,
+    }
+}
+
+async fn error(id: Id, error: Error) -> Response {
+    let response = Builder::new(id, status_mapping())
+        .error(error)
+        .expect("errors without data are always serializable");
+    envelope(response).await
+}
+
+/// Rewrite a JSON RPC 2.0 response into a 1.0 one.
+async fn envelope(response: Response) -> Response {
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map_or(false, |value| value == "application/json");
+    if !is_json {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!(target: "warp_json_rpc", "Failed to read response: {}", e);
+            return Response::new(Body::empty());
+        }
+    };
+    let mut res = match serde_json::from_slice::<Map<String, Value>>(&body) {
+        Ok(res) => res,
+        Err(_) => return Response::from_parts(parts, Body::from(body)),
+    };
+    res.remove("jsonrpc");
+    res.entry("result").or_insert(Value::Null);
+    res.entry("error").or_insert(Value::Null);
+    let body = Value::Object(res).to_string();
+    parts
+        .headers
+        .insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    async fn call(auth: Option<&str>, body: &str) -> (StatusCode, Value) {
+        let mut router = RpcRouter::new();
+        router
+            .method("getblockcount", |_: Vec<Value>| async move {
+                Ok::<_, Error>(800_000)
+            })
+            .method("fail", |(): ()| async move {
+                Err::<(), _>(Error::custom(-8, "failed"))
+            });
+        let mut req = warp::test::request().method("POST").body(body);
+        if let Some(auth) = auth {
+            req = req.header("Authorization", format!("Basic {}", base64::encode(auth)));
+        }
+        let res = req
+            .filter(&router.into_bitcoin_filter(Some(BasicAuth::new("user", "pass"))))
+            .await
+            .ok()
+            .unwrap();
+        let status = res.status();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn bitcoin_conventions() {
+        let (status, _) = call(Some("user:wrong"), r#"{"method": "getblockcount"}"#).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let body = r#"{"jsonrpc": "1.0", "method": "getblockcount", "params": [], "id": "c"}"#;
+        let (status, res) = call(Some("user:pass"), body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(res, json!({ "result": 800_000, "error": null, "id": "c" }));
+
+        let (status, res) = call(Some("user:pass"), r#"{"method": "fail", "id": 1}"#).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(res["result"], Value::Null);
+        assert_eq!(res["error"]["code"], -8);
+
+        let (status, _) = call(Some("user:pass"), r#"{"method": "nope", "id": 1}"#).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
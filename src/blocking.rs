@@ -0,0 +1,138 @@
+use crate::{Error, RpcRouter};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// A limit of blocking handlers running at once.
+///
+/// Handlers registered by [`RpcRouter::blocking_in`] with the same pool
+/// share the limit, and calls beyond it wait for a running one to finish.
+/// This keeps one kind of heavy work from occupying all threads of the
+/// blocking thread pool.
+///
+/// [`RpcRouter::blocking_in`]: ./struct.RpcRouter.html#method.blocking_in
+#[derive(Debug, Clone)]
+pub struct BlockingPool {
+    permits: Arc<Semaphore>,
+}
+
+impl BlockingPool {
+    /// Run at most `size` handlers at once.
+    pub fn new(size: usize) -> BlockingPool {
+        BlockingPool {
+            permits: Arc::new(Semaphore::new(size)),
+        }
+    }
+}
+
+impl RpcRouter {
+    /// Register a synchronous handler for the RPC method, which is run on
+    /// the blocking thread pool of tokio.
+    ///
+    /// Use this for CPU heavy or synchronous IO handlers, which would stall
+    /// other requests if they ran on the async runtime.
+    #[track_caller]
+    pub fn blocking<P, R, F>(&mut self, name: impl Into<String>, handler: F) -> &mut RpcRouter
+    where
+        for<'de> P: Deserialize<'de> + Send + 'static,
+        R: Serialize + Send + 'static,
+        F: Fn(P) -> Result<R, Error> + Send + Sync + 'static,
+    {
+        self.blocking_with(name, None, handler)
+    }
+
+    /// Same as [`blocking`], but run at most as many handlers at once as
+    /// `pool` allows.
+    ///
+    /// [`blocking`]: #method.blocking
+    #[track_caller]
+    pub fn blocking_in<P, R, F>(
+        &mut self,
+        name: impl Into<String>,
+        pool: &BlockingPool,
+        handler: F,
+    ) -> &mut RpcRouter
+    where
+        for<'de> P: Deserialize<'de> + Send + 'static,
+        R: Serialize + Send + 'static,
+        F: Fn(P) -> Result<R, Error> + Send + Sync + 'static,
+    {
+        self.blocking_with(name, Some(pool.permits.clone()), handler)
+    }
+
+    #[track_caller]
+    fn blocking_with<P, R, F>(
+        &mut self,
+        name: impl Into<String>,
+        permits: Option<Arc<Semaphore>>,
+        handler: F,
+    ) -> &mut RpcRouter
+    where
+        for<'de> P: Deserialize<'de> + Send + 'static,
+        R: Serialize + Send + 'static,
+        F: Fn(P) -> Result<R, Error> + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.method(name, move |params: P| {
+            let handler = handler.clone();
+            let permits = permits.clone();
+            async move {
+                let _permit = match permits {
+                    Some(permits) => Some(
+                        permits
+                            .acquire_owned()
+                            .await
+                            .map_err(|_| Error::INTERNAL_ERROR)?,
+                    ),
+                    None => None,
+                };
+                match tokio::task::spawn_blocking(move || handler(params)).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        log::error!(target: "warp_json_rpc", "Blocking handler failed: {}", e);
+                        Err(Error::INTERNAL_ERROR)
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Context, Id, Params};
+    use http::HeaderMap;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pool_limits_running_handlers() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        let mut router = RpcRouter::new();
+        router.blocking_in("work", &BlockingPool::new(2), {
+            let (running, max) = (running.clone(), max.clone());
+            move |(): ()| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(now)
+            }
+        });
+
+        let method = router.methods.get("work").unwrap();
+        let calls = (0..6).map(|_| {
+            let ctx = Context::from_parts(Id::Null, "work", HeaderMap::new());
+            method.call(Params::from_raw(None), ctx)
+        });
+        for result in futures::future::join_all(calls).await {
+            assert!(result.ok().unwrap().as_u64().unwrap() <= 2);
+        }
+        assert_eq!(max.load(Ordering::SeqCst), 2);
+    }
+}
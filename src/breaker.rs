@@ -0,0 +1,132 @@
+use crate::{quota, Error, RpcRouter};
+use http::HeaderMap;
+use serde::Serialize;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Error code returned while the circuit breaker of a method is open.
+pub const UNAVAILABLE_CODE: i64 = -32009;
+
+impl RpcRouter {
+    /// Stop calling the handler of the method for `cool_down` after it fails
+    /// `failures` times in a row.
+    ///
+    /// While the breaker is open, calls are answered by `UNAVAILABLE_CODE`
+    /// error whose `data` is an [`Unavailable`], without calling the handler.
+    /// The response also has a `Retry-After` header.
+    /// After the cool-down, calls reach the handler again, and the breaker
+    /// opens again on the first failure until a call succeeds.
+    ///
+    /// `INVALID_PARAMS` errors are not counted as failures, since they are
+    /// caused by the caller.
+    ///
+    /// [`Unavailable`]: ./struct.Unavailable.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn circuit_breaker(
+        &mut self,
+        name: &str,
+        failures: u32,
+        cool_down: Duration,
+    ) -> &mut RpcRouter {
+        self.registered(name).breaker = Some(Arc::new(Breaker {
+            failures,
+            cool_down,
+            state: Mutex::new(State::default()),
+        }));
+        self
+    }
+}
+
+/// `data` of the error answered while a circuit breaker is open.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Unavailable {
+    /// Seconds until the handler is called again.
+    pub retry_after: u64,
+}
+
+impl Unavailable {
+    pub(crate) fn insert_headers(&self, headers: &mut HeaderMap) {
+        quota::insert_retry_after(headers, self.retry_after);
+    }
+}
+
+impl From<Unavailable> for Error {
+    fn from(unavailable: Unavailable) -> Error {
+        Error::custom(UNAVAILABLE_CODE, "Temporarily unavailable").with_data(unavailable)
+    }
+}
+
+pub(crate) struct Breaker {
+    failures: u32,
+    cool_down: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    consecutive: u32,
+    open_until: Option<Instant>,
+}
+
+impl Breaker {
+    /// Fail if the breaker is open.
+    pub(crate) fn check(&self) -> Result<(), Unavailable> {
+        let state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(until) if until > Instant::now() => {
+                let remaining = until - Instant::now();
+                let retry_after = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+                Err(Unavailable { retry_after })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn record<T>(&self, result: &Result<T, Error>) {
+        let mut state = self.state.lock().unwrap();
+        match result {
+            Err(e) if e.code != Error::INVALID_PARAMS.code => {
+                state.consecutive = state.consecutive.saturating_add(1);
+                if state.consecutive >= self.failures {
+                    state.open_until = Some(Instant::now() + self.cool_down);
+                }
+            }
+            Err(_) => {}
+            Ok(_) => *state = State::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = Breaker {
+            failures: 2,
+            cool_down: Duration::from_millis(20),
+            state: Mutex::new(State::default()),
+        };
+        let failed = Err::<(), _>(Error::INTERNAL_ERROR);
+
+        breaker.record(&failed);
+        breaker.record(&Ok(()));
+        breaker.record(&failed);
+        assert!(breaker.check().is_ok());
+        breaker.record(&failed);
+        let unavailable = breaker.check().err().unwrap();
+        assert_eq!(unavailable.retry_after, 1);
+        assert_eq!(Error::from(unavailable).code, UNAVAILABLE_CODE);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.check().is_ok());
+        breaker.record(&failed);
+        assert!(breaker.check().is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{Error, RpcRouter};
+use futures::future::FutureExt as _;
+use serde::Serialize;
+use serde_json::Value;
+use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+
+/// Name of the method registered by [`RpcRouter::rpc_errors`].
+///
+/// [`RpcRouter::rpc_errors`]: ./struct.RpcRouter.html#method.rpc_errors
+pub const RPC_ERRORS: &str = "rpc_errors";
+
+/// A custom error code registered by [`RpcRouter::error`].
+///
+/// [`RpcRouter::error`]: ./struct.RpcRouter.html#method.error
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorInfo {
+    pub code: i64,
+    pub name: Cow<'static, str>,
+    pub description: Cow<'static, str>,
+}
+
+impl RpcRouter {
+    /// Register a custom error code which handlers may return.
+    ///
+    /// Once any code is registered, an error whose code is neither
+    /// registered, defined by the JSON RPC specification nor answered by this
+    /// crate itself (such as `QUOTA_EXCEEDED_CODE`) is logged. It is also
+    /// replaced by `INTERNAL_ERROR` error if [`strict_errors`] is called.
+    ///
+    /// An error already registered with the code is replaced.
+    ///
+    /// [`strict_errors`]: #method.strict_errors
+    pub fn error<N, D>(&mut self, code: i64, name: N, description: D) -> &mut RpcRouter
+    where
+        Cow<'static, str>: From<N> + From<D>,
+    {
+        let info = ErrorInfo {
+            code,
+            name: name.into(),
+            description: description.into(),
+        };
+        self.errors.entries.insert(code, info);
+        self
+    }
+
+    /// Replace errors with unregistered codes by `INTERNAL_ERROR` error,
+    /// carrying the original code as data, instead of only logging them.
+    ///
+    /// This has no effect until a code is registered by [`error`].
+    ///
+    /// [`error`]: #method.error
+    pub fn strict_errors(&mut self) -> &mut RpcRouter {
+        self.errors.strict = true;
+        self
+    }
+
+    /// Register the `rpc_errors` method, which lists the registered errors
+    /// ordered by code, along with their [`Retryability`].
+    ///
+    /// [`Retryability`]: ./enum.Retryability.html
+    ///
+    /// The list is taken when the router is turned into a filter, so errors
+    /// may be registered after calling this.
+    pub fn rpc_errors(&mut self) -> &mut RpcRouter {
+        self.errors.listed = true;
+        self
+    }
+
+    pub(crate) fn register_rpc_errors(&mut self) {
+        if !self.errors.listed {
+            return;
+        }
+        let list = self
+            .errors
+            .entries
+            .values()
+            .map(|info| {
+                let mut entry =
+                    serde_json::to_value(info).expect("ErrorInfo is always serializable");
+                let retryability = self.classify(None, Some(info.code));
+                entry["retryability"] = serde_json::to_value(retryability)
+                    .expect("Retryability is always serializable");
+                entry
+            })
+            .collect::<Vec<_>>();
+        let list = serde_json::Value::Array(list);
+        self.register(
+            RPC_ERRORS,
+            Arc::new(move |_, _| futures::future::ready(Ok(list.clone())).boxed()),
+        );
+    }
+}
+
+/// Codes of the errors this crate answers by itself or offers helpers for,
+/// that is every public `*_CODE` constant of the crate.
+const BUILTIN_CODES: &[i64] = &[
+    crate::UNAUTHORIZED_CODE,
+    crate::QUOTA_EXCEEDED_CODE,
+    crate::DEADLINE_EXCEEDED_CODE,
+    crate::UNAVAILABLE_CODE,
+    crate::IDEMPOTENCY_IN_FLIGHT_CODE,
+    crate::transaction::TRANSACTION_ABORTED_CODE,
+    crate::graphql::QUERY_ERROR_CODE,
+    crate::lsp::SERVER_NOT_INITIALIZED_CODE,
+    crate::lsp::REQUEST_CANCELLED_CODE,
+    #[cfg(feature = "eth")]
+    crate::eth::INVALID_INPUT_CODE,
+    #[cfg(feature = "eth")]
+    crate::eth::RESOURCE_NOT_FOUND_CODE,
+    #[cfg(feature = "eth")]
+    crate::eth::RESOURCE_UNAVAILABLE_CODE,
+    #[cfg(feature = "eth")]
+    crate::eth::TRANSACTION_REJECTED_CODE,
+    #[cfg(feature = "eth")]
+    crate::eth::METHOD_NOT_SUPPORTED_CODE,
+    #[cfg(feature = "eth")]
+    crate::eth::LIMIT_EXCEEDED_CODE,
+    #[cfg(feature = "eth")]
+    crate::eth::VERSION_NOT_SUPPORTED_CODE,
+    #[cfg(feature = "eth")]
+    crate::eth::EXECUTION_REVERTED_CODE,
+];
+
+#[derive(Clone, Default)]
+pub(crate) struct ErrorCatalog {
+    entries: BTreeMap<i64, ErrorInfo>,
+    listed: bool,
+    strict: bool,
+}
+
+impl ErrorCatalog {
+    /// Log an error with an unregistered code, and replace it by
+    /// `INTERNAL_ERROR` if the catalog is strict.
+    pub(crate) fn check(&self, method: &str, result: Result<Value, Error>) -> Result<Value, Error> {
+        match result {
+            Err(error) if !self.allows(error.code) => {
+                log::error!(
+                    target: "warp_json_rpc",
+                    "\"{}\" RPC returned unregistered error code {}",
+                    method,
+                    error.code
+                );
+                if self.strict {
+                    Err(Error::INTERNAL_ERROR.with_data(error.code))
+                } else {
+                    Err(error)
+                }
+            }
+            result => result,
+        }
+    }
+
+    fn allows(&self, code: i64) -> bool {
+        self.entries.is_empty()
+            || self.entries.contains_key(&code)
+            || BUILTIN_CODES.contains(&code)
+            || code == Error::PARSE_ERROR.code
+            || (Error::INTERNAL_ERROR.code..=Error::INVALID_REQUEST.code).contains(&code)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn allows_registered_and_predefined_codes() {
+        let mut router = RpcRouter::new();
+        assert!(router.errors.allows(1));
+
+        router.error(1, "InsufficientFunds", "The account balance is too low");
+        assert!(router.errors.allows(1));
+        assert!(router.errors.allows(Error::INVALID_PARAMS.code));
+        assert!(!router.errors.allows(2));
+        assert!(router.errors.allows(crate::QUOTA_EXCEEDED_CODE));
+        assert!(router.errors.allows(crate::DEADLINE_EXCEEDED_CODE));
+        assert!(router.errors.allows(crate::lsp::REQUEST_CANCELLED_CODE));
+        #[cfg(feature = "eth")]
+        for code in -32006..=-32000 {
+            assert!(router.errors.allows(code), "{}", code);
+        }
+
+        let result = router.errors.check("pay", Err(Error::custom(2, "unknown")));
+        assert_eq!(result.err().map(|e| e.code), Some(2));
+
+        router.strict_errors();
+        let result = router.errors.check("pay", Err(Error::custom(2, "unknown")));
+        assert_eq!(
+            result.err().map(|e| e.code),
+            Some(Error::INTERNAL_ERROR.code)
+        );
+        let result = router.errors.check("pay", Err(Error::custom(1, "known")));
+        assert_eq!(result.err().map(|e| e.code), Some(1));
+    }
+}
@@ -0,0 +1,171 @@
+//! Streams of notifications from `tokio` channels.
+//!
+//! The adapters turn receivers of an existing event bus into streams which can
+//! be returned by streaming and chunked methods, and [`long_poll`] waits for a
+//! single event in methods answered once.
+//!
+//! ```
+//! use warp_json_rpc::{channel, RpcRouter};
+//! use tokio::sync::broadcast;
+//!
+//! let (tx, _) = broadcast::channel::<String>(16);
+//!
+//! let mut router = RpcRouter::new();
+//! router.streaming("logs_subscribe", move |(): ()| channel::broadcast(tx.subscribe()));
+//! ```
+//!
+//! [`long_poll`]: ./fn.long_poll.html
+use crate::Error;
+use futures::{
+    future::Future,
+    stream::{self, Stream},
+};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::{
+    broadcast::{self as broadcast_channel, error::RecvError},
+    watch as watch_channel,
+};
+
+/// An item of a notification stream.
+///
+/// Serialized as the event itself, or as `{ "missed_events": <count> }`
+/// when the receiver lagged behind and events were dropped.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Notification<T> {
+    Event(T),
+    Missed { missed_events: u64 },
+}
+
+/// Stream events of a `broadcast` receiver until all senders are dropped.
+///
+/// Events dropped because the receiver lagged are notified by
+/// `Notification::Missed`.
+pub fn broadcast<T>(
+    rx: broadcast_channel::Receiver<T>,
+) -> impl Stream<Item = Result<Notification<T>, Error>> + Send + 'static
+where
+    T: Clone + Send + 'static,
+{
+    stream::unfold(rx, |mut rx| async move {
+        let notification = match rx.recv().await {
+            Ok(event) => Notification::Event(event),
+            Err(RecvError::Lagged(missed)) => Notification::Missed {
+                missed_events: missed,
+            },
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok(notification), rx))
+    })
+}
+
+/// Stream the current value of a `watch` receiver and every change of it,
+/// until the sender is dropped.
+///
+/// Intermediate values changed faster than they are streamed are skipped, as
+/// a `watch` channel keeps only the latest value.
+pub fn watch<T>(
+    rx: watch_channel::Receiver<T>,
+) -> impl Stream<Item = Result<Notification<T>, Error>> + Send + 'static
+where
+    T: Clone + Send + Sync + 'static,
+{
+    stream::unfold((rx, true), |(mut rx, first)| async move {
+        if !first && rx.changed().await.is_err() {
+            return None;
+        }
+        let value = rx.borrow().clone();
+        Some((Ok(Notification::Event(value)), (rx, false)))
+    })
+}
+
+/// Wait for `event` at most `max_wait`, and return `None` if it does not
+/// come in time, which a long-poll method answers by `null`.
+///
+/// If the client goes away while waiting, the call is dropped along with
+/// `event`, which releases e.g. a receiver held by it.
+///
+/// ```
+/// # use warp_json_rpc::{channel, Error, RpcRouter};
+/// use std::time::Duration;
+/// use tokio::sync::broadcast;
+///
+/// let (tx, _) = broadcast::channel::<String>(16);
+/// let mut router = RpcRouter::new();
+/// router.method("messages_poll", move |(): ()| {
+///     let mut rx = tx.subscribe();
+///     async move {
+///         let message = channel::long_poll(Duration::from_secs(30), rx.recv()).await;
+///         Ok::<_, Error>(message.and_then(Result::ok))
+///     }
+/// });
+/// ```
+pub async fn long_poll<F>(max_wait: Duration, event: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    tokio::time::timeout(max_wait, event).await.ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::stream::StreamExt as _;
+
+    #[tokio::test]
+    async fn broadcast_missed_events() {
+        let (tx, rx) = broadcast_channel::channel(2);
+        for n in 0..4 {
+            tx.send(n).unwrap();
+        }
+        drop(tx);
+
+        let items = broadcast(rx)
+            .map(|item| item.ok().unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            items,
+            vec![
+                Notification::Missed { missed_events: 2 },
+                Notification::Event(2),
+                Notification::Event(3),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&items[0]).unwrap(),
+            serde_json::json!({ "missed_events": 2 })
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_changes() {
+        let (tx, rx) = watch_channel::channel(0);
+        let values = watch(rx);
+        futures::pin_mut!(values);
+
+        assert_eq!(
+            values.next().await.unwrap().ok(),
+            Some(Notification::Event(0))
+        );
+        tx.send(1).unwrap();
+        assert_eq!(
+            values.next().await.unwrap().ok(),
+            Some(Notification::Event(1))
+        );
+        drop(tx);
+        assert!(values.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn long_poll_or_time_out() {
+        let (tx, mut rx) = watch_channel::channel(0);
+        let wait = Duration::from_millis(10);
+        assert_eq!(long_poll(wait, rx.changed()).await.map(|r| r.is_ok()), None);
+
+        tx.send(1).unwrap();
+        assert!(long_poll(wait, rx.changed()).await.unwrap().is_ok());
+        assert_eq!(*rx.borrow(), 1);
+    }
+}
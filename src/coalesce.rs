@@ -0,0 +1,117 @@
+use crate::{outcome::Outcome, Error, RpcRouter};
+use futures::future::{BoxFuture, FutureExt as _, Shared};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+impl RpcRouter {
+    /// Share one handler call among identical calls of the method in flight.
+    ///
+    /// While a call is running, calls of the same method with the same
+    /// parameter (compared by their JSON text) wait for it and receive its
+    /// outcome instead of calling the handler again. This cuts load during
+    /// thundering herds.
+    ///
+    /// Only use this for read-only methods.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn coalesce(&mut self, name: &str) -> &mut RpcRouter {
+        self.registered(name).coalesced = true;
+        self
+    }
+}
+
+type Key = (String, String);
+type Call = Shared<BoxFuture<'static, Outcome>>;
+
+#[derive(Default)]
+pub(crate) struct Coalescer {
+    next_id: AtomicU64,
+    in_flight: Mutex<HashMap<Key, (u64, Call)>>,
+}
+
+impl Coalescer {
+    /// Run the call made by `call`, or join the identical call in flight.
+    pub(crate) async fn run<F>(&self, method: &str, params: &str, call: F) -> Result<Value, Error>
+    where
+        F: FnOnce() -> BoxFuture<'static, Result<Value, Error>>,
+    {
+        let key = (method.to_string(), params.to_string());
+        let (id, shared) = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some((id, shared)) => {
+                    log::debug!(target: "warp_json_rpc", "Join \"{}\" RPC in flight", method);
+                    (*id, shared.clone())
+                }
+                None => {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    let shared = call()
+                        .map(|result| Outcome::from_result(&result))
+                        .boxed()
+                        .shared();
+                    in_flight.insert(key.clone(), (id, shared.clone()));
+                    (id, shared)
+                }
+            }
+        };
+
+        let outcome = shared.await;
+
+        // Whoever finishes first removes the entry, since the caller which
+        // started the call may have gone away.
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.get(&key).map(|(current, _)| *current) == Some(id) {
+            in_flight.remove(&key);
+        }
+        outcome.into_result()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::future;
+    use std::sync::{atomic::AtomicUsize, Arc};
+
+    #[tokio::test]
+    async fn identical_calls_share_one_call() {
+        let coalescer = Coalescer::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = futures::channel::oneshot::channel::<()>();
+        let rx = rx.shared();
+
+        let call = || {
+            let calls = calls.clone();
+            let rx = rx.clone();
+            move || {
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    rx.await.unwrap();
+                    Ok(Value::from(42))
+                }
+                .boxed()
+            }
+        };
+
+        let first = coalescer.run("get", "[1]", call());
+        let second = coalescer.run("get", "[1]", call());
+        let other = coalescer.run("get", "[2]", call());
+        // Polled after the calls above are started.
+        let release = async move { tx.send(()).unwrap() };
+        let (first, second, other, ()) = future::join4(first, second, other, release).await;
+
+        assert_eq!(first.ok(), Some(Value::from(42)));
+        assert_eq!(second.ok(), Some(Value::from(42)));
+        assert_eq!(other.ok(), Some(Value::from(42)));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(coalescer.in_flight.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,253 @@
+//! Generate Rust code for methods described by an [OpenRPC] document.
+//!
+//! [`generate`] turns a document into a trait with a function per method,
+//! and a `register` function registering an implementation of the trait to
+//! [`RpcRouter`]. It is meant to be called from a build script:
+//!
+//! ```no_run
+//! // build.rs
+//! let document = std::fs::read_to_string("openrpc.json").unwrap();
+//! let document = serde_json::from_str(&document).unwrap();
+//! let code = warp_json_rpc::codegen::generate(&document, "Calculator").unwrap();
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("calculator.rs");
+//! std::fs::write(out, code).unwrap();
+//! ```
+//!
+//! Parameters are received by position. Types are derived from the `type`
+//! keyword of the schemas; other schemas are received as
+//! `serde_json::Value`.
+//!
+//! [OpenRPC]: https://spec.open-rpc.org/
+//! [`generate`]: ./fn.generate.html
+//! [`RpcRouter`]: ../struct.RpcRouter.html
+use anyhow::anyhow;
+use serde_json::Value;
+use std::fmt::Write as _;
+
+/// Generate the trait named `trait_name` and its `register` function from
+/// the OpenRPC `document`.
+pub fn generate(document: &Value, trait_name: &str) -> anyhow::Result<String> {
+    let methods = document["methods"]
+        .as_array()
+        .ok_or_else(|| anyhow!("`methods` of the document is not an array"))?
+        .iter()
+        .map(Method::parse)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut code = String::new();
+    writeln!(code, "// Generated from an OpenRPC document. Do not edit.")?;
+    writeln!(code)?;
+    writeln!(code, "pub type RpcFuture<T> = ::std::pin::Pin<")?;
+    writeln!(
+        code,
+        "    Box<dyn ::std::future::Future<Output = Result<T, ::warp_json_rpc::Error>> + Send>,"
+    )?;
+    writeln!(code, ">;")?;
+    writeln!(code)?;
+    if let Some(title) = document["info"]["title"].as_str() {
+        writeln!(code, "/// {}", title)?;
+    }
+    writeln!(code, "pub trait {}: Send + Sync + 'static {{", trait_name)?;
+    for method in &methods {
+        if let Some(description) = method.description.as_ref() {
+            writeln!(code, "    /// {}", description)?;
+        }
+        let params = method
+            .params
+            .iter()
+            .map(|(name, ty)| format!(", {}: {}", name, ty))
+            .collect::<String>();
+        writeln!(
+            code,
+            "    fn {}(&self{}) -> RpcFuture<{}>;",
+            method.ident, params, method.result
+        )?;
+    }
+    writeln!(code, "}}")?;
+    writeln!(code)?;
+    writeln!(code, "pub fn register<S>(")?;
+    writeln!(code, "    router: &mut ::warp_json_rpc::RpcRouter,")?;
+    writeln!(code, "    service: ::std::sync::Arc<S>,")?;
+    writeln!(code, ") where")?;
+    writeln!(code, "    S: {},", trait_name)?;
+    writeln!(code, "{{")?;
+    for method in &methods {
+        let names = method
+            .params
+            .iter()
+            .map(|(name, _)| format!("{},", name))
+            .collect::<String>();
+        let types = method
+            .params
+            .iter()
+            .map(|(_, ty)| format!("{},", ty))
+            .collect::<String>();
+        let args = method
+            .params
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(code, "    {{")?;
+        writeln!(code, "        let service = service.clone();")?;
+        writeln!(
+            code,
+            "        router.method({:?}, move |({}): ({})| service.{}({}));",
+            method.name, names, types, method.ident, args
+        )?;
+        writeln!(code, "    }}")?;
+    }
+    writeln!(code, "}}")?;
+    Ok(code)
+}
+
+struct Method {
+    name: String,
+    ident: String,
+    description: Option<String>,
+    params: Vec<(String, String)>,
+    result: String,
+}
+
+impl Method {
+    fn parse(method: &Value) -> anyhow::Result<Method> {
+        let name = method["name"]
+            .as_str()
+            .ok_or_else(|| anyhow!("a method has no name"))?;
+        let params = method["params"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                let name = param["name"]
+                    .as_str()
+                    .map_or_else(|| format!("param{}", i), ident);
+                let ty = rust_type(&param["schema"]);
+                if param["required"] == true || ty.starts_with("Option<") {
+                    (name, ty)
+                } else {
+                    (name, format!("Option<{}>", ty))
+                }
+            })
+            .collect();
+        let description = method["description"]
+            .as_str()
+            .or_else(|| method["summary"].as_str())
+            .map(str::to_string);
+        Ok(Method {
+            name: name.to_string(),
+            ident: ident(name),
+            description,
+            params,
+            result: rust_type(&method["result"]["schema"]),
+        })
+    }
+}
+
+/// A Rust type receiving values conforming to `schema`.
+fn rust_type(schema: &Value) -> String {
+    let types = match &schema["type"] {
+        Value::String(ty) => vec![ty.as_str()],
+        Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    let nullable = types.contains(&"null");
+    let ty = match types.iter().filter(|ty| **ty != "null").collect::<Vec<_>>()[..] {
+        [&"integer"] => "i64".to_string(),
+        [&"number"] => "f64".to_string(),
+        [&"string"] => "String".to_string(),
+        [&"boolean"] => "bool".to_string(),
+        [&"array"] if schema["items"].is_object() => {
+            format!("Vec<{}>", rust_type(&schema["items"]))
+        }
+        [] if nullable => return "()".to_string(),
+        _ => return "::serde_json::Value".to_string(),
+    };
+    if nullable {
+        format!("Option<{}>", ty)
+    } else {
+        ty
+    }
+}
+
+/// A Rust identifier in snake case made from `name`.
+fn ident(name: &str) -> String {
+    let mut ident = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !ident.ends_with('_') {
+                ident.push('_');
+            }
+            ident.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            ident.push(c);
+        } else if !ident.ends_with('_') {
+            ident.push('_');
+        }
+    }
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if KEYWORDS.contains(&ident.as_str()) {
+        ident.insert_str(0, "r#");
+    }
+    ident
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while",
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn generate_trait_and_register() {
+        let document = json!({
+            "openrpc": "1.2.6",
+            "info": { "title": "Calculator", "version": "1.0.0" },
+            "methods": [
+                {
+                    "name": "add",
+                    "description": "Add two integers.",
+                    "params": [
+                        { "name": "lhs", "required": true, "schema": { "type": "integer" } },
+                        { "name": "rhs", "schema": { "type": "integer" } },
+                    ],
+                    "result": { "name": "result", "schema": { "type": "integer" } },
+                },
+                {
+                    "name": "listItems",
+                    "params": [],
+                    "result": { "name": "result", "schema": { "type": "array", "items": { "type": "string" } } },
+                },
+            ],
+        });
+        let code = generate(&document, "Calculator").unwrap();
+        assert!(code.contains(
+            "pub trait Calculator: Send + Sync + 'static {\n    \
+             /// Add two integers.\n    \
+             fn add(&self, lhs: i64, rhs: Option<i64>) -> RpcFuture<i64>;\n    \
+             fn list_items(&self) -> RpcFuture<Vec<String>>;\n}"
+        ));
+        assert!(code.contains(
+            r#"router.method("add", move |(lhs,rhs,): (i64,Option<i64>,)| service.add(lhs, rhs));"#
+        ));
+        assert!(code.contains(r#"router.method("listItems", move |(): ()| service.list_items());"#));
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(ident("eth_getBalance"), "eth_get_balance");
+        assert_eq!(ident("rpc.discover"), "rpc_discover");
+        assert_eq!(ident("type"), "r#type");
+        assert_eq!(ident("2fa"), "_2fa");
+    }
+}
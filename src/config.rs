@@ -0,0 +1,255 @@
+use crate::{Error, RpcRouter};
+use futures::future::{BoxFuture, FutureExt as _};
+use http::HeaderMap;
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{Arc, Once, RwLock},
+    time::Duration,
+};
+
+/// HTTP header carrying the API key of a request.
+pub const API_KEY: &str = "X-Api-Key";
+
+/// Error code returned when the API key is missing or not accepted.
+pub const UNAUTHORIZED_CODE: i64 = -32001;
+
+/// Settings of a router which can be changed while it is serving.
+///
+/// Every field is optional, and an absent field imposes no restriction.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DynamicConfig {
+    /// Methods which can be called. Calls of other registered methods are
+    /// answered by `METHOD_NOT_FOUND` error.
+    pub allowed_methods: Option<HashSet<String>>,
+    /// API keys accepted in the `X-Api-Key` header. Calls without an
+    /// accepted key are answered by `UNAUTHORIZED_CODE` error.
+    pub api_keys: Option<HashSet<String>>,
+    /// Replaces the `limit` given to [`RpcRouter::byte_quota`].
+    ///
+    /// [`RpcRouter::byte_quota`]: ./struct.RpcRouter.html#method.byte_quota
+    pub byte_quota: Option<u64>,
+}
+
+/// Where [`DynamicConfig`] is loaded from.
+///
+/// Implemented for `Fn() -> anyhow::Result<DynamicConfig>` closures, so a
+/// config can also be computed by a callback.
+///
+/// [`DynamicConfig`]: ./struct.DynamicConfig.html
+pub trait ConfigSource: Send + Sync {
+    fn load(&self) -> BoxFuture<'_, anyhow::Result<DynamicConfig>>;
+}
+
+impl<F> ConfigSource for F
+where
+    F: Fn() -> anyhow::Result<DynamicConfig> + Send + Sync,
+{
+    fn load(&self) -> BoxFuture<'_, anyhow::Result<DynamicConfig>> {
+        futures::future::ready(self()).boxed()
+    }
+}
+
+/// A `ConfigSource` reading a JSON file.
+#[derive(Debug, Clone)]
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new<P>(path: P) -> FileSource
+    where
+        P: Into<PathBuf>,
+    {
+        FileSource { path: path.into() }
+    }
+}
+
+impl ConfigSource for FileSource {
+    fn load(&self) -> BoxFuture<'_, anyhow::Result<DynamicConfig>> {
+        let path = self.path.clone();
+        async move {
+            let json = tokio::task::spawn_blocking(move || std::fs::read(path)).await??;
+            Ok(serde_json::from_slice(&json)?)
+        }
+        .boxed()
+    }
+}
+
+/// A `ConfigSource` reading environment variables.
+///
+/// With prefix `RPC_`, `RPC_ALLOWED_METHODS` and `RPC_API_KEYS` are comma
+/// separated lists, and `RPC_BYTE_QUOTA` is a number.
+#[derive(Debug, Clone)]
+pub struct EnvSource {
+    prefix: String,
+}
+
+impl EnvSource {
+    pub fn new(prefix: impl Into<String>) -> EnvSource {
+        EnvSource {
+            prefix: prefix.into(),
+        }
+    }
+
+    fn var(&self, name: &str) -> Option<String> {
+        std::env::var(format!("{}{}", self.prefix, name)).ok()
+    }
+}
+
+impl ConfigSource for EnvSource {
+    fn load(&self) -> BoxFuture<'_, anyhow::Result<DynamicConfig>> {
+        let list = |value: String| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let config = (|| {
+            Ok(DynamicConfig {
+                allowed_methods: self.var("ALLOWED_METHODS").map(list),
+                api_keys: self.var("API_KEYS").map(list),
+                byte_quota: self.var("BYTE_QUOTA").map(|n| n.parse()).transpose()?,
+            })
+        })();
+        futures::future::ready(config).boxed()
+    }
+}
+
+impl RpcRouter {
+    /// Load [`DynamicConfig`] from `source` now, and again every `interval`
+    /// while serving.
+    ///
+    /// A reloaded config replaces the previous one at once; calls see either
+    /// of them as a whole. If reloading fails, the previous config is kept.
+    ///
+    /// [`DynamicConfig`]: ./struct.DynamicConfig.html
+    pub async fn config_source<S>(
+        &mut self,
+        source: S,
+        interval: Duration,
+    ) -> anyhow::Result<&mut RpcRouter>
+    where
+        S: ConfigSource + 'static,
+    {
+        let config = source.load().await?;
+        self.config = Some(Arc::new(LiveConfig {
+            current: RwLock::new(Arc::new(config)),
+            source: Box::new(source),
+            interval,
+            polling: Once::new(),
+        }));
+        Ok(self)
+    }
+}
+
+pub(crate) struct LiveConfig {
+    current: RwLock<Arc<DynamicConfig>>,
+    source: Box<dyn ConfigSource>,
+    interval: Duration,
+    polling: Once,
+}
+
+impl LiveConfig {
+    /// The current config.
+    pub(crate) fn get(self: &Arc<Self>) -> Arc<DynamicConfig> {
+        // Polling is started lazily, since a runtime may not be running when
+        // the router is built.
+        self.polling.call_once(|| {
+            tokio::spawn(poll(Arc::downgrade(self)));
+        });
+        self.current.read().unwrap().clone()
+    }
+}
+
+async fn poll(config: std::sync::Weak<LiveConfig>) {
+    loop {
+        let interval = match config.upgrade() {
+            Some(config) => config.interval,
+            None => return,
+        };
+        tokio::time::sleep(interval).await;
+        let config = match config.upgrade() {
+            Some(config) => config,
+            None => return,
+        };
+        match config.source.load().await {
+            Ok(loaded) => *config.current.write().unwrap() = Arc::new(loaded),
+            Err(e) => log::error!(target: "warp_json_rpc", "Failed to reload config: {}", e),
+        }
+    }
+}
+
+impl DynamicConfig {
+    /// Fail if the call is not allowed by this config.
+    pub(crate) fn admit(&self, method: &str, headers: &HeaderMap) -> Result<(), Error> {
+        if let Some(allowed) = self.allowed_methods.as_ref() {
+            if !allowed.contains(method) {
+                return Err(Error::METHOD_NOT_FOUND);
+            }
+        }
+        if let Some(keys) = self.api_keys.as_ref() {
+            let key = headers.get(API_KEY).and_then(|key| key.to_str().ok());
+            if !key.map_or(false, |key| keys.contains(key)) {
+                return Err(Error::custom(UNAUTHORIZED_CODE, "Unauthorized"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn admit_calls() {
+        let config = serde_json::from_str::<DynamicConfig>(
+            r#"{ "allowed_methods": ["add"], "api_keys": ["secret"] }"#,
+        )
+        .unwrap();
+        let mut headers = HeaderMap::new();
+
+        let error = config.admit("sub", &headers).err().unwrap();
+        assert_eq!(error.code, Error::METHOD_NOT_FOUND.code);
+        let error = config.admit("add", &headers).err().unwrap();
+        assert_eq!(error.code, UNAUTHORIZED_CODE);
+
+        headers.insert(API_KEY, "secret".parse().unwrap());
+        assert!(config.admit("add", &headers).is_ok());
+        assert!(DynamicConfig::default()
+            .admit("sub", &HeaderMap::new())
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn reload_config() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let quota = Arc::new(AtomicU64::new(1));
+        let source = {
+            let quota = quota.clone();
+            move || {
+                Ok(DynamicConfig {
+                    byte_quota: Some(quota.load(Ordering::SeqCst)),
+                    ..DynamicConfig::default()
+                })
+            }
+        };
+        let mut router = RpcRouter::new();
+        router
+            .config_source(source, Duration::from_millis(10))
+            .await
+            .unwrap();
+        let config = router.config.clone().unwrap();
+        assert_eq!(config.get().byte_quota, Some(1));
+
+        quota.store(2, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(config.get().byte_quota, Some(2));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    intern::Name,
+    req::{Id, Request},
+    Error, MethodId,
+};
+use futures::future::Future;
+use http::HeaderMap;
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// HTTP header carrying how many milliseconds the client waits for the
+/// response.
+pub const REQUEST_TIMEOUT: &str = "Request-Timeout";
+
+/// Error code returned by [`Context::timeout`] when the deadline passes.
+///
+/// [`Context::timeout`]: ./struct.Context.html#method.timeout
+pub const DEADLINE_EXCEEDED_CODE: i64 = -32008;
+
+/// Information about the RPC request being handled.
+///
+/// Passed to [`BoxedHandler`]s along with the [`Params`].
+///
+/// [`BoxedHandler`]: ./type.BoxedHandler.html
+/// [`Params`]: ./struct.Params.html
+#[derive(Debug, Clone)]
+pub struct Context {
+    id: Id,
+    method: Name,
+    headers: Arc<HeaderMap>,
+    deadline: Option<Instant>,
+    remote_addr: Option<SocketAddr>,
+}
+
+impl Context {
+    pub(crate) fn new(req: &Request, headers: HeaderMap) -> Context {
+        let deadline = headers
+            .get(REQUEST_TIMEOUT)
+            .and_then(|value| value.to_str().ok()?.trim().parse::<u64>().ok())
+            // A timeout too far to tell is no deadline.
+            .and_then(|ms| Instant::now().checked_add(Duration::from_millis(ms)));
+        Context {
+            deadline,
+            ..Context::from_name(req.id(), req.method_name(), headers)
+        }
+    }
+
+    pub(crate) fn from_parts(id: Id, method: &str, headers: HeaderMap) -> Context {
+        Context::from_name(id, Name::new(method), headers)
+    }
+
+    fn from_name(id: Id, method: Name, headers: HeaderMap) -> Context {
+        Context {
+            id,
+            method,
+            headers: Arc::new(headers),
+            deadline: None,
+            remote_addr: None,
+        }
+    }
+
+    pub(crate) fn with_remote_addr(mut self, remote_addr: Option<SocketAddr>) -> Context {
+        self.remote_addr = remote_addr;
+        self
+    }
+
+    /// Use `timeout` from now as the deadline if the request has none.
+    pub(crate) fn or_timeout(mut self, timeout: Option<Duration>) -> Context {
+        if self.deadline.is_none() {
+            self.deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        }
+        self
+    }
+
+    pub fn id(&self) -> Id {
+        self.id.clone()
+    }
+
+    pub fn method(&self) -> &str {
+        self.method.name.as_str()
+    }
+
+    /// Identifier of the method, for comparing methods without comparing
+    /// their names.
+    pub fn method_id(&self) -> Option<MethodId> {
+        self.method.id
+    }
+
+    /// HTTP headers of the request.
+    ///
+    /// Empty while recovering journaled calls.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Address of the peer of the connection.
+    ///
+    /// This is the address of a proxy, if any is in between; see the
+    /// `Forwarded` header in [`headers`] for the original client. `None` if
+    /// the server does not know it (e.g. while recovering journaled calls).
+    ///
+    /// [`headers`]: #method.headers
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// When the client stops waiting for the response.
+    ///
+    /// Taken from the `Request-Timeout` header, or the default set by
+    /// [`RpcRouter::default_timeout`]. `None` if there is no deadline.
+    ///
+    /// [`RpcRouter::default_timeout`]: ./struct.RpcRouter.html#method.default_timeout
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Time left until the deadline, zero if it has passed.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Await `fut` until the deadline.
+    ///
+    /// Fails with `DEADLINE_EXCEEDED_CODE` error if the deadline passes first,
+    /// so the result can be propagated by `?`.
+    ///
+    /// ```
+    /// # use warp_json_rpc::{Context, Error};
+    /// async fn handle(ctx: Context) -> Result<u64, Error> {
+    ///     let rows = ctx.timeout(query_database()).await?;
+    ///     Ok(rows)
+    /// }
+    /// # async fn query_database() -> u64 { 0 }
+    /// ```
+    pub async fn timeout<F>(&self, fut: F) -> Result<F::Output, Error>
+    where
+        F: Future,
+    {
+        match self.deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), fut)
+                .await
+                .map_err(|_| Error::custom(DEADLINE_EXCEEDED_CODE, "Deadline exceeded")),
+            None => Ok(fut.await),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn timeout_by_deadline() {
+        let ctx = Context::from_parts(Id::Null, "m", HeaderMap::new());
+        assert_eq!(ctx.remaining_time(), None);
+        assert_eq!(ctx.timeout(async { 1 }).await.ok(), Some(1));
+
+        let ctx = ctx.or_timeout(Some(Duration::from_millis(10)));
+        assert!(ctx.remaining_time().unwrap() <= Duration::from_millis(10));
+        let error = ctx
+            .timeout(tokio::time::sleep(Duration::from_secs(1)))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.code, DEADLINE_EXCEEDED_CODE);
+        assert_eq!(ctx.remaining_time(), Some(Duration::from_secs(0)));
+    }
+
+    #[tokio::test]
+    async fn unbounded_timeout() {
+        let req = serde_json::from_str::<Request>(r#"{"jsonrpc": "2.0", "method": "m", "id": 1}"#)
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(REQUEST_TIMEOUT, u64::MAX.into());
+        let ctx = Context::new(&req, headers);
+        assert_eq!(ctx.timeout(async { 1 }).await.ok(), Some(1));
+
+        let ctx = Context::from_parts(Id::Null, "m", HeaderMap::new());
+        let ctx = ctx.or_timeout(Some(Duration::MAX));
+        assert_eq!(ctx.deadline(), None);
+    }
+}
@@ -0,0 +1,334 @@
+//! Chunked results fetched by continuation tokens.
+//!
+//! For clients which cannot use streaming transports, a chunked method
+//! answers with the first chunk of its results and a continuation token. The
+//! rest of the results are kept by the server and fetched chunk by chunk by
+//! the built-in `rpc_continue` method, until a chunk without continuation is
+//! returned.
+//!
+//! ```
+//! use warp_json_rpc::{Error, RpcRouter};
+//!
+//! let mut router = RpcRouter::new();
+//! router.chunked("tail", 100, |(n,): (u64,)| {
+//!     futures::stream::iter((0..n).map(Ok::<_, Error>))
+//! });
+//! ```
+use crate::{
+    kv::{KvStore, MemoryStore},
+    Error, RpcRouter,
+};
+use futures::{
+    future::FutureExt as _,
+    stream::{BoxStream, Stream, StreamExt as _},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Name of the method fetching the next chunk.
+pub const RPC_CONTINUE: &str = "rpc_continue";
+
+/// How long an unfetched continuation is kept.
+const TTL: Duration = Duration::from_secs(60);
+
+/// Result of a chunked method and of `rpc_continue`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Chunk<T> {
+    pub items: Vec<T>,
+    /// Pass this to `rpc_continue` to fetch the next chunk. `None` if this is
+    /// the last chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub continuation: Option<String>,
+}
+
+impl RpcRouter {
+    /// Register a handler for the RPC method, which streams its results in
+    /// chunks of up to `chunk_size` items.
+    ///
+    /// The method answers with the first [`Chunk`]. Also registers the
+    /// `rpc_continue` method, whose parameter is `[continuation]`. If the
+    /// stream yields an error, the error is answered and the rest of the
+    /// stream is dropped. A continuation not fetched for a minute is dropped.
+    ///
+    /// Tokens are kept in memory of this process, unless they are kept in a
+    /// shared store by [`continuations_in`].
+    ///
+    /// [`continuations_in`]: #method.continuations_in
+    /// [`Chunk`]: ./continuation/struct.Chunk.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    #[track_caller]
+    pub fn chunked<P, T, F, S>(
+        &mut self,
+        name: impl Into<String>,
+        chunk_size: usize,
+        handler: F,
+    ) -> &mut RpcRouter
+    where
+        for<'de> P: Deserialize<'de> + Send + 'static,
+        T: Serialize,
+        F: Fn(P) -> S + Send + Sync + 'static,
+        S: Stream<Item = Result<T, Error>> + Send + 'static,
+    {
+        assert!(chunk_size > 0, "chunk size must be positive");
+        let store = self.continuations().clone();
+        self.method(name, move |params: P| {
+            let stream = handler(params)
+                .map(|item| {
+                    item.and_then(|item| {
+                        serde_json::to_value(item).map_err(|e| {
+                            log::error!(target: "warp_json_rpc", "Failed to serialize result: {}", e);
+                            Error::INTERNAL_ERROR
+                        })
+                    })
+                })
+                .boxed();
+            store.next_chunk(stream, chunk_size, None)
+        })
+    }
+
+    /// Keep continuation tokens in `store`.
+    ///
+    /// The rest of a result can only be fetched from the process which
+    /// answered its first chunk, since the stream of the result stays there.
+    /// Sharing a store between processes tells a token held by another
+    /// process, e.g. behind a load balancer without sticky sessions, from an
+    /// unknown or expired one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a chunked method is registered before.
+    pub fn continuations_in<S>(&mut self, store: S) -> &mut RpcRouter
+    where
+        S: KvStore + 'static,
+    {
+        assert!(
+            self.continuations.is_none(),
+            "`RpcRouter::continuations_in` must be called before `chunked`"
+        );
+        self.register_continuations(Arc::new(store));
+        self
+    }
+
+    fn continuations(&mut self) -> &Arc<Continuations> {
+        if self.continuations.is_none() {
+            self.register_continuations(Arc::new(MemoryStore::new()));
+        }
+        self.continuations.as_ref().unwrap()
+    }
+
+    fn register_continuations(&mut self, tokens: Arc<dyn KvStore>) {
+        let store = Arc::new(Continuations::new(tokens));
+        let continuations = store.clone();
+        self.method(RPC_CONTINUE, move |(token,): (String,)| {
+            let continuations = continuations.clone();
+            async move {
+                let (stream, chunk_size) = continuations.take(&token).await?;
+                continuations
+                    .next_chunk(stream, chunk_size, Some(token))
+                    .await
+            }
+            .boxed()
+        });
+        self.continuations = Some(store);
+    }
+}
+
+type Pending = (Instant, BoxStream<'static, Result<Value, Error>>, usize);
+
+pub(crate) struct Continuations {
+    /// Tokens of continuations, naming the process which holds each.
+    tokens: Arc<dyn KvStore>,
+    /// Names this process as the holder of tokens.
+    holder: String,
+    streams: Mutex<Streams>,
+}
+
+/// The rest of the results of continuations held by this process.
+struct Streams {
+    pending: HashMap<String, Pending>,
+    /// When expired streams are dropped next.
+    next_sweep: Instant,
+}
+
+impl Continuations {
+    fn new(tokens: Arc<dyn KvStore>) -> Continuations {
+        Continuations {
+            tokens,
+            holder: new_token(),
+            streams: Mutex::new(Streams {
+                pending: HashMap::new(),
+                next_sweep: Instant::now() + TTL,
+            }),
+        }
+    }
+
+    async fn take(
+        &self,
+        token: &str,
+    ) -> Result<(BoxStream<'static, Result<Value, Error>>, usize), Error> {
+        let unknown = || Error::INVALID_PARAMS.with_data("unknown or expired continuation");
+        let key = store_key(token);
+        match self.tokens.get(&key).await {
+            Ok(Some(holder)) if holder != self.holder.as_bytes() => {
+                return Err(
+                    Error::INVALID_PARAMS.with_data("continuation is held by another process")
+                );
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                self.streams.lock().unwrap().pending.remove(token);
+                return Err(unknown());
+            }
+            // The stream is still known here.
+            Err(e) => {
+                log::error!(target: "warp_json_rpc", "Failed to get continuation token: {}", e)
+            }
+        }
+        if let Err(e) = self.tokens.delete(&key).await {
+            log::error!(target: "warp_json_rpc", "Failed to delete continuation token: {}", e);
+        }
+        let mut streams = self.streams.lock().unwrap();
+        let (at, stream, chunk_size) = streams.pending.remove(token).ok_or_else(unknown)?;
+        if at.elapsed() > TTL {
+            return Err(unknown());
+        }
+        Ok((stream, chunk_size))
+    }
+
+    async fn put(
+        &self,
+        token: String,
+        stream: BoxStream<'static, Result<Value, Error>>,
+        chunk_size: usize,
+    ) {
+        let put = self
+            .tokens
+            .put(&store_key(&token), self.holder.clone().into_bytes(), TTL);
+        if let Err(e) = put.await {
+            log::error!(target: "warp_json_rpc", "Failed to put continuation token: {}", e);
+        }
+        let mut streams = self.streams.lock().unwrap();
+        let now = Instant::now();
+        if now >= streams.next_sweep {
+            streams
+                .pending
+                .retain(|_, (at, _, _)| now.duration_since(*at) <= TTL);
+            streams.next_sweep = now + TTL;
+        }
+        streams.pending.insert(token, (now, stream, chunk_size));
+    }
+
+    /// Pull the next chunk from `stream`, keeping the rest under `token` (or
+    /// a new token) if it is not exhausted.
+    fn next_chunk(
+        self: &Arc<Self>,
+        mut stream: BoxStream<'static, Result<Value, Error>>,
+        chunk_size: usize,
+        token: Option<String>,
+    ) -> impl std::future::Future<Output = Result<Chunk<Value>, Error>> + Send + 'static {
+        let store = self.clone();
+        async move {
+            let mut items = Vec::with_capacity(chunk_size);
+            while items.len() < chunk_size {
+                match stream.next().await {
+                    Some(item) => items.push(item?),
+                    None => {
+                        return Ok(Chunk {
+                            items,
+                            continuation: None,
+                        })
+                    }
+                }
+            }
+            let token = token.unwrap_or_else(new_token);
+            store.put(token.clone(), stream, chunk_size).await;
+            Ok(Chunk {
+                items,
+                continuation: Some(token),
+            })
+        }
+    }
+}
+
+fn store_key(token: &str) -> String {
+    format!("continuation:{}", token)
+}
+
+pub(crate) fn new_token() -> String {
+    let bytes = rand::random::<[u8; 16]>();
+    let mut token = String::with_capacity(32);
+    for b in bytes.iter() {
+        write!(token, "{:02x}", b).unwrap();
+    }
+    token
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn fetch_chunks() {
+        let store = Arc::new(Continuations::new(Arc::new(MemoryStore::new())));
+        let stream = futures::stream::iter((0..5).map(|n| Ok(Value::from(n)))).boxed();
+
+        let chunk = store.next_chunk(stream, 2, None).await.ok().unwrap();
+        assert_eq!(chunk.items, vec![0, 1]);
+        let token = chunk.continuation.unwrap();
+
+        let (stream, size) = store.take(&token).await.ok().unwrap();
+        let chunk = store
+            .next_chunk(stream, size, Some(token.clone()))
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(chunk.items, vec![2, 3]);
+        assert_eq!(chunk.continuation.as_ref(), Some(&token));
+
+        let (stream, size) = store.take(&token).await.ok().unwrap();
+        let chunk = store
+            .next_chunk(stream, size, Some(token.clone()))
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(chunk.items, vec![4]);
+        assert_eq!(chunk.continuation, None);
+        assert!(store.take(&token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn error_drops_stream() {
+        let store = Arc::new(Continuations::new(Arc::new(MemoryStore::new())));
+        let stream =
+            futures::stream::iter(vec![Ok(Value::from(0)), Err(Error::custom(1, "failed"))])
+                .boxed();
+        let error = store.next_chunk(stream, 5, None).await.err().unwrap();
+        assert_eq!(error.code, 1);
+        assert!(store.streams.lock().unwrap().pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tokens_held_by_another_process() {
+        let tokens = Arc::new(MemoryStore::new());
+        let here = Arc::new(Continuations::new(tokens.clone()));
+        let there = Arc::new(Continuations::new(tokens));
+        let stream = futures::stream::iter((0..5).map(|n| Ok(Value::from(n)))).boxed();
+
+        let chunk = here.next_chunk(stream, 2, None).await.ok().unwrap();
+        let token = chunk.continuation.unwrap();
+        let error = there.take(&token).await.err().unwrap();
+        let data = serde_json::to_value(error.data.unwrap()).unwrap();
+        assert_eq!(data, "continuation is held by another process");
+        assert!(here.take(&token).await.is_ok());
+        assert!(there.take("unknown").await.is_err());
+    }
+}
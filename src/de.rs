@@ -0,0 +1,502 @@
+//! Lenient deserialization of RPC parameters.
+//!
+//! JSON RPC implementations commonly accept calls which
+//!
+//! - omit `params` entirely when the method takes nothing (or only optional
+//!   parameters), and
+//! - omit trailing optional positional parameters.
+//!
+//! `serde_json` rejects both, so parameters are deserialized through the
+//! wrappers in this module.
+use crate::{schema, Violation};
+use serde::de::{
+    self, DeserializeSeed, Deserializer, Error as _, IntoDeserializer, MapAccess, SeqAccess,
+    Visitor,
+};
+use serde_json::de::StrRead;
+use std::{fmt, marker::PhantomData};
+
+/// Deserialize present parameters, padding missing trailing elements of a
+/// top level tuple with `null`.
+pub(crate) fn from_str<'de, T>(s: &'de str) -> Result<T, serde_json::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut de = serde_json::Deserializer::from_str(s);
+    let value = T::deserialize(Present(&mut de))?;
+    de.end()?;
+    Ok(value)
+}
+
+/// Deserialize absent parameters as if they were empty.
+///
+/// Tuples are deserialized from an empty array (padded by `null`), structs and
+/// maps from an empty object, and everything else from `null`.
+pub(crate) fn absent<'de, T>() -> Result<T, serde_json::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    T::deserialize(Absent)
+}
+
+macro_rules! forward {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.0.$method(visitor)
+            }
+        )*
+    };
+}
+
+struct Present<'a, 'de>(&'a mut serde_json::Deserializer<StrRead<'de>>);
+
+impl<'a, 'de> Deserializer<'de> for Present<'a, 'de> {
+    type Error = serde_json::Error;
+
+    forward! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32
+        deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char
+        deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_option deserialize_unit deserialize_seq deserialize_map
+        deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.deserialize_tuple(len, Padding { visitor, len })
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0
+            .deserialize_tuple_struct(name, len, Padding { visitor, len })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+}
+
+struct Absent;
+
+impl<'de> Deserializer<'de> for Absent {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_none()
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Padding { visitor, len }.visit_seq(Empty(PhantomData))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(Empty(PhantomData))
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(Empty(PhantomData))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(Empty(PhantomData))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct enum identifier ignored_any
+    }
+}
+
+/// `Visitor` which pads a sequence up to `len` elements.
+struct Padding<V> {
+    visitor: V,
+    len: usize,
+}
+
+impl<'de, V> Visitor<'de> for Padding<V>
+where
+    V: Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.visitor.expecting(f)
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        self.visitor.visit_seq(PaddedSeq {
+            seq,
+            index: 0,
+            len: self.len,
+            exhausted: false,
+        })
+    }
+}
+
+struct PaddedSeq<A> {
+    seq: A,
+    index: usize,
+    len: usize,
+    exhausted: bool,
+}
+
+impl<'de, A> SeqAccess<'de> for PaddedSeq<A>
+where
+    A: SeqAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, A::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let mut seed = Some(seed);
+        if !self.exhausted {
+            match self.seq.next_element_seed(Slot(&mut seed))? {
+                Some(value) => {
+                    self.index += 1;
+                    return Ok(Some(value));
+                }
+                None => self.exhausted = true,
+            }
+        }
+
+        if self.index >= self.len {
+            return Ok(None);
+        }
+        let seed = seed.expect("seed is not consumed by an exhausted sequence");
+        let value = seed
+            .deserialize(IntoDeserializer::<A::Error>::into_deserializer(()))
+            .map_err(|_| {
+                A::Error::invalid_length(self.index, &format!("{} parameters", self.len).as_str())
+            })?;
+        self.index += 1;
+        Ok(Some(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len.saturating_sub(self.index))
+    }
+}
+
+/// `DeserializeSeed` which leaves the inner seed untouched unless it is used.
+struct Slot<'a, T>(&'a mut Option<T>);
+
+impl<'a, 'de, T> DeserializeSeed<'de> for Slot<'a, T>
+where
+    T: DeserializeSeed<'de>,
+{
+    type Value = T::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.0
+            .take()
+            .expect("seed is used only once")
+            .deserialize(deserializer)
+    }
+}
+
+struct Empty<E>(PhantomData<E>);
+
+impl<'de, E> SeqAccess<'de> for Empty<E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn next_element_seed<T>(&mut self, _seed: T) -> Result<Option<T::Value>, E>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        Ok(None)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+impl<'de, E> MapAccess<'de> for Empty<E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn next_key_seed<K>(&mut self, _seed: K) -> Result<Option<K::Value>, E>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(&mut self, _seed: V) -> Result<V::Value, E>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        Err(E::custom("value is missing"))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+/// Describe why deserializing `params` failed, pointing at the offending
+/// value.
+///
+/// `serde_json` reports where in the text it failed, so the pointer is that
+/// of the innermost value (or member key) spanning the position. A missing
+/// field is pointed at below its object.
+pub(crate) fn violation(params: Option<&str>, e: &serde_json::Error) -> Violation {
+    let message = e.to_string();
+    let message = message
+        .strip_suffix(&format!(" at line {} column {}", e.line(), e.column()))
+        .map(str::to_string)
+        .unwrap_or(message);
+    let mut pointer = match params.filter(|_| e.line() > 0) {
+        Some(json) => {
+            let offset = json
+                .split('\n')
+                .take(e.line() - 1)
+                .map(|line| line.len() + 1)
+                .sum::<usize>()
+                + e.column();
+            Scanner {
+                json,
+                at: 0,
+                offset,
+            }
+            .value("")
+            .unwrap_or_default()
+        }
+        None => String::new(),
+    };
+    if let Some(field) = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.strip_suffix('`'))
+    {
+        pointer = schema::child(&pointer, field);
+    }
+    let expected = message
+        .split_once(", expected ")
+        .map(|(_, expected)| expected.to_string());
+    Violation {
+        pointer,
+        message,
+        expected,
+    }
+}
+
+/// Walks well-formed JSON text to find the value spanning `offset`.
+struct Scanner<'a> {
+    json: &'a str,
+    at: usize,
+    offset: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.json.as_bytes().get(self.at).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.at += 1;
+        }
+    }
+
+    fn spans(&self, start: usize) -> bool {
+        start <= self.offset && self.offset <= self.at
+    }
+
+    /// Scan the value at `pointer`, returning the pointer of the innermost
+    /// value within it spanning the offset.
+    fn value(&mut self, pointer: &str) -> Option<String> {
+        self.skip_whitespace();
+        let start = self.at;
+        let inner = match self.peek() {
+            Some(b'{') => self.object(pointer),
+            Some(b'[') => self.array(pointer),
+            Some(b'"') => {
+                self.string();
+                None
+            }
+            _ => {
+                while let Some(b) = self.peek() {
+                    if b",]} \t\n\r".contains(&b) {
+                        break;
+                    }
+                    self.at += 1;
+                }
+                None
+            }
+        };
+        inner.or_else(|| Some(pointer.to_string()).filter(|_| self.spans(start)))
+    }
+
+    fn object(&mut self, pointer: &str) -> Option<String> {
+        self.at += 1;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b'"') => {}
+                Some(b',') => {
+                    self.at += 1;
+                    continue;
+                }
+                _ => {
+                    self.at += 1;
+                    return None;
+                }
+            }
+            let start = self.at;
+            self.string();
+            let key =
+                serde_json::from_str::<String>(&self.json[start..self.at]).unwrap_or_default();
+            let child = schema::child(pointer, &key);
+            if self.spans(start) {
+                return Some(child);
+            }
+            self.skip_whitespace();
+            self.at += 1;
+            if let Some(found) = self.value(&child) {
+                return Some(found);
+            }
+        }
+    }
+
+    fn array(&mut self, pointer: &str) -> Option<String> {
+        self.at += 1;
+        let mut index = 0;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => {
+                    self.at += 1;
+                    continue;
+                }
+                Some(b']') | None => {
+                    self.at += 1;
+                    return None;
+                }
+                _ => {}
+            }
+            if let Some(found) = self.value(&schema::child(pointer, &index.to_string())) {
+                return Some(found);
+            }
+            index += 1;
+        }
+    }
+
+    fn string(&mut self) {
+        self.at += 1;
+        while let Some(b) = self.peek() {
+            self.at += if b == b'\\' { 2 } else { 1 };
+            if b == b'"' {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,510 @@
+//! Exact numeric parameters for financial and blockchain APIs.
+//!
+//! Amounts are commonly sent as strings, since JSON numbers are read as
+//! `f64` by most clients. [`Decimal`] and [`U256String`] are serialized as
+//! strings by default; use [`as_number`] or [`as_hex`] with
+//! `#[serde(serialize_with = "...")]` to serialize them otherwise. Both are
+//! deserialized from either form, and malformed values fail deserialization,
+//! so they are answered by `INVALID_PARAMS` error.
+//!
+//! [`Decimal`]: ./struct.Decimal.html
+//! [`U256String`]: ./struct.U256String.html
+//! [`as_number`]: ./fn.as_number.html
+//! [`as_hex`]: ./fn.as_hex.html
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize, Serialize, Serializer,
+};
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+/// Error of parsing a [`Decimal`] or [`U256String`].
+///
+/// [`Decimal`]: ./struct.Decimal.html
+/// [`U256String`]: ./struct.U256String.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/*
+ * =======
+ * Decimal
+ * =======
+ */
+/// A decimal number of up to 38 significant digits, kept exactly.
+///
+/// The value is `mantissa * 10^-scale`. Trailing zeros are kept, so `"1.50"`
+/// is serialized back as `"1.50"`, but decimals are compared by their value,
+/// so `"1.50"` equals `"1.5"`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(mantissa: i128, scale: u32) -> Decimal {
+        Decimal { mantissa, scale }
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// The mantissa and scale without trailing zeros.
+    fn normalized(&self) -> (i128, u32) {
+        let (mut mantissa, mut scale) = (self.mantissa, self.scale);
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        (mantissa, scale)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl Eq for Decimal {}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Decimal, ParseError> {
+        let invalid = || ParseError(format!("invalid decimal {:?}", s));
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, s),
+        };
+        let (int, frac) = match unsigned.find('.') {
+            Some(dot) => (&unsigned[..dot], &unsigned[dot + 1..]),
+            None => (unsigned, ""),
+        };
+        if int.is_empty() || (unsigned.contains('.') && frac.is_empty()) {
+            return Err(invalid());
+        }
+
+        let mut mantissa = 0_i128;
+        for c in int.chars().chain(frac.chars()) {
+            let digit = c.to_digit(10).ok_or_else(invalid)?;
+            mantissa = mantissa
+                .checked_mul(10)
+                .and_then(|m| m.checked_add(digit as i128))
+                .ok_or_else(|| ParseError(format!("decimal {:?} has too many digits", s)))?;
+        }
+        Ok(Decimal {
+            mantissa: if negative { -mantissa } else { mantissa },
+            scale: frac.len() as u32,
+        })
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if self.mantissa < 0 {
+            f.write_str("-")?;
+        }
+        if scale == 0 {
+            return f.write_str(&digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{}.{}", int, frac)
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Serialize a [`Decimal`] as a JSON number.
+///
+/// Integers are serialized exactly, others as the nearest `f64`.
+///
+/// [`Decimal`]: ./struct.Decimal.html
+pub fn as_number<S>(decimal: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if decimal.scale == 0 {
+        if let Ok(n) = i64::try_from(decimal.mantissa) {
+            return serializer.serialize_i64(n);
+        }
+    }
+    let n = decimal
+        .to_string()
+        .parse::<f64>()
+        .map_err(serde::ser::Error::custom)?;
+    serializer.serialize_f64(n)
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D>(deserializer: D) -> Result<Decimal, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DecimalVisitor;
+
+        impl<'de> Visitor<'de> for DecimalVisitor {
+            type Value = Decimal;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a decimal string or number")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Decimal, E> {
+                s.parse().map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, n: i64) -> Result<Decimal, E> {
+                Ok(Decimal::new(n as i128, 0))
+            }
+
+            fn visit_u64<E: de::Error>(self, n: u64) -> Result<Decimal, E> {
+                Ok(Decimal::new(n as i128, 0))
+            }
+
+            fn visit_f64<E: de::Error>(self, n: f64) -> Result<Decimal, E> {
+                if !n.is_finite() {
+                    return Err(E::custom(format!("invalid decimal {}", n)));
+                }
+                // `Display` of `f64` is the shortest representation which
+                // reads back as the same number, without exponent.
+                self.visit_str(&n.to_string())
+            }
+        }
+
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+/*
+ * ==========
+ * U256String
+ * ==========
+ */
+/// A 256 bit unsigned integer, such as an amount of wei.
+///
+/// Deserialized from a decimal string, a `0x` prefixed hex string or a JSON
+/// integer. Serialized as a decimal string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct U256String {
+    /// Little endian 64 bit limbs.
+    limbs: [u64; 4],
+}
+
+impl U256String {
+    pub const ZERO: U256String = U256String { limbs: [0; 4] };
+    pub const MAX: U256String = U256String {
+        limbs: [u64::MAX; 4],
+    };
+
+    pub fn from_be_bytes(bytes: [u8; 32]) -> U256String {
+        let mut limbs = [0; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let mut chunk = [0; 8];
+            chunk.copy_from_slice(&bytes[32 - 8 * (i + 1)..32 - 8 * i]);
+            *limb = u64::from_be_bytes(chunk);
+        }
+        U256String { limbs }
+    }
+
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        for (i, limb) in self.limbs.iter().enumerate() {
+            bytes[32 - 8 * (i + 1)..32 - 8 * i].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// The value if it fits in `u128`.
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.limbs[2] != 0 || self.limbs[3] != 0 {
+            return None;
+        }
+        Some((self.limbs[1] as u128) << 64 | self.limbs[0] as u128)
+    }
+
+    /// `self * mul + add`, or `None` on overflow.
+    fn mul_add(&self, mul: u64, add: u64) -> Option<U256String> {
+        let mut limbs = [0; 4];
+        let mut carry = add as u128;
+        for (out, limb) in limbs.iter_mut().zip(self.limbs.iter()) {
+            let n = *limb as u128 * mul as u128 + carry;
+            *out = n as u64;
+            carry = n >> 64;
+        }
+        if carry != 0 {
+            return None;
+        }
+        Some(U256String { limbs })
+    }
+
+    /// `(self / div, self % div)`.
+    fn div_rem(&self, div: u64) -> (U256String, u64) {
+        let mut limbs = [0; 4];
+        let mut rem = 0_u128;
+        for (out, limb) in limbs.iter_mut().zip(self.limbs.iter()).rev() {
+            let n = rem << 64 | *limb as u128;
+            *out = (n / div as u128) as u64;
+            rem = n % div as u128;
+        }
+        (U256String { limbs }, rem as u64)
+    }
+
+    fn parse_radix(digits: &str, radix: u32, s: &str) -> Result<U256String, ParseError> {
+        if digits.is_empty() {
+            return Err(ParseError(format!("invalid integer {:?}", s)));
+        }
+        digits.chars().try_fold(U256String::ZERO, |n, c| {
+            let digit = c
+                .to_digit(radix)
+                .ok_or_else(|| ParseError(format!("invalid integer {:?}", s)))?;
+            n.mul_add(radix as u64, digit as u64)
+                .ok_or_else(|| ParseError(format!("integer {} does not fit in 256 bits", s)))
+        })
+    }
+}
+
+impl From<u64> for U256String {
+    fn from(n: u64) -> U256String {
+        U256String {
+            limbs: [n, 0, 0, 0],
+        }
+    }
+}
+
+impl From<u128> for U256String {
+    fn from(n: u128) -> U256String {
+        U256String {
+            limbs: [n as u64, (n >> 64) as u64, 0, 0],
+        }
+    }
+}
+
+impl Ord for U256String {
+    fn cmp(&self, other: &U256String) -> Ordering {
+        self.limbs.iter().rev().cmp(other.limbs.iter().rev())
+    }
+}
+
+impl PartialOrd for U256String {
+    fn partial_cmp(&self, other: &U256String) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for U256String {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<U256String, ParseError> {
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => U256String::parse_radix(hex, 16, s),
+            None => U256String::parse_radix(s, 10, s),
+        }
+    }
+}
+
+impl fmt::Display for U256String {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const CHUNK: u64 = 10_000_000_000_000_000_000;
+
+        let mut chunks = Vec::new();
+        let mut n = *self;
+        loop {
+            let (quot, rem) = n.div_rem(CHUNK);
+            chunks.push(rem);
+            if quot == U256String::ZERO {
+                break;
+            }
+            n = quot;
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:019}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::LowerHex for U256String {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut limbs = self.limbs.iter().rev().skip_while(|limb| **limb == 0);
+        match limbs.next() {
+            Some(first) => write!(f, "{:x}", first)?,
+            None => return f.write_str("0"),
+        }
+        for limb in limbs {
+            write!(f, "{:016x}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for U256String {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Serialize a [`U256String`] as a `0x` prefixed hex string.
+///
+/// [`U256String`]: ./struct.U256String.html
+pub fn as_hex<S>(n: &U256String, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format!("0x{:x}", n))
+}
+
+impl<'de> Deserialize<'de> for U256String {
+    fn deserialize<D>(deserializer: D) -> Result<U256String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct U256Visitor;
+
+        impl<'de> Visitor<'de> for U256Visitor {
+            type Value = U256String;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a decimal or hex string of an unsigned integer")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<U256String, E> {
+                s.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, n: u64) -> Result<U256String, E> {
+                Ok(U256String::from(n))
+            }
+        }
+
+        deserializer.deserialize_any(U256Visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decimal_roundtrip() {
+        for s in &["0", "1.50", "-0.001", "123456789.123456789"] {
+            let decimal = s.parse::<Decimal>().unwrap();
+            assert_eq!(decimal.to_string(), *s);
+        }
+        assert_eq!("-0.001".parse::<Decimal>().unwrap(), Decimal::new(-1, 3));
+        assert!("1.".parse::<Decimal>().is_err());
+        assert!("1e5".parse::<Decimal>().is_err());
+        assert!("1".repeat(40).parse::<Decimal>().is_err());
+    }
+
+    #[test]
+    fn compare_decimals_by_value() {
+        use std::collections::HashSet;
+
+        let parse = |s: &str| s.parse::<Decimal>().unwrap();
+        assert_eq!(parse("1.5"), parse("1.50"));
+        assert_eq!(parse("-1.500"), parse("-1.5"));
+        assert_eq!(parse("0.00"), parse("0"));
+        assert_eq!(parse("10"), Decimal::new(100, 1));
+        assert_ne!(parse("1.5"), parse("1.05"));
+        assert_ne!(parse("15"), parse("1.5"));
+
+        let set = ["1.5", "1.50", "1.500"]
+            .iter()
+            .map(|s| parse(s))
+            .collect::<HashSet<_>>();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn deserialize_decimal() {
+        let decimal = serde_json::from_str::<Decimal>("2.25").unwrap();
+        assert_eq!(decimal, Decimal::new(225, 2));
+        let decimal = serde_json::from_str::<Decimal>(r#""2.250""#).unwrap();
+        assert_eq!(serde_json::to_string(&decimal).unwrap(), r#""2.250""#);
+
+        #[derive(Serialize)]
+        struct Amount(#[serde(serialize_with = "as_number")] Decimal);
+        assert_eq!(serde_json::to_string(&Amount(decimal)).unwrap(), "2.25");
+        assert_eq!(
+            serde_json::to_string(&Amount(Decimal::new(7, 0))).unwrap(),
+            "7"
+        );
+    }
+
+    #[test]
+    fn u256_roundtrip() {
+        let max = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+        assert_eq!(max.parse::<U256String>().unwrap(), U256String::MAX);
+        assert_eq!(U256String::MAX.to_string(), max);
+        assert!(
+            "115792089237316195423570985008687907853269984665640564039457584007913129639936"
+                .parse::<U256String>()
+                .is_err()
+        );
+
+        let n = "0x1000000000000000000000000".parse::<U256String>().unwrap();
+        assert_eq!(n.to_u128(), Some(1 << 96));
+        assert_eq!(n.to_string(), "79228162514264337593543950336");
+        assert_eq!(format!("{:x}", n), "1000000000000000000000000");
+        assert_eq!(U256String::from_be_bytes(n.to_be_bytes()), n);
+        assert!(U256String::from(1_u64) < n);
+        assert_eq!(U256String::ZERO.to_string(), "0");
+    }
+
+    #[test]
+    fn deserialize_u256() {
+        assert_eq!(
+            serde_json::from_str::<U256String>("42").unwrap(),
+            U256String::from(42_u64)
+        );
+        let error = serde_json::from_str::<U256String>(r#""0xzz""#).unwrap_err();
+        assert!(error.to_string().contains("invalid integer"));
+
+        #[derive(Serialize)]
+        struct Wei(#[serde(serialize_with = "as_hex")] U256String);
+        assert_eq!(
+            serde_json::to_string(&Wei(U256String::from(255_u64))).unwrap(),
+            r#""0xff""#
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{Error, DEADLINE_EXCEEDED_CODE};
+use futures::future::Future;
+use std::{
+    pin::Pin,
+    task::{Context as TaskContext, Poll},
+    time::Duration,
+};
+use tokio::{
+    sync::oneshot,
+    time::{self, Sleep},
+};
+
+/// Create a response which is completed later through the [`Completer`],
+/// e.g. from a task receiving an external callback.
+///
+/// The handler returns the [`DeferredResponse`], and hands the `Completer` to
+/// whatever completes the call.
+///
+/// ```
+/// # use warp_json_rpc::{deferred, Completer, Error, RpcRouter};
+/// use std::{sync::{Arc, Mutex}, time::Duration};
+///
+/// let pending = Arc::new(Mutex::new(Vec::<Completer<String>>::new()));
+/// let mut router = RpcRouter::new();
+/// router.method("await_payment", {
+///     let pending = pending.clone();
+///     move |(): ()| {
+///         let (completer, response) = deferred();
+///         pending.lock().unwrap().push(completer);
+///         response.timeout(Duration::from_secs(30))
+///     }
+/// });
+///
+/// // Later, when the payment provider calls back.
+/// for completer in pending.lock().unwrap().drain(..) {
+///     completer.complete(Ok("paid".to_string()));
+/// }
+/// ```
+///
+/// [`Completer`]: ./struct.Completer.html
+/// [`DeferredResponse`]: ./struct.DeferredResponse.html
+pub fn deferred<T>() -> (Completer<T>, DeferredResponse<T>) {
+    let (tx, rx) = oneshot::channel();
+    let response = DeferredResponse { rx, timeout: None };
+    (Completer { tx }, response)
+}
+
+/// Completes a [`DeferredResponse`].
+///
+/// Dropping it without completing answers the call by `INTERNAL_ERROR`.
+///
+/// [`DeferredResponse`]: ./struct.DeferredResponse.html
+pub struct Completer<T> {
+    tx: oneshot::Sender<Result<T, Error>>,
+}
+
+impl<T> Completer<T> {
+    /// Answer the call by `result`, and return whether it is still waited
+    /// for.
+    pub fn complete(self, result: Result<T, Error>) -> bool {
+        self.tx.send(result).is_ok()
+    }
+
+    /// Whether the call is no longer waited for, as its response timed out or
+    /// the client went away.
+    pub fn is_cancelled(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    /// Wait until the call is no longer waited for, to release what is held
+    /// for it.
+    pub async fn cancelled(&mut self) {
+        self.tx.closed().await
+    }
+}
+
+/// A response completed by a [`Completer`], created by [`deferred`].
+///
+/// [`Completer`]: ./struct.Completer.html
+/// [`deferred`]: ./fn.deferred.html
+pub struct DeferredResponse<T> {
+    rx: oneshot::Receiver<Result<T, Error>>,
+    timeout: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> DeferredResponse<T> {
+    /// Answer the call by `DEADLINE_EXCEEDED_CODE` error unless it is
+    /// completed within `duration`.
+    pub fn timeout(mut self, duration: Duration) -> DeferredResponse<T> {
+        self.timeout = Some(Box::pin(time::sleep(duration)));
+        self
+    }
+}
+
+impl<T> Future for DeferredResponse<T> {
+    type Output = Result<T, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(result) = Pin::new(&mut self.rx).poll(cx) {
+            return Poll::Ready(result.unwrap_or_else(|_| {
+                log::error!(target: "warp_json_rpc", "Deferred response is dropped without completing");
+                Err(Error::INTERNAL_ERROR)
+            }));
+        }
+        match self
+            .timeout
+            .as_mut()
+            .map(|timeout| timeout.as_mut().poll(cx))
+        {
+            Some(Poll::Ready(())) => {
+                self.rx.close();
+                Poll::Ready(Err(Error::custom(
+                    DEADLINE_EXCEEDED_CODE,
+                    "Deadline exceeded",
+                )))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn complete_later() {
+        let (completer, response) = deferred::<u64>();
+        tokio::spawn(async move {
+            time::sleep(Duration::from_millis(5)).await;
+            assert!(completer.complete(Ok(1)));
+        });
+        assert_eq!(response.await.ok(), Some(1));
+
+        let (completer, response) = deferred::<u64>();
+        drop(completer);
+        assert_eq!(
+            response.await.err().unwrap().code,
+            Error::INTERNAL_ERROR.code
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_by_timeout_and_drop() {
+        let (mut completer, response) = deferred::<u64>();
+        let response = response.timeout(Duration::from_millis(5));
+        assert_eq!(response.await.err().unwrap().code, DEADLINE_EXCEEDED_CODE);
+        completer.cancelled().await;
+        assert!(!completer.complete(Ok(1)));
+
+        let (completer, response) = deferred::<u64>();
+        assert!(!completer.is_cancelled());
+        drop(response);
+        assert!(completer.is_cancelled());
+    }
+}
@@ -0,0 +1,168 @@
+//! Structural comparison of JSON values, used to compare the results of
+//! shadowed methods.
+use serde::Serialize;
+use serde_json::Value;
+
+/// A place where two JSON values differ.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Difference {
+    /// JSON pointer of the differing value.
+    pub pointer: String,
+    /// `None` if the value is missing on this side.
+    pub primary: Option<Value>,
+    pub shadow: Option<Value>,
+}
+
+/// Compare `primary` and `shadow`, and return where they differ.
+///
+/// Values at pointers matching any of `ignore` are not compared. A `*`
+/// segment of a pattern matches any member or index, so `/result/*/updated`
+/// ignores `updated` of every element of `result`.
+pub fn diff(primary: &Value, shadow: &Value, ignore: &[String]) -> Vec<Difference> {
+    let patterns = ignore
+        .iter()
+        .map(|pattern| segments(pattern))
+        .collect::<Vec<_>>();
+    let mut differences = Vec::new();
+    walk(
+        &mut Vec::new(),
+        Some(primary),
+        Some(shadow),
+        &patterns,
+        &mut differences,
+    );
+    differences
+}
+
+pub(crate) fn segments(pointer: &str) -> Vec<String> {
+    pointer
+        .split('/')
+        .skip(1)
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+fn pointer(path: &[String]) -> String {
+    path.iter()
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+fn ignored(path: &[String], patterns: &[Vec<String>]) -> bool {
+    patterns.iter().any(|pattern| {
+        pattern.len() == path.len()
+            && pattern
+                .iter()
+                .zip(path)
+                .all(|(pattern, segment)| pattern == "*" || pattern == segment)
+    })
+}
+
+fn walk(
+    path: &mut Vec<String>,
+    primary: Option<&Value>,
+    shadow: Option<&Value>,
+    patterns: &[Vec<String>],
+    differences: &mut Vec<Difference>,
+) {
+    if ignored(path, patterns) {
+        return;
+    }
+    match (primary, shadow) {
+        (Some(Value::Object(lhs)), Some(Value::Object(rhs))) => {
+            let mut keys = lhs.keys().chain(rhs.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                path.push(key.clone());
+                walk(path, lhs.get(key), rhs.get(key), patterns, differences);
+                path.pop();
+            }
+        }
+        (Some(Value::Array(lhs)), Some(Value::Array(rhs))) => {
+            for i in 0..lhs.len().max(rhs.len()) {
+                path.push(i.to_string());
+                walk(path, lhs.get(i), rhs.get(i), patterns, differences);
+                path.pop();
+            }
+        }
+        (lhs, rhs) if lhs != rhs => differences.push(Difference {
+            pointer: pointer(path),
+            primary: lhs.cloned(),
+            shadow: rhs.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+/// Receives differences between the results of shadowed methods and their
+/// shadows.
+pub trait DiffReporter: Send + Sync {
+    /// Called for every shadowed call, with no `differences` if the results
+    /// are the same.
+    fn report(&self, method: &str, differences: &[Difference]);
+}
+
+impl<F> DiffReporter for F
+where
+    F: Fn(&str, &[Difference]) + Send + Sync,
+{
+    fn report(&self, method: &str, differences: &[Difference]) {
+        self(method, differences)
+    }
+}
+
+/// A `DiffReporter` logging divergent calls.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogReporter;
+
+impl DiffReporter for LogReporter {
+    fn report(&self, method: &str, differences: &[Difference]) {
+        if differences.is_empty() {
+            return;
+        }
+        let differences = serde_json::to_string(differences).unwrap_or_default();
+        log::warn!(target: "warp_json_rpc", "Shadow of \"{}\" RPC diverges: {}", method, differences);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn structural_diff() {
+        let primary = json!({ "result": { "items": [1, 2], "name": "a", "at": 1 } });
+        let shadow = json!({ "result": { "items": [1, 3, 4], "at": 2 } });
+        let differences = diff(&primary, &shadow, &["/result/at".to_string()]);
+        assert_eq!(
+            differences,
+            vec![
+                Difference {
+                    pointer: "/result/items/1".to_string(),
+                    primary: Some(json!(2)),
+                    shadow: Some(json!(3)),
+                },
+                Difference {
+                    pointer: "/result/items/2".to_string(),
+                    primary: None,
+                    shadow: Some(json!(4)),
+                },
+                Difference {
+                    pointer: "/result/name".to_string(),
+                    primary: Some(json!("a")),
+                    shadow: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore_by_wildcard() {
+        let primary = json!([{ "id": 1, "at": 1 }, { "id": 2, "at": 1 }]);
+        let shadow = json!([{ "id": 1, "at": 2 }, { "id": 2, "at": 3 }]);
+        assert!(diff(&primary, &shadow, &["/*/at".to_string()]).is_empty());
+        assert_eq!(diff(&primary, &shadow, &[]).len(), 2);
+    }
+}
@@ -0,0 +1,406 @@
+use crate::{flags, router::Method, Context, Error, RpcRouter};
+use futures::future::FutureExt as _;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::{fmt::Write as _, sync::Arc};
+use warp::{reply::Html, Filter, Rejection};
+
+/// Name of the method registered by [`RpcRouter::rpc_discover`].
+///
+/// [`RpcRouter::rpc_discover`]: ./struct.RpcRouter.html#method.rpc_discover
+pub const RPC_DISCOVER: &str = "rpc.discover";
+
+/// Human readable documentation of a method.
+///
+/// ```
+/// # use warp_json_rpc::{Error, MethodDoc, RpcRouter};
+/// use serde_json::json;
+///
+/// let mut router = RpcRouter::new();
+/// router
+///     .method("add", |(lhs, rhs): (i64, i64)| async move { Ok::<_, Error>(lhs + rhs) })
+///     .doc(
+///         "add",
+///         MethodDoc::new("Add two integers.")
+///             .with_param("lhs", "The left operand.", true)
+///             .with_param("rhs", "The right operand.", true)
+///             .with_example("one plus two", json!([1, 2]), json!(3)),
+///     )
+///     .rpc_discover("Calculator", "1.0.0");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MethodDoc {
+    pub description: String,
+    pub params: Vec<ParamDoc>,
+    pub examples: Vec<Example>,
+}
+
+/// Documentation of a parameter, by name for named parameters or in order
+/// for positional ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParamDoc {
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+}
+
+/// An example call of a method.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Example {
+    pub name: String,
+    pub params: Value,
+    pub result: Value,
+}
+
+impl MethodDoc {
+    pub fn new(description: impl Into<String>) -> MethodDoc {
+        MethodDoc {
+            description: description.into(),
+            ..MethodDoc::default()
+        }
+    }
+
+    pub fn with_param(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        required: bool,
+    ) -> MethodDoc {
+        self.params.push(ParamDoc {
+            name: name.into(),
+            description: description.into(),
+            required,
+        });
+        self
+    }
+
+    pub fn with_example(
+        mut self,
+        name: impl Into<String>,
+        params: Value,
+        result: Value,
+    ) -> MethodDoc {
+        self.examples.push(Example {
+            name: name.into(),
+            params,
+            result,
+        });
+        self
+    }
+}
+
+impl RpcRouter {
+    /// Attach documentation to the method.
+    ///
+    /// The documentation is served by [`rpc_discover`] and [`docs_page`].
+    ///
+    /// [`rpc_discover`]: #method.rpc_discover
+    /// [`docs_page`]: #method.docs_page
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn doc(&mut self, name: &str, doc: MethodDoc) -> &mut RpcRouter {
+        self.registered(name).doc = Some(doc);
+        self
+    }
+
+    /// Register the `rpc.discover` method, which returns an [OpenRPC]
+    /// document of the methods enabled for the caller.
+    ///
+    /// The document is made from the documentation given by [`doc`] and the
+    /// schemas given by [`params_schema`] and [`result_schema`]. It is taken
+    /// when the router is turned into a filter, so methods may be registered
+    /// after calling this.
+    ///
+    /// [OpenRPC]: https://spec.open-rpc.org/
+    /// [`doc`]: #method.doc
+    /// [`params_schema`]: #method.params_schema
+    /// [`result_schema`]: #method.result_schema
+    pub fn rpc_discover(
+        &mut self,
+        title: impl Into<String>,
+        version: impl Into<String>,
+    ) -> &mut RpcRouter {
+        self.discover = Some((title.into(), version.into()));
+        self
+    }
+
+    /// An [OpenRPC] document of every registered method.
+    ///
+    /// [OpenRPC]: https://spec.open-rpc.org/
+    pub fn openrpc(&self) -> Value {
+        let methods = self
+            .documented()
+            .into_iter()
+            .map(|(_, _, doc)| doc)
+            .collect();
+        self.openrpc_with(methods)
+    }
+
+    /// Create a `Filter` serving an HTML page documenting every method
+    /// registered so far.
+    ///
+    /// ```
+    /// # use warp_json_rpc::RpcRouter;
+    /// # use warp::Filter as _;
+    /// # let router = RpcRouter::new();
+    /// let docs = warp::path("docs").and(router.docs_page());
+    /// ```
+    pub fn docs_page(&self) -> impl Filter<Extract = (Html<String>,), Error = Rejection> + Clone {
+        let page = Arc::new(html(&self.openrpc()));
+        warp::get()
+            .and(warp::path::end())
+            .map(move || warp::reply::html(page.to_string()))
+    }
+
+    pub(crate) fn register_rpc_discover(&mut self) {
+        if self.discover.is_none() {
+            return;
+        }
+        let mut methods = self.documented();
+        let discover = json!({
+            "name": RPC_DISCOVER,
+            "description": "Returns an OpenRPC document of this service.",
+            "params": [],
+            "result": { "name": "result", "schema": {} },
+        });
+        methods.push((RPC_DISCOVER.to_string(), None, discover));
+        methods.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        let document = self.openrpc_with(Vec::new());
+        let resolver = self.flags.clone();
+        self.register(
+            RPC_DISCOVER,
+            Arc::new(move |_, ctx: Context| {
+                let mut document = document.clone();
+                document["methods"] = methods
+                    .iter()
+                    .filter(|(_, flag, _)| flags::enabled(flag.as_deref(), resolver.as_ref(), &ctx))
+                    .map(|(_, _, doc)| doc.clone())
+                    .collect();
+                futures::future::ready(Ok::<_, Error>(document)).boxed()
+            }),
+        );
+        // Replacing the placeholder is not a mistake of the user.
+        self.replaced
+            .retain(|duplicate| duplicate.name != RPC_DISCOVER);
+    }
+
+    fn openrpc_with(&self, methods: Vec<Value>) -> Value {
+        let (title, version) = self
+            .discover
+            .clone()
+            .unwrap_or_else(|| (String::new(), String::new()));
+        json!({
+            "openrpc": "1.2.6",
+            "info": { "title": title, "version": version },
+            "methods": methods,
+        })
+    }
+
+    fn documented(&self) -> Vec<(String, Option<String>, Value)> {
+        let mut methods = self
+            .methods
+            .iter()
+            .filter(|(_, method)| self.exposes(method.visibility))
+            .map(|(name, method)| (name.clone(), method.flag.clone(), describe(name, method)))
+            .collect::<Vec<_>>();
+        methods.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        methods
+    }
+}
+
+fn describe(name: &str, method: &Method) -> Value {
+    let doc = method.doc.clone().unwrap_or_default();
+    let params_schema = method
+        .params_schema
+        .as_ref()
+        .map(|schema| schema.as_value());
+    let params = doc
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| {
+            let schema = params_schema
+                .and_then(|schema| {
+                    schema["properties"]
+                        .get(&param.name)
+                        .or_else(|| schema["items"].get(i))
+                })
+                .cloned()
+                .unwrap_or_else(|| json!({}));
+            json!({
+                "name": param.name,
+                "description": param.description,
+                "required": param.required,
+                "schema": schema,
+            })
+        })
+        .collect::<Vec<_>>();
+    let examples = doc
+        .examples
+        .iter()
+        .map(|example| {
+            json!({
+                "name": example.name,
+                "params": example_params(&doc, &example.params),
+                "result": { "name": "result", "value": example.result },
+            })
+        })
+        .collect::<Vec<_>>();
+    let result_schema = method
+        .result_schema
+        .as_ref()
+        .map_or_else(|| json!({}), |schema| schema.as_value().clone());
+
+    let mut described = Map::new();
+    described.insert("name".to_string(), Value::from(name));
+    if !doc.description.is_empty() {
+        described.insert("description".to_string(), Value::from(doc.description));
+    }
+    described.insert("params".to_string(), Value::from(params));
+    described.insert(
+        "result".to_string(),
+        json!({ "name": "result", "schema": result_schema }),
+    );
+    if !examples.is_empty() {
+        described.insert("examples".to_string(), Value::from(examples));
+    }
+    Value::Object(described)
+}
+
+/// Example params as OpenRPC example pairings, which name every value.
+fn example_params(doc: &MethodDoc, params: &Value) -> Value {
+    let name = |i: usize| {
+        doc.params
+            .get(i)
+            .map_or_else(|| i.to_string(), |param| param.name.clone())
+    };
+    match params {
+        Value::Object(params) => params
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect(),
+        Value::Array(params) => params
+            .iter()
+            .enumerate()
+            .map(|(i, value)| json!({ "name": name(i), "value": value }))
+            .collect(),
+        Value::Null => Value::Array(Vec::new()),
+        params => json!([{ "name": "params", "value": params }]),
+    }
+}
+
+fn html(document: &Value) -> String {
+    let text = |value: &Value| escape(value.as_str().unwrap_or(""));
+    let mut page = String::new();
+    let title = text(&document["info"]["title"]);
+    let _ = write!(
+        page,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{} {}</h1>\n",
+        title,
+        title,
+        text(&document["info"]["version"])
+    );
+    for method in document["methods"].as_array().into_iter().flatten() {
+        let name = text(&method["name"]);
+        let _ = write!(page, "<section id=\"{}\">\n<h2>{}</h2>\n", name, name);
+        if let Some(description) = method["description"].as_str() {
+            let _ = writeln!(page, "<p>{}</p>", escape(description));
+        }
+        let params = method["params"].as_array().cloned().unwrap_or_default();
+        if !params.is_empty() {
+            page.push_str(
+                "<table>\n<tr><th>Parameter</th><th>Required</th><th>Description</th></tr>\n",
+            );
+            for param in &params {
+                let _ = writeln!(
+                    page,
+                    "<tr><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+                    text(&param["name"]),
+                    if param["required"] == true {
+                        "yes"
+                    } else {
+                        "no"
+                    },
+                    text(&param["description"])
+                );
+            }
+            page.push_str("</table>\n");
+        }
+        for example in method["examples"].as_array().into_iter().flatten() {
+            let _ = writeln!(
+                page,
+                "<h3>{}</h3>\n<pre>{}</pre>",
+                text(&example["name"]),
+                escape(&serde_json::to_string_pretty(example).unwrap_or_default())
+            );
+        }
+        page.push_str("</section>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Schema;
+
+    fn router() -> RpcRouter {
+        let mut router = RpcRouter::new();
+        router
+            .method("add", |(lhs, rhs): (i64, i64)| async move {
+                Ok::<_, Error>(lhs + rhs)
+            })
+            .params_schema(
+                "add",
+                Schema::new(json!({ "items": [{ "type": "integer" }, { "type": "integer" }] })),
+            )
+            .doc(
+                "add",
+                MethodDoc::new("Add <two> integers.")
+                    .with_param("lhs", "The left operand.", true)
+                    .with_param("rhs", "The right operand.", true)
+                    .with_example("one plus two", json!([1, 2]), json!(3)),
+            )
+            .rpc_discover("Calculator", "1.0.0");
+        router
+    }
+
+    #[test]
+    fn openrpc_document() {
+        let document = router().openrpc();
+        assert_eq!(document["info"]["title"], "Calculator");
+        let add = &document["methods"][0];
+        assert_eq!(add["name"], "add");
+        assert_eq!(add["params"][1]["name"], "rhs");
+        assert_eq!(add["params"][1]["schema"], json!({ "type": "integer" }));
+        assert_eq!(
+            add["examples"][0]["params"],
+            json!([{ "name": "lhs", "value": 1 }, { "name": "rhs", "value": 2 }])
+        );
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        let page = html(&router().openrpc());
+        assert!(page.contains("<h2>add</h2>"));
+        assert!(page.contains("Add &lt;two&gt; integers."));
+    }
+}
@@ -0,0 +1,72 @@
+use crate::RpcRouter;
+use std::panic::Location;
+
+/// What to do when a method is registered again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Replace the earlier handler, logging a warning. This is the default.
+    Replace,
+    /// Panic, naming both places the method is registered at.
+    Panic,
+}
+
+impl Default for Duplicates {
+    fn default() -> Duplicates {
+        Duplicates::Replace
+    }
+}
+
+/// A method registered again, found by `register`.
+#[derive(Debug, Clone)]
+pub(crate) struct Duplicate {
+    pub(crate) name: String,
+    pub(crate) first: &'static Location<'static>,
+    pub(crate) again: &'static Location<'static>,
+}
+
+impl RpcRouter {
+    /// Decide what happens when a method is registered more than once.
+    ///
+    /// Registration functions are `#[track_caller]`, so both places are
+    /// named by the warning, the panic and [`preflight`].
+    ///
+    /// [`preflight`]: #method.preflight
+    pub fn on_duplicate(&mut self, duplicates: Duplicates) -> &mut RpcRouter {
+        self.duplicates = duplicates;
+        self
+    }
+
+    pub(crate) fn duplicated(&mut self, duplicate: Duplicate) {
+        match self.duplicates {
+            Duplicates::Replace => {
+                log::warn!(
+                    target: "warp_json_rpc",
+                    "RPC method \"{}\" registered at {} is replaced by the one at {}",
+                    duplicate.name,
+                    duplicate.first,
+                    duplicate.again
+                );
+                self.replaced.push(duplicate);
+            }
+            Duplicates::Panic => panic!(
+                "RPC method \"{}\" is registered at {} and again at {}",
+                duplicate.name, duplicate.first, duplicate.again
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    #[should_panic(expected = "src/duplicate.rs")]
+    fn panic_names_both_places() {
+        RpcRouter::new()
+            .on_duplicate(Duplicates::Panic)
+            .method("a", |(): ()| async move { Ok::<_, Error>(()) })
+            .method("a", |(): ()| async move { Ok::<_, Error>(()) });
+    }
+}
@@ -0,0 +1,291 @@
+//! Conventions of the Ethereum JSON RPC API.
+//!
+//! ```
+//! use warp_json_rpc::{eth::{BlockNumber, Quantity}, Error, RpcRouter};
+//!
+//! let mut router = RpcRouter::new();
+//! router.method(
+//!     "eth_getBalance",
+//!     |(address, block): (String, Option<BlockNumber>)| async move {
+//!         Ok::<_, Error>(Quantity::from(1_000_000_000_000_000_000_u64))
+//!     },
+//! );
+//! ```
+use crate::{binary::HexBytes, decimal::U256String, Error};
+use rand::Rng as _;
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize, Serialize, Serializer,
+};
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+/// Invalid method parameter, of EIP-1474.
+pub const INVALID_INPUT_CODE: i64 = -32000;
+/// Requested resource not found, of EIP-1474.
+pub const RESOURCE_NOT_FOUND_CODE: i64 = -32001;
+/// Requested resource not available, of EIP-1474.
+pub const RESOURCE_UNAVAILABLE_CODE: i64 = -32002;
+/// Transaction creation failed, of EIP-1474.
+pub const TRANSACTION_REJECTED_CODE: i64 = -32003;
+/// Method is not implemented, of EIP-1474.
+pub const METHOD_NOT_SUPPORTED_CODE: i64 = -32004;
+/// Request exceeds a defined limit, of EIP-1474.
+pub const LIMIT_EXCEEDED_CODE: i64 = -32005;
+/// Version of JSON RPC protocol is not supported, of EIP-1474.
+pub const VERSION_NOT_SUPPORTED_CODE: i64 = -32006;
+/// Code used by geth when a call is reverted.
+pub const EXECUTION_REVERTED_CODE: i64 = 3;
+
+/// Unformatted data, such as an address, a hash or a transaction.
+///
+/// Serialized as `0x` prefixed lowercase hex. `0x` can be omitted when
+/// deserializing.
+pub type Data<const MAX: usize = { usize::MAX }> = HexBytes<MAX>;
+
+/// The error returned by geth when a call is reverted, whose `data` is the
+/// revert reason.
+pub fn execution_reverted(reason: Data) -> Error {
+    Error::custom(EXECUTION_REVERTED_CODE, "execution reverted").with_data(reason)
+}
+
+/// A subscription id in the format of geth: `0x` followed by 32 random hex
+/// digits without leading zeros.
+pub fn subscription_id() -> String {
+    let id = rand::thread_rng().gen::<u128>();
+    format!("{:#x}", id)
+}
+
+/// An error of parsing a [`Quantity`] or a [`BlockNumber`].
+///
+/// [`Quantity`]: ./struct.Quantity.html
+/// [`BlockNumber`]: ./enum.BlockNumber.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// An integer encoded as the Ethereum API does: `0x` prefixed hex without
+/// leading zeros, such as `0x0` and `0x41`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Quantity(pub U256String);
+
+impl Quantity {
+    /// The value if it fits in `u64`, as block numbers and gas do.
+    pub fn to_u64(&self) -> Option<u64> {
+        self.0.to_u128().and_then(|n| u64::try_from(n).ok())
+    }
+}
+
+impl From<u64> for Quantity {
+    fn from(n: u64) -> Quantity {
+        Quantity(U256String::from(n))
+    }
+}
+
+impl From<u128> for Quantity {
+    fn from(n: u128) -> Quantity {
+        Quantity(U256String::from(n))
+    }
+}
+
+impl From<U256String> for Quantity {
+    fn from(n: U256String) -> Quantity {
+        Quantity(n)
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Quantity, ParseError> {
+        let invalid = || ParseError(format!("invalid quantity {:?}", s));
+        let hex = s.strip_prefix("0x").ok_or_else(invalid)?;
+        if hex.is_empty() || (hex.len() > 1 && hex.starts_with('0')) {
+            return Err(invalid());
+        }
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        s.parse::<U256String>()
+            .map(Quantity)
+            .map_err(|e| ParseError(e.to_string()))
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:x}", self.0)
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D>(deserializer: D) -> Result<Quantity, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(FromStrVisitor::<Quantity>::new("a hex quantity"))
+    }
+}
+
+/// A block specified by number or by tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockNumber {
+    Earliest,
+    Latest,
+    Pending,
+    Safe,
+    Finalized,
+    Number(Quantity),
+}
+
+impl Default for BlockNumber {
+    fn default() -> BlockNumber {
+        BlockNumber::Latest
+    }
+}
+
+impl FromStr for BlockNumber {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<BlockNumber, ParseError> {
+        match s {
+            "earliest" => Ok(BlockNumber::Earliest),
+            "latest" => Ok(BlockNumber::Latest),
+            "pending" => Ok(BlockNumber::Pending),
+            "safe" => Ok(BlockNumber::Safe),
+            "finalized" => Ok(BlockNumber::Finalized),
+            s => s
+                .parse()
+                .map(BlockNumber::Number)
+                .map_err(|_| ParseError(format!("invalid block number {:?}", s))),
+        }
+    }
+}
+
+impl fmt::Display for BlockNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockNumber::Earliest => f.write_str("earliest"),
+            BlockNumber::Latest => f.write_str("latest"),
+            BlockNumber::Pending => f.write_str("pending"),
+            BlockNumber::Safe => f.write_str("safe"),
+            BlockNumber::Finalized => f.write_str("finalized"),
+            BlockNumber::Number(n) => n.fmt(f),
+        }
+    }
+}
+
+impl Serialize for BlockNumber {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockNumber {
+    fn deserialize<D>(deserializer: D) -> Result<BlockNumber, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(FromStrVisitor::<BlockNumber>::new(
+            "a block tag or a hex block number",
+        ))
+    }
+}
+
+struct FromStrVisitor<T> {
+    expecting: &'static str,
+    parsed: std::marker::PhantomData<T>,
+}
+
+impl<T> FromStrVisitor<T> {
+    fn new(expecting: &'static str) -> FromStrVisitor<T> {
+        FromStrVisitor {
+            expecting,
+            parsed: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'de, T> Visitor<'de> for FromStrVisitor<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<T, E> {
+        s.parse().map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quantity() {
+        assert_eq!(
+            serde_json::to_string(&Quantity::from(0_u64)).unwrap(),
+            r#""0x0""#
+        );
+        assert_eq!(
+            serde_json::to_string(&Quantity::from(65_u64)).unwrap(),
+            r#""0x41""#
+        );
+        assert_eq!(
+            serde_json::from_str::<Quantity>(r#""0x400""#)
+                .unwrap()
+                .to_u64(),
+            Some(1024)
+        );
+        for invalid in &["0x", "0x0400", "ff", "0xzz"] {
+            assert!(invalid.parse::<Quantity>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn block_number() {
+        let blocks = serde_json::from_str::<Vec<BlockNumber>>(r#"["latest", "0x10"]"#).unwrap();
+        assert_eq!(
+            blocks,
+            vec![
+                BlockNumber::Latest,
+                BlockNumber::Number(Quantity::from(16_u64))
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&blocks).unwrap(),
+            r#"["latest","0x10"]"#
+        );
+        assert!(serde_json::from_str::<BlockNumber>(r#""newest""#).is_err());
+    }
+
+    #[test]
+    fn subscription_id_format() {
+        let id = subscription_id();
+        assert!(id.starts_with("0x") && id.len() <= 34);
+        assert!(!id[2..].starts_with('0'));
+    }
+}
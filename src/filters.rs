@@ -1,10 +1,11 @@
 use crate::{
+    rejection::InvalidRequest,
     store::{self, LazyReqStore},
-    Builder, Request,
+    Builder, Request, StatusMapping,
 };
 use futures::future;
 use serde::Deserialize;
-use warp::{filters, reject, Filter, Rejection};
+use warp::{filters, hyper::body::Bytes, reject, Filter, Rejection};
 
 /// Create a [`Filter`] that requires and initializes JSON RPC handling.
 ///
@@ -23,19 +24,32 @@ use warp::{filters, reject, Filter, Rejection};
 pub fn json_rpc() -> impl Filter<Extract = (Builder,), Error = Rejection> + Copy {
     filters::method::post()
         .and(filters::header::exact("Content-Type", "application/json"))
-        // Get and set `Request` if it is not stored already.
+        // Parse the body and store the outcome if it is not stored already.
+        // The body is read at most once even if this filter is composed by `or`.
         .and(store::filled().or(store_req()))
         .map(|_| ())
         .untuple_one()
-        .and(store::stored_req().map(|req: Request| Builder::new(req.id())))
+        .and(store::stored_req())
+        .and(filters::ext::optional::<StatusMapping>())
+        .map(|req: Request, status: Option<StatusMapping>| {
+            Builder::new(req.id(), status.unwrap_or_default())
+        })
 }
 
 fn store_req() -> impl Filter<Extract = (), Error = Rejection> + Copy {
-    filters::body::json::<Request>()
+    filters::body::bytes()
         .and(store::store())
-        .map(|req: Request, store: LazyReqStore| {
+        .map(|body: Bytes, store: LazyReqStore| {
+            #[cfg(test)]
+            store
+                .parses
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let parsed = serde_json::from_slice::<Request>(&body).map_err(|e| {
+                log::debug!(target: "warp_json_rpc", "Invalid JSON RPC request: {}", e);
+                InvalidRequest::from_json(&e)
+            });
             store
-                .fill(req)
+                .fill(parsed)
                 .expect("LazyReqStore is filled more than twice");
         })
         .untuple_one()
@@ -54,9 +68,11 @@ fn store_req() -> impl Filter<Extract = (), Error = Rejection> + Copy {
 /// let rpc = json_rpc().and(method("greet"));
 /// ```
 pub fn method(name: &'static str) -> impl Filter<Extract = (), Error = Rejection> + Copy {
-    store::stored_req()
-        .and_then(move |req: Request| {
-            if req.method() == name {
+    // Match on the cached request without cloning it, since this runs once per
+    // `or` branch.
+    store::store()
+        .and_then(move |store: LazyReqStore| {
+            if store.method() == Some(name) {
                 log::info!(target: "warp_json_rpc", "\"{}\" RPC", name);
                 future::ok(())
             } else {
@@ -86,3 +102,92 @@ where
         future::ready(req.deserialize_param::<T>().map_err(|_| reject::reject()))
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use warp::filters::BoxedFilter;
+
+    fn request(body: &str) -> warp::test::RequestBuilder {
+        request_with(body, LazyReqStore::empty())
+    }
+
+    fn request_with(body: &str, store: LazyReqStore) -> warp::test::RequestBuilder {
+        warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .extension(store)
+            .body(body)
+    }
+
+    #[tokio::test]
+    async fn or_branches_share_parsed_request() {
+        let filter = json_rpc()
+            .and(method("sub"))
+            .map(|_| "sub")
+            .or(json_rpc().and(method("add")).map(|_| "add"))
+            .unify();
+
+        let body = r#"{"jsonrpc": "2.0", "method": "add", "id": 1}"#;
+        let res = request(body).filter(&filter).await.unwrap();
+        assert_eq!(res, "add");
+    }
+
+    #[tokio::test]
+    async fn or_branches_share_parse_failure() {
+        let filter = json_rpc()
+            .and(method("sub"))
+            .or(json_rpc().and(method("add")));
+
+        let store = LazyReqStore::empty();
+        let rejection = request_with("{ invalid }", store.clone())
+            .filter(&filter)
+            .await
+            .err()
+            .unwrap();
+        assert!(rejection.find::<InvalidRequest>().is_some());
+        assert_eq!(store.parses.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_service() {
+        let rejection = warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .body(r#"{"jsonrpc": "2.0", "method": "add", "id": 1}"#)
+            .filter(&json_rpc())
+            .await
+            .err()
+            .unwrap();
+        assert!(rejection
+            .find::<crate::rejection::MissingService>()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn many_or_branches() {
+        fn branch(name: &'static str) -> BoxedFilter<(&'static str,)> {
+            json_rpc().and(method(name)).map(move |_| name).boxed()
+        }
+
+        const NAMES: [&str; 50] = [
+            "m00", "m01", "m02", "m03", "m04", "m05", "m06", "m07", "m08", "m09", "m10", "m11",
+            "m12", "m13", "m14", "m15", "m16", "m17", "m18", "m19", "m20", "m21", "m22", "m23",
+            "m24", "m25", "m26", "m27", "m28", "m29", "m30", "m31", "m32", "m33", "m34", "m35",
+            "m36", "m37", "m38", "m39", "m40", "m41", "m42", "m43", "m44", "m45", "m46", "m47",
+            "m48", "m49",
+        ];
+        let filter = NAMES[1..].iter().fold(branch(NAMES[0]), |acc, name| {
+            acc.or(branch(name)).unify().boxed()
+        });
+
+        let store = LazyReqStore::empty();
+        let body = r#"{"jsonrpc": "2.0", "method": "m49", "id": 1}"#;
+        let res = request_with(body, store.clone())
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(res, "m49");
+        assert_eq!(store.parses.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+}
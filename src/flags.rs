@@ -0,0 +1,137 @@
+use crate::{router::Method, Context, Error, RpcRouter};
+use futures::future::FutureExt as _;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Name of the method registered by [`RpcRouter::rpc_methods`].
+///
+/// [`RpcRouter::rpc_methods`]: ./struct.RpcRouter.html#method.rpc_methods
+pub const RPC_METHODS: &str = "rpc_methods";
+
+pub(crate) type FlagResolver = Arc<dyn Fn(&str, &Context) -> bool + Send + Sync>;
+
+impl RpcRouter {
+    /// Decide whether a feature flag is enabled for a call by `resolve`.
+    ///
+    /// `resolve` receives the flag and the context of the call, so flags can
+    /// be enabled per tenant (e.g. by a header) or for a percentage of
+    /// callers by [`in_rollout`].
+    ///
+    /// [`in_rollout`]: ./fn.in_rollout.html
+    pub fn feature_flags<F>(&mut self, resolve: F) -> &mut RpcRouter
+    where
+        F: Fn(&str, &Context) -> bool + Send + Sync + 'static,
+    {
+        self.flags = Some(Arc::new(resolve));
+        self
+    }
+
+    /// Enable the method only for calls for which `flag` is enabled.
+    ///
+    /// Other calls are answered by `METHOD_NOT_FOUND` error, and the method
+    /// is not listed by `rpc_methods` for them. If [`feature_flags`] is not
+    /// called, every flag is disabled.
+    ///
+    /// [`feature_flags`]: #method.feature_flags
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn gated(&mut self, name: &str, flag: impl Into<String>) -> &mut RpcRouter {
+        self.registered(name).flag = Some(flag.into());
+        self
+    }
+
+    /// Register the `rpc_methods` method, which lists the names of the
+    /// methods enabled for the caller in alphabetical order.
+    ///
+    /// The list is taken when the router is turned into a filter, so methods
+    /// may be registered after calling this.
+    pub fn rpc_methods(&mut self) -> &mut RpcRouter {
+        self.list_methods = true;
+        self
+    }
+
+    pub(crate) fn enabled(&self, method: &Method, ctx: &Context) -> bool {
+        enabled(method.flag.as_deref(), self.flags.as_ref(), ctx)
+    }
+
+    pub(crate) fn register_rpc_methods(&mut self) {
+        if !self.list_methods {
+            return;
+        }
+        let mut methods = self
+            .methods
+            .iter()
+            .map(|(name, method)| (name.clone(), method.flag.clone()))
+            .chain(std::iter::once((RPC_METHODS.to_string(), None)))
+            .collect::<Vec<_>>();
+        methods.sort();
+        methods.dedup();
+        let flags = self.flags.clone();
+        self.register(
+            RPC_METHODS,
+            Arc::new(move |_, ctx: Context| {
+                let names = methods
+                    .iter()
+                    .filter(|(_, flag)| enabled(flag.as_deref(), flags.as_ref(), &ctx))
+                    .map(|(name, _)| Value::from(name.as_str()))
+                    .collect();
+                futures::future::ready(Ok::<_, Error>(Value::Array(names))).boxed()
+            }),
+        );
+    }
+}
+
+pub(crate) fn enabled(flag: Option<&str>, flags: Option<&FlagResolver>, ctx: &Context) -> bool {
+    match (flag, flags) {
+        (None, _) => true,
+        (Some(flag), Some(resolve)) => resolve(flag, ctx),
+        (Some(_), None) => false,
+    }
+}
+
+/// Whether `key` (e.g. a user or tenant id) is among `percent` percent of keys
+/// for which `flag` is rolled out.
+///
+/// The same key is always in or out for the same flag, and raising `percent`
+/// only adds keys.
+pub fn in_rollout(flag: &str, key: &str, percent: u8) -> bool {
+    let hash = fnv1a(flag.bytes().chain(std::iter::once(0)).chain(key.bytes()));
+    hash % 100 < percent as u64
+}
+
+/// FNV-1a, which is stable across processes and releases unlike the standard
+/// library hasher.
+fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rollout_is_stable_and_monotonic() {
+        let keys = (0..1000).map(|n| n.to_string()).collect::<Vec<_>>();
+        let count = |percent| {
+            keys.iter()
+                .filter(|key| in_rollout("f", key, percent))
+                .count()
+        };
+        assert_eq!(count(0), 0);
+        assert_eq!(count(100), 1000);
+        let (ten, fifty) = (count(10), count(50));
+        assert!(ten > 50 && ten < 150, "{}", ten);
+        assert!(fifty > 400 && fifty < 600, "{}", fifty);
+        assert!(keys
+            .iter()
+            .filter(|key| in_rollout("f", key, 10))
+            .all(|key| in_rollout("f", key, 50)));
+    }
+}
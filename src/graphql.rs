@@ -0,0 +1,174 @@
+//! Serve GraphQL queries through a JSON RPC method.
+//!
+//! [`GraphQl`] is a [`Plugin`] registering the `graphql_query` method, which
+//! passes the query to a user provided executor.
+//!
+//! ```
+//! use warp_json_rpc::{graphql::{GraphQl, Query, QueryResponse}, RpcRouter};
+//!
+//! let mut router = RpcRouter::new();
+//! router.plugin(GraphQl::new(|query: Query| async move {
+//!     // Run `query` by your GraphQL library.
+//!     QueryResponse::data(serde_json::json!({ "hello": "world" }))
+//! }));
+//! ```
+//!
+//! [`GraphQl`]: ./struct.GraphQl.html
+//! [`Plugin`]: ../trait.Plugin.html
+use crate::{Error, Plugin, RpcRouter};
+use futures::future::Future;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{borrow::Cow, sync::Arc};
+
+/// Name of the method registered by [`GraphQl`].
+///
+/// [`GraphQl`]: ./struct.GraphQl.html
+pub const METHOD: &str = "graphql_query";
+
+/// Error code used when a query fails without producing any data.
+pub const QUERY_ERROR_CODE: i64 = -32000;
+
+/// Parameter of the `graphql_query` method.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Query {
+    pub query: String,
+    #[serde(default)]
+    pub variables: Option<Value>,
+    #[serde(default)]
+    pub operation_name: Option<String>,
+}
+
+/// Response of a GraphQL executor.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct QueryResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<QueryError>,
+}
+
+impl QueryResponse {
+    pub fn data(data: Value) -> QueryResponse {
+        QueryResponse {
+            data: Some(data),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn errors(errors: Vec<QueryError>) -> QueryResponse {
+        QueryResponse { data: None, errors }
+    }
+}
+
+/// A GraphQL error, as defined by the GraphQL specification.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct QueryError {
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<Location>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Value>,
+}
+
+impl QueryError {
+    pub fn new<S>(message: S) -> QueryError
+    where
+        S: Into<String>,
+    {
+        QueryError {
+            message: message.into(),
+            locations: Vec::new(),
+            path: Vec::new(),
+            extensions: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Location {
+    pub line: u64,
+    pub column: u64,
+}
+
+/// Convert GraphQL errors into a JSON RPC error, carrying them in `data`.
+pub fn into_error(errors: Vec<QueryError>) -> Error {
+    let message = match errors.first() {
+        Some(first) => Cow::Owned(first.message.clone()),
+        None => Cow::Borrowed("GraphQL query failed"),
+    };
+    Error::custom(QUERY_ERROR_CODE, message).with_data(errors)
+}
+
+/// A [`Plugin`] registering the `graphql_query` method.
+///
+/// A response with `data` is returned as the RPC result, including partial
+/// `errors` if any. A response without `data` is turned into a JSON RPC error
+/// by [`into_error`].
+///
+/// [`Plugin`]: ../trait.Plugin.html
+/// [`into_error`]: ./fn.into_error.html
+pub struct GraphQl<E> {
+    executor: Arc<E>,
+}
+
+impl<E, Fut> GraphQl<E>
+where
+    E: Fn(Query) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = QueryResponse> + Send + 'static,
+{
+    pub fn new(executor: E) -> GraphQl<E> {
+        GraphQl {
+            executor: Arc::new(executor),
+        }
+    }
+}
+
+impl<E, Fut> Plugin for GraphQl<E>
+where
+    E: Fn(Query) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = QueryResponse> + Send + 'static,
+{
+    fn register(&self, router: &mut RpcRouter) {
+        let executor = self.executor.clone();
+        router.method(METHOD, move |query: Query| {
+            let res = executor(query);
+            async move {
+                let res = res.await;
+                if res.data.is_none() && !res.errors.is_empty() {
+                    Err(into_error(res.errors))
+                } else {
+                    Ok(res)
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize_query() {
+        let query =
+            serde_json::from_str::<Query>(r#"{ "query": "{ hello }", "operationName": "Op" }"#)
+                .unwrap();
+        assert_eq!(query.query, "{ hello }");
+        assert_eq!(query.variables, None);
+        assert_eq!(query.operation_name, Some("Op".to_string()));
+    }
+
+    #[test]
+    fn errors_into_error_data() {
+        let error = into_error(vec![QueryError::new("Unknown field")]);
+        assert_eq!(error.code, QUERY_ERROR_CODE);
+        assert_eq!(error.message, "Unknown field");
+
+        let data = serde_json::to_value(error.data.unwrap()).unwrap();
+        assert_eq!(data, serde_json::json!([{ "message": "Unknown field" }]));
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    kv::{KvStore, MemoryStore},
+    multipart::Attachment,
+    outcome::Outcome,
+    sha256, Context, Error, RpcRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{sync::Arc, time::Duration};
+use tokio::runtime::Handle;
+
+/// HTTP header carrying the idempotency key of a request.
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+/// Error code answered when a call with the same idempotency key is still
+/// being handled.
+pub const IDEMPOTENCY_IN_FLIGHT_CODE: i64 = -32011;
+
+type Caller = Arc<dyn Fn(&Context) -> Option<String> + Send + Sync>;
+
+impl RpcRouter {
+    /// Honor the `Idempotency-Key` header.
+    ///
+    /// The outcome of a call carrying the header is kept for `window`. A retry
+    /// of the same method with the same key and parameter text within the
+    /// window is answered by the kept outcome without calling the handler
+    /// again, so client retries do not duplicate side effects. A retry while
+    /// the call is still being handled is answered by
+    /// `IDEMPOTENCY_IN_FLIGHT_CODE` error.
+    ///
+    /// Keys of different callers are told apart once the caller is decided by
+    /// [`idempotency_caller`].
+    ///
+    /// Outcomes are kept in memory of this process.
+    ///
+    /// [`idempotency_caller`]: #method.idempotency_caller
+    pub fn idempotency(&mut self, window: Duration) -> &mut RpcRouter {
+        self.idempotency_in(window, MemoryStore::new())
+    }
+
+    /// Honor the `Idempotency-Key` header as [`idempotency`], keeping
+    /// outcomes in `store`.
+    ///
+    /// [`idempotency`]: #method.idempotency
+    pub fn idempotency_in<S>(&mut self, window: Duration, store: S) -> &mut RpcRouter
+    where
+        S: KvStore + 'static,
+    {
+        self.idempotency = Some(Arc::new(IdempotencyCache {
+            window,
+            store: Arc::new(store),
+            caller: None,
+        }));
+        self
+    }
+
+    /// Keep the outcomes of each caller apart, as identified by `caller`,
+    /// e.g. by an API key header, so a caller can never be answered by the
+    /// outcome of a call of another caller with the same idempotency key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`idempotency`] or [`idempotency_in`] is not called before.
+    ///
+    /// [`idempotency`]: #method.idempotency
+    /// [`idempotency_in`]: #method.idempotency_in
+    pub fn idempotency_caller<F>(&mut self, caller: F) -> &mut RpcRouter
+    where
+        F: Fn(&Context) -> Option<String> + Send + Sync + 'static,
+    {
+        let cache = self
+            .idempotency
+            .as_mut()
+            .and_then(Arc::get_mut)
+            .expect("`RpcRouter::idempotency` must be called before `idempotency_caller`");
+        cache.caller = Some(Arc::new(caller));
+        self
+    }
+}
+
+pub(crate) struct IdempotencyCache {
+    window: Duration,
+    store: Arc<dyn KvStore>,
+    caller: Option<Caller>,
+}
+
+impl IdempotencyCache {
+    /// Where the outcome of a call of `method` with `key` is kept.
+    pub(crate) fn slot(
+        self: &Arc<Self>,
+        method: &str,
+        key: &str,
+        params: Option<&str>,
+        ctx: &Context,
+    ) -> Slot {
+        let caller = self.caller.as_ref().and_then(|caller| caller(ctx));
+        // The scope is a JSON array, so its parts can never read as others,
+        // and the parameter text follows its closing bracket.
+        let scope = serde_json::json!([caller, method, key]).to_string();
+        let digest = sha256::digest(&[scope.as_bytes(), params.unwrap_or("").as_bytes()]);
+        Slot {
+            cache: self.clone(),
+            key: format!("idempotency:{}", sha256::hex(&digest)),
+        }
+    }
+}
+
+/// What a slot holds.
+pub(crate) enum Found {
+    Kept(Result<Value, Error>, Vec<Attachment>),
+    InFlight,
+}
+
+impl Found {
+    pub(crate) fn in_flight() -> Error {
+        Error::custom(
+            IDEMPOTENCY_IN_FLIGHT_CODE,
+            "Call with this idempotency key is in flight",
+        )
+    }
+}
+
+pub(crate) struct Slot {
+    cache: Arc<IdempotencyCache>,
+    key: String,
+}
+
+impl Slot {
+    pub(crate) async fn get(&self) -> Option<Found> {
+        let bytes = match self.cache.store.get(&self.key).await {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                log::error!(target: "warp_json_rpc", "Failed to get idempotent outcome: {}", e);
+                return None;
+            }
+        };
+        match serde_json::from_slice::<Stored>(&bytes) {
+            Ok(Stored::InFlight) => Some(Found::InFlight),
+            Ok(Stored::Kept(kept)) => {
+                let (result, attachments) = kept.into_parts();
+                Some(Found::Kept(result, attachments))
+            }
+            Err(e) => {
+                log::error!(target: "warp_json_rpc", "Kept idempotent outcome is broken: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Mark the call as in flight, unless the slot holds something already,
+    /// which is returned instead.
+    ///
+    /// `Ok(None)` is returned if the store fails, so the call is handled
+    /// without keeping its outcome.
+    pub(crate) async fn reserve(self) -> Result<Option<Reservation>, Found> {
+        let marker = serde_json::to_vec(&Stored::InFlight).expect("marker is serializable");
+        let window = self.cache.window;
+        match self
+            .cache
+            .store
+            .put_if_absent(&self.key, marker, window)
+            .await
+        {
+            Ok(true) => Ok(Some(Reservation { slot: Some(self) })),
+            // Taken by another call, which may have ended since.
+            Ok(false) => Err(self.get().await.unwrap_or(Found::InFlight)),
+            Err(e) => {
+                log::error!(target: "warp_json_rpc", "Failed to reserve idempotency key: {}", e);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// A slot marked as in flight by this call, released if the call ends before
+/// its outcome is kept, e.g. since the client went away.
+pub(crate) struct Reservation {
+    slot: Option<Slot>,
+}
+
+impl Reservation {
+    pub(crate) async fn complete(
+        mut self,
+        result: &Result<Value, Error>,
+        attachments: &[Attachment],
+    ) {
+        let slot = self.slot.take().expect("reservation is completed once");
+        let kept = Stored::Kept(Kept {
+            outcome: Outcome::from_result(result),
+            attachments: attachments.iter().map(KeptAttachment::from).collect(),
+        });
+        let bytes = match serde_json::to_vec(&kept) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::error!(target: "warp_json_rpc", "Failed to serialize idempotent outcome: {}", e);
+                release(slot);
+                return;
+            }
+        };
+        let put = slot.cache.store.put(&slot.key, bytes, slot.cache.window);
+        if let Err(e) = put.await {
+            log::error!(target: "warp_json_rpc", "Failed to put idempotent outcome: {}", e);
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            release(slot);
+        }
+    }
+}
+
+fn release(slot: Slot) {
+    match Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(async move {
+                if let Err(e) = slot.cache.store.delete(&slot.key).await {
+                    log::error!(target: "warp_json_rpc", "Failed to release idempotency key: {}", e);
+                }
+            });
+        }
+        Err(_) => {
+            log::error!(target: "warp_json_rpc", "Idempotency key is not released outside of a runtime");
+        }
+    }
+}
+
+/// What a slot holds in the store.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum Stored {
+    InFlight,
+    Kept(Kept),
+}
+
+/// An outcome as kept in the store, with the attachments of its result.
+#[derive(Serialize, Deserialize)]
+struct Kept {
+    outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<KeptAttachment>,
+}
+
+impl Kept {
+    fn into_parts(self) -> (Result<Value, Error>, Vec<Attachment>) {
+        let attachments = self
+            .attachments
+            .into_iter()
+            .filter_map(|kept| {
+                let data = base64::decode(&kept.data).ok()?;
+                Some(Attachment::new(kept.content_id, kept.content_type, data))
+            })
+            .collect();
+        (self.outcome.into_result(), attachments)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeptAttachment {
+    content_id: String,
+    content_type: String,
+    /// Base64 of the data.
+    data: String,
+}
+
+impl From<&Attachment> for KeptAttachment {
+    fn from(attachment: &Attachment) -> KeptAttachment {
+        KeptAttachment {
+            content_id: attachment.content_id.clone(),
+            content_type: attachment.content_type.clone(),
+            data: base64::encode(&attachment.data),
+        }
+    }
+}
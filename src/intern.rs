@@ -0,0 +1,195 @@
+//! Interned names of registered methods.
+//!
+//! Names are interned by the router when methods are registered, and each
+//! gets a [`MethodId`] which middleware can compare methods by.
+//!
+//! The method name of a request is still allocated and hashed once when it
+//! is resolved, since the body is parsed before it reaches a router.
+//!
+//! [`MethodId`]: ./struct.MethodId.html
+use crate::RpcRouter;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use std::{collections::HashMap, fmt, sync::Arc};
+
+/// A cheaply comparable identifier of a registered method name.
+///
+/// Identifiers are given by each router, so they can only be compared with
+/// identifiers of the same router, as returned by [`RpcRouter::method_id`].
+///
+/// [`RpcRouter::method_id`]: ./struct.RpcRouter.html#method.method_id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MethodId(u32);
+
+impl RpcRouter {
+    /// The identifier of `name`, if a method of the name is registered.
+    pub fn method_id(&self, name: &str) -> Option<MethodId> {
+        self.methods.id(name)
+    }
+}
+
+/// Values of registered methods, keyed by the identifiers of their interned
+/// names.
+///
+/// Names stay interned once a value is removed, so identifiers are never
+/// reused.
+#[derive(Clone)]
+pub(crate) struct Methods<T> {
+    ids: HashMap<String, MethodId>,
+    names: Vec<Arc<String>>,
+    values: Vec<Option<T>>,
+}
+
+impl<T> Default for Methods<T> {
+    fn default() -> Methods<T> {
+        Methods {
+            ids: HashMap::new(),
+            names: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<T> Methods<T> {
+    fn intern(&mut self, name: String) -> MethodId {
+        let (names, values) = (&mut self.names, &mut self.values);
+        *self.ids.entry(name).or_insert_with_key(|name| {
+            names.push(Arc::new(name.clone()));
+            values.push(None);
+            MethodId(names.len() as u32 - 1)
+        })
+    }
+
+    pub(crate) fn id(&self, name: &str) -> Option<MethodId> {
+        self.ids
+            .get(name)
+            .copied()
+            .filter(|id| self.values[id.0 as usize].is_some())
+    }
+
+    /// Put `value` of `name`, and return the value it replaces.
+    pub(crate) fn insert(&mut self, name: String, value: T) -> Option<T> {
+        let id = self.intern(name);
+        self.values[id.0 as usize].replace(value)
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&T> {
+        self.by_id(self.ids.get(name).copied()?)
+    }
+
+    pub(crate) fn get_mut(&mut self, name: &str) -> Option<&mut T> {
+        let id = self.ids.get(name).copied()?;
+        self.values[id.0 as usize].as_mut()
+    }
+
+    pub(crate) fn by_id(&self, id: MethodId) -> Option<&T> {
+        self.values[id.0 as usize].as_ref()
+    }
+
+    pub(crate) fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// The value of the method of `name`, giving `name` its identifier if
+    /// it is registered.
+    pub(crate) fn resolve(&self, name: &mut Name) -> Option<&T> {
+        let id = self.id(&name.name)?;
+        name.name = self.names[id.0 as usize].clone();
+        name.id = Some(id);
+        self.by_id(id)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&String, &T)> {
+        self.names
+            .iter()
+            .zip(&self.values)
+            .filter_map(|(name, value)| Some((&**name, value.as_ref()?)))
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &T> {
+        self.values.iter().flatten()
+    }
+
+    pub(crate) fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        for value in self.values.iter_mut() {
+            if !matches!(value, Some(value) if keep(value)) {
+                *value = None;
+            }
+        }
+    }
+}
+
+/// A method name of a request, with its identifier once it is resolved to a
+/// registered method.
+#[derive(Debug, Clone)]
+pub(crate) struct Name {
+    pub(crate) name: Arc<String>,
+    pub(crate) id: Option<MethodId>,
+}
+
+impl Name {
+    pub(crate) fn new(name: &str) -> Name {
+        Name {
+            name: Arc::new(name.to_string()),
+            id: None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D>(deserializer: D) -> Result<Name, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NameVisitor;
+
+        impl<'de> Visitor<'de> for NameVisitor {
+            type Value = Name;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a method name")
+            }
+
+            fn visit_str<E>(self, name: &str) -> Result<Name, E>
+            where
+                E: de::Error,
+            {
+                Ok(Name::new(name))
+            }
+        }
+
+        deserializer.deserialize_str(NameVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registered_names_are_shared() {
+        let mut methods = Methods::default();
+        assert_eq!(methods.insert("a".to_string(), 1), None);
+        assert_eq!(methods.insert("a".to_string(), 2), Some(1));
+        methods.insert("b".to_string(), 3);
+        let id = methods.id("a").unwrap();
+        assert_ne!(methods.id("b"), Some(id));
+
+        let mut name = serde_json::from_str::<Name>(r#""a""#).unwrap();
+        assert_eq!(name.id, None);
+        assert_eq!(methods.resolve(&mut name), Some(&2));
+        assert_eq!(name.id, Some(id));
+        assert!(Arc::ptr_eq(&name.name, &methods.names[0]));
+
+        let mut name = serde_json::from_str::<Name>(r#""unknown""#).unwrap();
+        assert_eq!(methods.resolve(&mut name), None);
+        assert_eq!(name.name.as_str(), "unknown");
+
+        methods.retain(|value| *value != 2);
+        assert_eq!(methods.get("a"), None);
+        assert_eq!(methods.id("a"), None);
+        assert_eq!(methods.iter().collect::<Vec<_>>(), [(&"b".to_string(), &3)]);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::req::Id;
+use futures::future::{self, BoxFuture};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+/// A journaled call of a mutating RPC method.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct JournalEntry {
+    pub request_id: Id,
+    pub method: String,
+    /// Raw JSON text of the RPC parameter.
+    pub params: Option<String>,
+}
+
+/// A write-ahead journal of calls, for at-least-once processing.
+///
+/// Calls of journaled methods (see [`RpcRouter::journaled`]) are appended
+/// before the handler runs and marked complete after it finishes. After a
+/// crash, [`RpcRouter::recover`] calls the handlers again for every entry
+/// which was never completed.
+///
+/// Implement this trait on a durable store. [`MemoryJournal`] is provided for
+/// tests.
+///
+/// [`RpcRouter::journaled`]: ./struct.RpcRouter.html#method.journaled
+/// [`RpcRouter::recover`]: ./struct.RpcRouter.html#method.recover
+/// [`MemoryJournal`]: ./struct.MemoryJournal.html
+pub trait Journal: Send + Sync {
+    /// Durably append the entry and return its sequence number.
+    fn append(&self, entry: JournalEntry) -> BoxFuture<'_, anyhow::Result<u64>>;
+
+    /// Mark the entry as complete.
+    fn complete(&self, seq: u64) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    /// Every entry not completed yet, in appended order.
+    fn incomplete(&self) -> BoxFuture<'_, anyhow::Result<Vec<(u64, JournalEntry)>>>;
+}
+
+impl<J> Journal for Arc<J>
+where
+    J: Journal + ?Sized,
+{
+    fn append(&self, entry: JournalEntry) -> BoxFuture<'_, anyhow::Result<u64>> {
+        (**self).append(entry)
+    }
+
+    fn complete(&self, seq: u64) -> BoxFuture<'_, anyhow::Result<()>> {
+        (**self).complete(seq)
+    }
+
+    fn incomplete(&self) -> BoxFuture<'_, anyhow::Result<Vec<(u64, JournalEntry)>>> {
+        (**self).incomplete()
+    }
+}
+
+/// A `Journal` keeping entries in memory.
+///
+/// Entries do not survive a restart, so this is only useful for tests.
+#[derive(Debug, Default)]
+pub struct MemoryJournal {
+    inner: Mutex<MemoryJournalInner>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryJournalInner {
+    next_seq: u64,
+    entries: BTreeMap<u64, JournalEntry>,
+}
+
+impl MemoryJournal {
+    pub fn new() -> MemoryJournal {
+        MemoryJournal::default()
+    }
+}
+
+impl Journal for MemoryJournal {
+    fn append(&self, entry: JournalEntry) -> BoxFuture<'_, anyhow::Result<u64>> {
+        let mut inner = self.inner.lock().unwrap();
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+        inner.entries.insert(seq, entry);
+        Box::pin(future::ok(seq))
+    }
+
+    fn complete(&self, seq: u64) -> BoxFuture<'_, anyhow::Result<()>> {
+        self.inner.lock().unwrap().entries.remove(&seq);
+        Box::pin(future::ok(()))
+    }
+
+    fn incomplete(&self) -> BoxFuture<'_, anyhow::Result<Vec<(u64, JournalEntry)>>> {
+        let entries = self
+            .inner
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .map(|(seq, entry)| (*seq, entry.clone()))
+            .collect();
+        Box::pin(future::ok(entries))
+    }
+}
@@ -0,0 +1,274 @@
+use futures::future::{BoxFuture, FutureExt as _};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Where stateful features keep their state, such as the outcomes kept by
+/// [`RpcRouter::idempotency_in`].
+///
+/// Sharing a store between processes, e.g. by a Redis implementation, shares
+/// the state between them.
+///
+/// [`RpcRouter::idempotency_in`]: ./struct.RpcRouter.html#method.idempotency_in
+pub trait KvStore: Send + Sync {
+    /// The value of `key`, if it is put and not expired nor deleted.
+    fn get(&self, key: &str) -> BoxFuture<'_, anyhow::Result<Option<Vec<u8>>>>;
+
+    /// Put `value` at `key` for `ttl`, replacing the previous value.
+    fn put(&self, key: &str, value: Vec<u8>, ttl: Duration) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    fn delete(&self, key: &str) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    /// Put `value` at `key` for `ttl` unless a value is put there and not
+    /// expired, and return whether it was put.
+    ///
+    /// This gets and puts the value by default, so processes racing may
+    /// both put it. A shared store should do it atomically, e.g. by `SET`
+    /// with `NX` and `PX` of Redis.
+    fn put_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'_, anyhow::Result<bool>> {
+        let key = key.to_string();
+        async move {
+            if self.get(&key).await?.is_some() {
+                return Ok(false);
+            }
+            self.put(&key, value, ttl).await?;
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    /// Add `by` to the counter at `key`, which is 0 if absent, put it for
+    /// `ttl` and return it. Counters are kept as decimal text.
+    ///
+    /// This gets and puts the value by default, so increments racing from
+    /// processes may be lost. A shared store should do it atomically, e.g. by
+    /// `INCRBY` and `EXPIRE` of Redis.
+    fn increment(&self, key: &str, by: u64, ttl: Duration) -> BoxFuture<'_, anyhow::Result<u64>> {
+        let key = key.to_string();
+        async move {
+            let count = counter(self.get(&key).await?.as_deref())? + by;
+            self.put(&key, count.to_string().into_bytes(), ttl).await?;
+            Ok(count)
+        }
+        .boxed()
+    }
+}
+
+/// The counter in `value` put by `KvStore::increment`.
+pub(crate) fn counter(value: Option<&[u8]>) -> anyhow::Result<u64> {
+    match value {
+        Some(value) => Ok(std::str::from_utf8(value)?.parse()?),
+        None => Ok(0),
+    }
+}
+
+impl<S> KvStore for Arc<S>
+where
+    S: KvStore + ?Sized,
+{
+    fn get(&self, key: &str) -> BoxFuture<'_, anyhow::Result<Option<Vec<u8>>>> {
+        (**self).get(key)
+    }
+
+    fn put(&self, key: &str, value: Vec<u8>, ttl: Duration) -> BoxFuture<'_, anyhow::Result<()>> {
+        (**self).put(key, value, ttl)
+    }
+
+    fn delete(&self, key: &str) -> BoxFuture<'_, anyhow::Result<()>> {
+        (**self).delete(key)
+    }
+
+    fn put_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'_, anyhow::Result<bool>> {
+        (**self).put_if_absent(key, value, ttl)
+    }
+
+    fn increment(&self, key: &str, by: u64, ttl: Duration) -> BoxFuture<'_, anyhow::Result<u64>> {
+        (**self).increment(key, by, ttl)
+    }
+}
+
+/// A `KvStore` keeping values in memory of this process.
+///
+/// Expired values are dropped once they are got, and by a sweep whenever
+/// the store has doubled since the last one, so puts take constant time on
+/// average.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    values: HashMap<String, (Instant, Vec<u8>)>,
+    /// How many values were left by the last sweep.
+    swept: usize,
+}
+
+impl Entries {
+    /// The value of `key`, dropping it if it is expired.
+    fn live(&mut self, key: &str, now: Instant) -> Option<&mut (Instant, Vec<u8>)> {
+        if matches!(self.values.get(key), Some((expires, _)) if now >= *expires) {
+            self.values.remove(key);
+        }
+        self.values.get_mut(key)
+    }
+
+    fn insert(&mut self, key: &str, expires: Instant, value: Vec<u8>, now: Instant) {
+        if self.values.len() >= (self.swept * 2).max(SWEEP_FROM) {
+            self.values.retain(|_, (expires, _)| now < *expires);
+            self.swept = self.values.len();
+        }
+        self.values.insert(key.to_string(), (expires, value));
+    }
+}
+
+/// Fewest values for which a `MemoryStore` sweeps expired ones.
+const SWEEP_FROM: usize = 64;
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl KvStore for MemoryStore {
+    fn get(&self, key: &str) -> BoxFuture<'_, anyhow::Result<Option<Vec<u8>>>> {
+        let mut entries = self.entries.lock().unwrap();
+        let value = entries
+            .live(key, Instant::now())
+            .map(|(_, value)| value.clone());
+        futures::future::ready(Ok(value)).boxed()
+    }
+
+    fn put(&self, key: &str, value: Vec<u8>, ttl: Duration) -> BoxFuture<'_, anyhow::Result<()>> {
+        let now = Instant::now();
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, now + ttl, value, now);
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    fn delete(&self, key: &str) -> BoxFuture<'_, anyhow::Result<()>> {
+        self.entries.lock().unwrap().values.remove(key);
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    fn put_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'_, anyhow::Result<bool>> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        let present = entries.live(key, now).is_some();
+        if !present {
+            entries.insert(key, now + ttl, value, now);
+        }
+        futures::future::ready(Ok(!present)).boxed()
+    }
+
+    fn increment(&self, key: &str, by: u64, ttl: Duration) -> BoxFuture<'_, anyhow::Result<u64>> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        let current = entries.live(key, now).map(|(_, value)| value.as_slice());
+        let count = match counter(current) {
+            Ok(count) => count + by,
+            Err(e) => return futures::future::ready(Err(e)).boxed(),
+        };
+        entries.insert(key, now + ttl, count.to_string().into_bytes(), now);
+        futures::future::ready(Ok(count)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn values_expire() {
+        let store = MemoryStore::new();
+        store
+            .put("a", b"1".to_vec(), Duration::from_millis(10))
+            .await
+            .unwrap();
+        store
+            .put("b", b"2".to_vec(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(store.get("a").await.unwrap(), Some(b"1".to_vec()));
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(store.get("a").await.unwrap(), None);
+        store.delete("b").await.unwrap();
+        assert_eq!(store.get("b").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn sweep_expired_values() {
+        let store = MemoryStore::new();
+        for n in 0..SWEEP_FROM {
+            let key = n.to_string();
+            store
+                .put(&key, Vec::new(), Duration::from_millis(10))
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        store
+            .put("kept", Vec::new(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(store.entries.lock().unwrap().values.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn increment_counters() {
+        struct Plain(MemoryStore);
+
+        impl KvStore for Plain {
+            fn get(&self, key: &str) -> BoxFuture<'_, anyhow::Result<Option<Vec<u8>>>> {
+                self.0.get(key)
+            }
+
+            fn put(
+                &self,
+                key: &str,
+                value: Vec<u8>,
+                ttl: Duration,
+            ) -> BoxFuture<'_, anyhow::Result<()>> {
+                self.0.put(key, value, ttl)
+            }
+
+            fn delete(&self, key: &str) -> BoxFuture<'_, anyhow::Result<()>> {
+                self.0.delete(key)
+            }
+        }
+
+        let ttl = Duration::from_secs(60);
+        let stores: [Box<dyn KvStore>; 2] = [
+            Box::new(MemoryStore::new()),
+            Box::new(Plain(MemoryStore::new())),
+        ];
+        for store in stores.iter() {
+            assert_eq!(store.increment("n", 2, ttl).await.unwrap(), 2);
+            assert_eq!(store.increment("n", 3, ttl).await.unwrap(), 5);
+            assert_eq!(store.get("n").await.unwrap(), Some(b"5".to_vec()));
+            store.put("s", b"x".to_vec(), ttl).await.unwrap();
+            assert!(store.increment("s", 1, ttl).await.is_err());
+        }
+    }
+}
@@ -30,13 +30,120 @@
 //!     .unwrap();
 //! }
 //! ```
+mod access;
+mod audit;
+pub mod binary;
+pub mod bitcoin;
+mod blocking;
+mod breaker;
+mod catalog;
+pub mod channel;
+mod coalesce;
+pub mod codegen;
+mod config;
+mod context;
+pub mod continuation;
+mod de;
+pub mod decimal;
+mod deferred;
+pub mod diff;
+mod discover;
+mod duplicate;
+#[cfg(feature = "eth")]
+pub mod eth;
 pub mod filters;
+mod flags;
+pub mod graphql;
+mod idempotency;
+mod intern;
+mod journal;
+mod kv;
+mod locale;
+pub mod lsp;
+pub mod meta;
+pub mod multipart;
+pub mod ndjson;
+mod outcome;
+pub mod page;
+#[cfg(feature = "playground")]
+mod playground;
+mod plugin;
+mod preflight;
+mod priority;
+mod quota;
+mod redact;
+pub mod rejection;
+mod report;
 mod req;
 mod res;
+mod retry;
+mod router;
+mod routes;
+mod rules;
+mod saga;
+mod sampling;
+mod schema;
 mod service;
+mod sha256;
+mod shadow;
+mod shard;
+mod signing;
+mod status;
 mod store;
+pub mod time;
+pub mod topics;
+pub mod transaction;
+mod transform;
+mod usage;
+mod visibility;
+pub mod webhook;
 
-pub use req::Request;
-pub use res::{Builder, Error};
+pub use access::LogFormat;
+pub use audit::{AuditRecord, AuditSink, JsonlSink, LogSink, ParamsRetention, Retention};
+pub use blocking::BlockingPool;
+pub use breaker::{Unavailable, UNAVAILABLE_CODE};
+pub use catalog::{ErrorInfo, RPC_ERRORS};
+pub use config::{ConfigSource, DynamicConfig, EnvSource, FileSource, API_KEY, UNAUTHORIZED_CODE};
+pub use context::{Context, DEADLINE_EXCEEDED_CODE, REQUEST_TIMEOUT};
+pub use deferred::{deferred, Completer, DeferredResponse};
+pub use discover::{Example, MethodDoc, ParamDoc, RPC_DISCOVER};
+pub use duplicate::Duplicates;
+pub use flags::{in_rollout, RPC_METHODS};
+pub use idempotency::{IDEMPOTENCY_IN_FLIGHT_CODE, IDEMPOTENCY_KEY};
+pub use intern::MethodId;
+pub use journal::{Journal, JournalEntry, MemoryJournal};
+pub use kv::{KvStore, MemoryStore};
+pub use meta::{ExecutionMeta, MetaPlacement};
+pub use plugin::Plugin;
+pub use preflight::{Diagnostic, Severity};
+pub use quota::{
+    QuotaExceeded, QuotaUsage, QUOTA_EXCEEDED_CODE, RATE_LIMIT_LIMIT, RATE_LIMIT_REMAINING,
+    RATE_LIMIT_RESET,
+};
+pub use redact::REDACTED;
+pub use report::{ErrorReport, ErrorReporter, Failure};
+pub use req::{Id, Params, Request};
+pub use res::{Builder, Error, ReservedCode};
+pub use retry::Retryability;
+pub use router::{BoxedHandler, RpcRouter, WithState};
+pub use routes::Routes;
+pub use saga::Saga;
+pub use sampling::LogSampling;
+pub use schema::{Schema, Violation};
 pub use service::service;
+pub use service::service_with_status;
 pub use service::JsonRpcService;
+pub use signing::{verify_hmac_sha256, verify_signature, HmacSha256, Signer, SIGNATURE};
+pub use status::StatusMapping;
+pub use usage::{Usage, RPC_USAGE};
+pub use visibility::Visibility;
+
+/// Derive `Deserialize` for RPC parameters accepting both positional and named
+/// forms. Requires `derive` feature.
+#[cfg(feature = "derive")]
+pub use warp_json_rpc_derive::RpcParams;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+}
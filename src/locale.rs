@@ -0,0 +1,127 @@
+use crate::{Error, RpcRouter};
+use http::{header::ACCEPT_LANGUAGE, HeaderMap};
+use serde_json::Value;
+use std::{borrow::Cow, sync::Arc};
+
+type Resolve = Arc<dyn Fn(i64, &str) -> Option<Cow<'static, str>> + Send + Sync>;
+
+impl RpcRouter {
+    /// Resolve `error.message` of error responses by `resolve`, in the
+    /// language requested by the `Accept-Language` header.
+    ///
+    /// `resolve` receives the error code and a language tag, and is tried for
+    /// each requested language in order of preference until it returns a
+    /// message. If none is returned, the message given by the handler is kept.
+    /// Codes and `data` are never changed.
+    ///
+    /// ```
+    /// # use warp_json_rpc::RpcRouter;
+    /// let mut router = RpcRouter::new();
+    /// router.localize(|code, lang| match (code, lang) {
+    ///     (-32602, "fr") => Some("Paramètres invalides".into()),
+    ///     _ => None,
+    /// });
+    /// ```
+    pub fn localize<F>(&mut self, resolve: F) -> &mut RpcRouter
+    where
+        F: Fn(i64, &str) -> Option<Cow<'static, str>> + Send + Sync + 'static,
+    {
+        self.localizer = Some(Localizer {
+            resolve: Arc::new(resolve),
+        });
+        self
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Localizer {
+    resolve: Resolve,
+}
+
+impl Localizer {
+    pub(crate) fn localize(
+        &self,
+        languages: &[String],
+        result: Result<Value, Error>,
+    ) -> Result<Value, Error> {
+        result.map_err(|mut error| {
+            let message = languages
+                .iter()
+                .find_map(|lang| (self.resolve)(error.code, lang));
+            if let Some(message) = message {
+                error.message = message;
+            }
+            error
+        })
+    }
+}
+
+/// Language tags of the `Accept-Language` header, most preferred first.
+///
+/// The wildcard and tags with zero or malformed quality are dropped.
+pub(crate) fn languages(headers: &HeaderMap) -> Vec<String> {
+    let mut tags = headers
+        .get_all(ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            if !quality.is_finite() || quality <= 0.0 {
+                return None;
+            }
+            Some((tag.to_string(), quality))
+        })
+        .collect::<Vec<_>>();
+    // The sort is stable, so tags of equal quality keep their order.
+    tags.sort_by(|(_, lhs), (_, rhs)| rhs.partial_cmp(lhs).unwrap());
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_accept_language() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT_LANGUAGE,
+            "fr-CH, fr;q=0.9, en;q=0.8, de;q=0, *;q=0.5"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(languages(&headers), vec!["fr-CH", "fr", "en"]);
+        assert!(languages(&HeaderMap::new()).is_empty());
+
+        headers.insert(ACCEPT_LANGUAGE, "en;q=NaN, de;q=inf, fr".parse().unwrap());
+        assert_eq!(languages(&headers), vec!["fr"]);
+    }
+
+    #[test]
+    fn localize_error_message() {
+        let localizer = Localizer {
+            resolve: Arc::new(|code, lang| match (code, lang) {
+                (1, "fr") => Some("échoué".into()),
+                _ => None,
+            }),
+        };
+        let languages = vec!["fr-CH".to_string(), "fr".to_string()];
+        let error = localizer
+            .localize(&languages, Err(Error::custom(1, "failed")))
+            .err()
+            .unwrap();
+        assert_eq!(error.code, 1);
+        assert_eq!(error.message, "échoué");
+
+        let error = localizer
+            .localize(&languages, Err(Error::custom(2, "failed")))
+            .err()
+            .unwrap();
+        assert_eq!(error.message, "failed");
+    }
+}
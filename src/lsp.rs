@@ -0,0 +1,313 @@
+//! Serve a router as a [Language Server Protocol][lsp] server.
+//!
+//! LSP sends JSON RPC messages over a byte stream (usually stdio or TCP),
+//! each framed by a `Content-Length` header. [`serve`] reads messages from
+//! the stream, dispatches requests and notifications to the router, and
+//! writes the responses back, following the rules of LSP:
+//!
+//! - Requests received before `initialize` are answered by
+//!   `SERVER_NOT_INITIALIZED_CODE` error, and notifications are dropped.
+//! - `$/cancelRequest` cancels the request in flight, which is answered by
+//!   `REQUEST_CANCELLED_CODE` error.
+//! - Notifications are handled by the method of the same name, and whatever
+//!   it returns is dropped.
+//! - The `exit` notification stops serving.
+//! - Responses sent by the client are ignored, since the router never sends
+//!   requests to the client.
+//!
+//! ```no_run
+//! use warp_json_rpc::{lsp, Error, RpcRouter};
+//! use serde_json::{json, Value};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let mut router = RpcRouter::new();
+//! router.method("initialize", |_: Value| async move {
+//!     Ok::<_, Error>(json!({ "capabilities": {} }))
+//! });
+//! lsp::serve(router, tokio::io::stdin(), tokio::io::stdout()).await
+//! # }
+//! ```
+//!
+//! [lsp]: https://microsoft.github.io/language-server-protocol/
+//! [`serve`]: ./fn.serve.html
+use crate::{req::Id, Builder, Error, Request, RpcRouter, StatusMapping};
+use futures::future::{AbortHandle, Abortable};
+use http::HeaderMap;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{
+        AsyncBufRead, AsyncBufReadExt as _, AsyncRead, AsyncReadExt as _, AsyncWrite,
+        AsyncWriteExt as _, BufReader,
+    },
+    sync::mpsc,
+};
+use warp::reply::Response;
+
+/// Error code of requests received before `initialize`.
+pub const SERVER_NOT_INITIALIZED_CODE: i64 = -32002;
+/// Error code of requests cancelled by `$/cancelRequest`.
+pub const REQUEST_CANCELLED_CODE: i64 = -32800;
+/// Name of the notification cancelling a request.
+pub const CANCEL_REQUEST: &str = "$/cancelRequest";
+
+/// Read a message framed by a `Content-Length` header, or `None` at the end
+/// of the stream.
+pub async fn read_message<R>(reader: &mut R) -> io::Result<Option<Vec<u8>>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return match length {
+                None => Ok(None),
+                Some(_) => Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid("malformed header"))?;
+        if name.trim().eq_ignore_ascii_case("Content-Length") {
+            let value = value.trim().parse::<usize>();
+            length = Some(value.map_err(|_| invalid("invalid Content-Length"))?);
+        }
+    }
+    let length = length.ok_or_else(|| invalid("Content-Length is missing"))?;
+    let mut message = vec![0; length];
+    reader.read_exact(&mut message).await?;
+    Ok(Some(message))
+}
+
+/// Write a message framed by a `Content-Length` header.
+pub async fn write_message<W>(writer: &mut W, message: &[u8]) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let header = format!("Content-Length: {}\r\n\r\n", message.len());
+    writer.write_all(header.as_bytes()).await?;
+    writer.write_all(message).await?;
+    writer.flush().await
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Serve `router` on the stream until the `exit` notification or the end of
+/// the stream.
+pub async fn serve<R, W>(mut router: RpcRouter, reader: R, mut writer: W) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    router.register_builtins();
+    let router = Arc::new(router);
+    let mut reader = BufReader::new(reader);
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    let writing = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            write_message(&mut writer, &message).await?;
+        }
+        Ok::<_, io::Error>(())
+    });
+    let in_flight = Arc::new(Mutex::new(HashMap::<String, AbortHandle>::new()));
+    let mut initialized = false;
+
+    while let Some(message) = read_message(&mut reader).await? {
+        let mut message = match serde_json::from_slice::<Value>(&message) {
+            Ok(message) => message,
+            Err(_) => {
+                let _ = tx.send(error(Id::Null, Error::PARSE_ERROR).await);
+                continue;
+            }
+        };
+        let method = message["method"].as_str().map(str::to_string);
+        let id = message.get("id").cloned();
+        match (method, id) {
+            (Some(method), Some(id)) => {
+                let id = match serde_json::from_value::<Id>(id) {
+                    Ok(id) => id,
+                    Err(_) => {
+                        let _ = tx.send(error(Id::Null, Error::INVALID_REQUEST).await);
+                        continue;
+                    }
+                };
+                if !initialized && method != "initialize" {
+                    let e = Error::custom(SERVER_NOT_INITIALIZED_CODE, "Server not initialized");
+                    let _ = tx.send(error(id, e).await);
+                    continue;
+                }
+                initialized = true;
+
+                let key = key(&id);
+                let (handle, registration) = AbortHandle::new_pair();
+                in_flight.lock().unwrap().insert(key.clone(), handle);
+                let (router, tx, in_flight) = (router.clone(), tx.clone(), in_flight.clone());
+                tokio::spawn(async move {
+                    let call = Abortable::new(call(&router, id.clone(), message), registration);
+                    let response = match call.await {
+                        Ok(response) => response,
+                        Err(_) => {
+                            let e = Error::custom(REQUEST_CANCELLED_CODE, "Request cancelled");
+                            error(id, e).await
+                        }
+                    };
+                    in_flight.lock().unwrap().remove(&key);
+                    let _ = tx.send(response);
+                });
+            }
+            (Some(method), None) => match method.as_str() {
+                "exit" => break,
+                CANCEL_REQUEST => {
+                    let id = serde_json::from_value::<Id>(message["params"]["id"].take());
+                    if let Some(handle) = id
+                        .ok()
+                        .and_then(|id| in_flight.lock().unwrap().remove(&key(&id)))
+                    {
+                        handle.abort();
+                    }
+                }
+                _ if initialized => {
+                    // Notifications carry no id, which `Request` requires.
+                    message["id"] = Value::Null;
+                    let router = router.clone();
+                    tokio::spawn(async move {
+                        call(&router, Id::Null, message).await;
+                    });
+                }
+                _ => {
+                    log::debug!(target: "warp_json_rpc", "Drop \"{}\" notification before initialize", method)
+                }
+            },
+            (None, Some(_)) => {
+                log::debug!(target: "warp_json_rpc", "Ignore a response from the client");
+            }
+            (None, None) => {
+                let _ = tx.send(error(Id::Null, Error::INVALID_REQUEST).await);
+            }
+        }
+    }
+
+    drop(tx);
+    writing
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+}
+
+fn key(id: &Id) -> String {
+    serde_json::to_string(id).unwrap_or_default()
+}
+
+async fn call(router: &Arc<RpcRouter>, id: Id, message: Value) -> Vec<u8> {
+    // `Request` keeps params as `RawValue`, which can only be deserialized
+    // from text.
+    let req = match serde_json::from_str::<Request>(&message.to_string()) {
+        Ok(req) => req,
+        Err(_) => return error(id, Error::INVALID_REQUEST).await,
+    };
+    let res = Builder::new(id.clone(), StatusMapping::default());
+    match router.dispatch(res, req, HeaderMap::new(), None).await {
+        Ok(response) => body(response).await,
+        Err(_) => error(id, Error::METHOD_NOT_FOUND).await,
+    }
+}
+
+async fn error(id: Id, error: Error) -> Vec<u8> {
+    match Builder::new(id, StatusMapping::default()).error(error) {
+        Ok(response) => body(response).await,
+        Err(e) => {
+            log::error!(target: "warp_json_rpc", "Failed to serialize response: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+async fn body(response: Response) -> Vec<u8> {
+    hyper::body::to_bytes(response.into_body())
+        .await
+        .map(|body| body.to_vec())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn framed(messages: &[Value]) -> Vec<u8> {
+        let mut framed = Vec::new();
+        for message in messages {
+            let message = message.to_string();
+            framed.extend(format!("Content-Length: {}\r\n\r\n{}", message.len(), message).bytes());
+        }
+        framed
+    }
+
+    #[tokio::test]
+    async fn framing() {
+        let input = framed(&[json!(1), json!("two")]);
+        let mut reader = &input[..];
+        assert_eq!(
+            read_message(&mut reader).await.unwrap(),
+            Some(b"1".to_vec())
+        );
+        assert_eq!(
+            read_message(&mut reader).await.unwrap(),
+            Some(br#""two""#.to_vec())
+        );
+        assert_eq!(read_message(&mut reader).await.unwrap(), None);
+
+        let mut reader = &b"Content-Type: x\r\n\r\n"[..];
+        assert!(read_message(&mut reader).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn serve_lifecycle() {
+        let mut router = RpcRouter::new();
+        router
+            .method("initialize", |_: Value| async move {
+                Ok::<_, Error>(json!({ "capabilities": {} }))
+            })
+            .method("add", |(lhs, rhs): (i64, i64)| async move {
+                Ok::<_, Error>(lhs + rhs)
+            })
+            .method("slow", |(): ()| async move {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok::<_, Error>(())
+            });
+        let input = framed(&[
+            json!({ "jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1 }),
+            json!({ "jsonrpc": "2.0", "method": "initialize", "params": {}, "id": 2 }),
+            json!({ "jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 3 }),
+            json!({ "jsonrpc": "2.0", "method": "slow", "id": 4 }),
+            json!({ "jsonrpc": "2.0", "method": "$/cancelRequest", "params": { "id": 4 } }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ]);
+
+        let (output, mut client) = tokio::io::duplex(4096);
+        serve(router, &input[..], output).await.unwrap();
+        let mut reader = BufReader::new(&mut client);
+        let mut responses = HashMap::new();
+        while responses.len() < 4 {
+            let message = read_message(&mut reader).await.unwrap().unwrap();
+            let message = serde_json::from_slice::<Value>(&message).unwrap();
+            responses.insert(message["id"].as_i64().unwrap(), message);
+        }
+        assert_eq!(responses[&1]["error"]["code"], SERVER_NOT_INITIALIZED_CODE);
+        assert_eq!(responses[&2]["result"], json!({ "capabilities": {} }));
+        assert_eq!(responses[&3]["result"], 3);
+        assert_eq!(responses[&4]["error"]["code"], REQUEST_CANCELLED_CODE);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::RpcRouter;
+use serde::Serialize;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// HTTP header carrying [`ExecutionMeta::server_time`].
+///
+/// [`ExecutionMeta::server_time`]: ./struct.ExecutionMeta.html#structfield.server_time
+pub const SERVER_TIME: &str = "X-Server-Time";
+
+/// HTTP header carrying [`ExecutionMeta::duration`] in milliseconds.
+///
+/// [`ExecutionMeta::duration`]: ./struct.ExecutionMeta.html#structfield.duration
+pub const DURATION: &str = "X-Duration-Ms";
+
+/// HTTP header carrying [`ExecutionMeta::node_id`].
+///
+/// [`ExecutionMeta::node_id`]: ./struct.ExecutionMeta.html#structfield.node_id
+pub const NODE_ID: &str = "X-Node-Id";
+
+/// Metadata of how a call was executed, for client-side debugging.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExecutionMeta {
+    /// Milliseconds since the Unix epoch when the response was built.
+    pub server_time: u64,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<Arc<String>>,
+}
+
+impl ExecutionMeta {
+    /// Metadata of a call which took `duration`, stamped with the current
+    /// time.
+    pub fn new(duration: Duration, node_id: Option<Arc<String>>) -> ExecutionMeta {
+        let server_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        ExecutionMeta {
+            server_time,
+            duration,
+            node_id,
+        }
+    }
+}
+
+fn serialize_millis<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+/// Where [`ExecutionMeta`] is put in responses.
+///
+/// [`ExecutionMeta`]: ./struct.ExecutionMeta.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaPlacement {
+    /// In the `meta` field of the response object, next to `result` or
+    /// `error`.
+    Field,
+    /// In the `X-Server-Time`, `X-Duration-Ms` and `X-Node-Id` headers.
+    Headers,
+}
+
+impl RpcRouter {
+    /// Add [`ExecutionMeta`] to every response.
+    ///
+    /// `node_id` identifies this server, e.g. when several servers are behind
+    /// a load balancer.
+    ///
+    /// [`ExecutionMeta`]: ./struct.ExecutionMeta.html
+    pub fn execution_meta(
+        &mut self,
+        placement: MetaPlacement,
+        node_id: Option<String>,
+    ) -> &mut RpcRouter {
+        self.meta = Some((placement, node_id.map(Arc::new)));
+        self
+    }
+}
+
+pub(crate) fn insert_headers(headers: &mut http::HeaderMap, meta: &ExecutionMeta) {
+    headers.insert(SERVER_TIME, meta.server_time.into());
+    let duration = format!("{:.3}", meta.duration.as_secs_f64() * 1000.0);
+    headers.insert(DURATION, duration.parse().unwrap());
+    if let Some(node_id) = meta.node_id.as_ref() {
+        match node_id.parse() {
+            Ok(value) => {
+                headers.insert(NODE_ID, value);
+            }
+            Err(_) => {
+                log::warn!(target: "warp_json_rpc", "Node id is not a valid header value: {}", node_id)
+            }
+        }
+    }
+}
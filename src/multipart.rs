@@ -0,0 +1,204 @@
+//! Binary attachments of results in `multipart/mixed` responses.
+//!
+//! A successful call of a method registered by
+//! [`RpcRouter::with_attachments`] is answered by a `multipart/mixed` body.
+//! The first part is the JSON RPC response object, and each following part is
+//! an attachment whose `Content-ID` the result refers to (e.g. as
+//! `"cid:<content id>"`). Errors are answered by an ordinary JSON response.
+//!
+//! [`RpcRouter::with_attachments`]: ../struct.RpcRouter.html#method.with_attachments
+use crate::{router::Method, Builder, Context, Error, Params, RpcRouter};
+use futures::future::{BoxFuture, Future, FutureExt as _};
+use hyper::{body::Bytes, Body};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use warp::reply::Response;
+
+/// A binary attachment of a result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// Referred by the result, without angle brackets.
+    pub content_id: String,
+    pub content_type: String,
+    pub data: Bytes,
+}
+
+impl Attachment {
+    pub fn new<I, T, D>(content_id: I, content_type: T, data: D) -> Attachment
+    where
+        I: Into<String>,
+        T: Into<String>,
+        D: Into<Bytes>,
+    {
+        Attachment {
+            content_id: content_id.into(),
+            content_type: content_type.into(),
+            data: data.into(),
+        }
+    }
+
+    /// `cid:` URL referring this attachment.
+    pub fn url(&self) -> String {
+        format!("cid:{}", self.content_id)
+    }
+}
+
+/// Result of a method with attachments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithAttachments<R> {
+    pub result: R,
+    pub attachments: Vec<Attachment>,
+}
+
+pub(crate) type MultipartHandler = Arc<
+    dyn Fn(Params, Context) -> BoxFuture<'static, Result<(Value, Vec<Attachment>), Error>>
+        + Send
+        + Sync,
+>;
+
+impl RpcRouter {
+    /// Register a handler for the RPC method, whose result is sent with
+    /// binary attachments.
+    ///
+    /// See the [module documentation] for how they are answered.
+    ///
+    /// [module documentation]: ./multipart/index.html
+    ///
+    /// ```
+    /// use warp_json_rpc::{multipart::{Attachment, WithAttachments}, Error, RpcRouter};
+    ///
+    /// let mut router = RpcRouter::new();
+    /// router.with_attachments("read_file", |(name,): (String,)| async move {
+    ///     let file = Attachment::new("file", "application/octet-stream", vec![0_u8; 1024]);
+    ///     Ok::<_, Error>(WithAttachments {
+    ///         result: serde_json::json!({ "name": name, "content": file.url() }),
+    ///         attachments: vec![file],
+    ///     })
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn with_attachments<P, R, F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        handler: F,
+    ) -> &mut RpcRouter
+    where
+        for<'de> P: Deserialize<'de> + Send + 'static,
+        R: Serialize,
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<WithAttachments<R>, Error>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let multipart: MultipartHandler = Arc::new(move |params: Params, _ctx: Context| {
+            let handler = handler.clone();
+            async move {
+                let res = handler(params.parse::<P>()?).await?;
+                let result = serde_json::to_value(res.result).map_err(|e| {
+                    log::error!(target: "warp_json_rpc", "Failed to serialize result: {}", e);
+                    Error::INTERNAL_ERROR
+                })?;
+                Ok((result, res.attachments))
+            }
+            .boxed()
+        });
+
+        let name = name.into();
+        let plain = multipart.clone();
+        self.register(
+            name.clone(),
+            Arc::new(move |params, ctx| {
+                plain(params, ctx)
+                    .map(|res| res.map(|(result, _)| result))
+                    .boxed()
+            }),
+        );
+        self.registered(&name).multipart = Some(multipart);
+        self
+    }
+}
+
+impl Method {
+    /// Call the handler with attachments in the same way as [`Method::call`].
+    pub(crate) async fn call_multipart(
+        &self,
+        params: Params,
+        ctx: Context,
+    ) -> Result<(Value, Vec<Attachment>), Error> {
+        let handler = self.multipart.as_ref().expect("method has attachments");
+        self.validate(&params)?;
+        let _turn = ctx.timeout(self.turn(&params)).await?;
+        handler(params, ctx)
+            .await
+            .map_err(|e| self.sensitive.redact_error(e))
+    }
+}
+
+pub(crate) fn reply(res: Builder, result: Value, attachments: Vec<Attachment>) -> Response {
+    let boundary = format!("{:032x}", rand::random::<u128>());
+    let response = json!({ "jsonrpc": "2.0", "id": res.id(), "result": result });
+    let json = serde_json::to_vec(&response).expect("Value is always serializable");
+
+    let mut body = Vec::new();
+    part(&mut body, &boundary, "application/json", None, &json);
+    for attachment in attachments.iter() {
+        part(
+            &mut body,
+            &boundary,
+            &attachment.content_type,
+            Some(&attachment.content_id),
+            &attachment.data,
+        );
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    http::Response::builder()
+        .header(
+            "Content-Type",
+            format!("multipart/mixed; boundary=\"{}\"", boundary),
+        )
+        .header("Content-Length", body.len())
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn part(body: &mut Vec<u8>, boundary: &str, content_type: &str, id: Option<&str>, data: &[u8]) {
+    body.extend_from_slice(
+        format!("--{}\r\nContent-Type: {}\r\n", boundary, content_type).as_bytes(),
+    );
+    if let Some(id) = id {
+        body.extend_from_slice(format!("Content-ID: <{}>\r\n", id).as_bytes());
+    }
+    body.extend_from_slice(b"\r\n");
+    body.extend_from_slice(data);
+    body.extend_from_slice(b"\r\n");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{req::Id, StatusMapping};
+
+    #[tokio::test]
+    async fn multipart_body() {
+        let res = Builder::new(Id::Number(1), StatusMapping::default());
+        let attachment = Attachment::new("a", "text/plain", "hello");
+        let res = reply(res, Value::from(attachment.url()), vec![attachment]);
+
+        let content_type = res.headers()["Content-Type"].to_str().unwrap().to_string();
+        let boundary = content_type
+            .strip_prefix("multipart/mixed; boundary=\"")
+            .unwrap()
+            .trim_end_matches('"')
+            .to_string();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let expected = format!(
+            "--{b}\r\nContent-Type: application/json\r\n\r\n\
+             {{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":\"cid:a\"}}\r\n\
+             --{b}\r\nContent-Type: text/plain\r\nContent-ID: <a>\r\n\r\nhello\r\n\
+             --{b}--\r\n",
+            b = boundary
+        );
+        assert_eq!(std::str::from_utf8(&body).unwrap(), expected);
+    }
+}
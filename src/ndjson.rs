@@ -0,0 +1,184 @@
+//! Newline delimited JSON streaming of results.
+//!
+//! A call of a streaming method whose request accepts `application/x-ndjson`
+//! is answered by a stream of lines. Each item is sent as an `rpc_chunk`
+//! notification whose `params` is `{ "id": <request id>, "item": <item> }`,
+//! and the stream is terminated by an ordinary response object whose result
+//! is `null` (or the error of the stream).
+//!
+//! Other calls are answered by a single response whose result is the array of
+//! all items.
+use crate::{req::Id, router::Settle, Builder, Context, Error, Params, RpcRouter};
+use futures::{
+    future::{self, FutureExt as _},
+    stream::{self, BoxStream, Stream, StreamExt as _, TryStreamExt as _},
+};
+use http::{header::ACCEPT, HeaderMap};
+use hyper::{body::Bytes, Body};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{convert::Infallible, sync::Arc};
+use tokio::sync::OwnedSemaphorePermit;
+use warp::reply::Response;
+
+/// Media type of a newline delimited JSON stream.
+pub const NDJSON: &str = "application/x-ndjson";
+
+/// Method of the notifications carrying streamed items.
+pub const CHUNK_METHOD: &str = "rpc_chunk";
+
+pub(crate) type StreamHandler =
+    Arc<dyn Fn(Params, Context) -> BoxStream<'static, Result<Value, Error>> + Send + Sync>;
+
+impl RpcRouter {
+    /// Register a handler for the RPC method, which streams its results.
+    ///
+    /// See the [module documentation] for how they are answered.
+    ///
+    /// [module documentation]: ./ndjson/index.html
+    ///
+    /// ```
+    /// use warp_json_rpc::{Error, RpcRouter};
+    ///
+    /// let mut router = RpcRouter::new();
+    /// router.streaming("tail_log", |(lines,): (usize,)| {
+    ///     futures::stream::iter((0..lines).map(|n| Ok::<_, Error>(format!("line {}", n))))
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn streaming<P, T, F, S>(&mut self, name: impl Into<String>, handler: F) -> &mut RpcRouter
+    where
+        for<'de> P: Deserialize<'de> + Send + 'static,
+        T: Serialize,
+        F: Fn(P) -> S + Send + Sync + 'static,
+        S: Stream<Item = Result<T, Error>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let stream: StreamHandler = Arc::new(move |params: Params, _ctx: Context| {
+            match params.parse::<P>() {
+                Ok(params) => handler(params)
+                    .map(|item| {
+                        item.and_then(|item| {
+                            serde_json::to_value(item).map_err(|e| {
+                                log::error!(target: "warp_json_rpc", "Failed to serialize result: {}", e);
+                                Error::INTERNAL_ERROR
+                            })
+                        })
+                    })
+                    .boxed(),
+                Err(e) => stream::once(future::ready(Err(e))).boxed(),
+            }
+        });
+
+        let name = name.into();
+        let collect = stream.clone();
+        self.register(
+            name.clone(),
+            Arc::new(move |params, ctx| {
+                collect(params, ctx)
+                    .try_collect::<Vec<_>>()
+                    .map(|items| items.map(Value::Array))
+                    .boxed()
+            }),
+        );
+        self.registered(&name).stream = Some(stream);
+        self
+    }
+}
+
+/// Whether the request accepts a newline delimited JSON stream.
+pub(crate) fn accepts(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media| media.split(';').next().map(str::trim) == Some(NDJSON))
+}
+
+/// Answer by the stream of `items`, settling the call once the stream ends.
+///
+/// `permit` of the priority class is held until then.
+pub(crate) fn reply(
+    res: Builder,
+    items: BoxStream<'static, Result<Value, Error>>,
+    settle: Settle,
+    permit: Option<OwnedSemaphorePermit>,
+) -> Response {
+    let id = res.id().clone();
+    let state = Some((items, settle, permit, 0_u64));
+    let lines = stream::unfold(state, move |state| {
+        let id = id.clone();
+        async move {
+            let (mut items, settle, permit, mut bytes) = state?;
+            let end = match items.next().await {
+                Some(Ok(item)) => {
+                    let line = chunk(&id, item);
+                    bytes += line.len() as u64;
+                    return Some((Ok(line), Some((items, settle, permit, bytes))));
+                }
+                Some(Err(error)) => Err(error),
+                None => Ok(Value::Null),
+            };
+            drop(items);
+            let result = settle.localize(settle.settle(end, None).await);
+            drop(permit);
+            let error_code = result.as_ref().err().map(|e| e.code);
+            let line = self::end(&id, result.map(|_| ()));
+            bytes += line.len() as u64;
+            settle.account(error_code, bytes).await;
+            Some((Ok::<_, Infallible>(line), None))
+        }
+    });
+
+    http::Response::builder()
+        .header("Content-Type", NDJSON)
+        .body(Body::wrap_stream(lines))
+        .unwrap()
+}
+
+fn chunk(id: &Id, item: Value) -> Bytes {
+    line(json!({
+        "jsonrpc": "2.0",
+        "method": CHUNK_METHOD,
+        "params": { "id": id, "item": item },
+    }))
+}
+
+fn end(id: &Id, result: Result<(), Error>) -> Bytes {
+    let end = match result {
+        Ok(()) => json!({ "jsonrpc": "2.0", "id": id, "result": null }),
+        Err(error) => match serde_json::to_value(error) {
+            Ok(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+            Err(e) => {
+                log::error!(target: "warp_json_rpc", "Failed to serialize response: {}", e);
+                json!({ "jsonrpc": "2.0", "id": id, "error": Error::INTERNAL_ERROR })
+            }
+        },
+    };
+    line(end)
+}
+
+fn line(value: Value) -> Bytes {
+    let mut line = serde_json::to_vec(&value).expect("Value is always serializable");
+    line.push(b'\n');
+    Bytes::from(line)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accepts_ndjson() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts(&headers));
+        headers.insert(
+            ACCEPT,
+            "application/json, application/x-ndjson;q=0.9"
+                .parse()
+                .unwrap(),
+        );
+        assert!(accepts(&headers));
+    }
+}
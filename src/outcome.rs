@@ -0,0 +1,48 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// `Result<Value, Error>` which can be cloned.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum Outcome {
+    Success(Value),
+    Error {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+}
+
+impl Outcome {
+    pub(crate) fn from_result(result: &Result<Value, Error>) -> Outcome {
+        match result {
+            Ok(value) => Outcome::Success(value.clone()),
+            Err(error) => Outcome::Error {
+                code: error.code,
+                message: error.message.to_string(),
+                data: error
+                    .data
+                    .as_ref()
+                    .and_then(|data| serde_json::to_value(data).ok()),
+            },
+        }
+    }
+
+    pub(crate) fn into_result(self) -> Result<Value, Error> {
+        match self {
+            Outcome::Success(value) => Ok(value),
+            Outcome::Error {
+                code,
+                message,
+                data,
+            } => {
+                let error = Error::custom(code, Cow::Owned(message));
+                Err(match data {
+                    Some(data) => error.with_data(data),
+                    None => error,
+                })
+            }
+        }
+    }
+}
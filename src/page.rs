@@ -0,0 +1,137 @@
+//! Pagination convention for methods returning large lists.
+//!
+//! A paginated method takes [`PageParams`] and returns a [`Page`]. Clients
+//! pass `next_cursor` of a page as `cursor` to request the next one, until a
+//! page without `next_cursor` is returned.
+//!
+//! ```
+//! use warp_json_rpc::{page::{Cursor, Page, PageParams}, Error, RpcRouter};
+//!
+//! let mut router = RpcRouter::new();
+//! router.method("list_numbers", |params: PageParams| async move {
+//!     let start = match params.cursor.as_ref() {
+//!         Some(cursor) => cursor.decode::<u64>()?,
+//!         None => 0,
+//!     };
+//!     let limit = params.limit_or(10, 100) as u64;
+//!     let items = (start..1000).take(limit as usize).collect::<Vec<_>>();
+//!     let next = Some(start + limit).filter(|next| *next < 1000);
+//!     Ok::<_, Error>(Page::new(items, next.map(|next| Cursor::encode(&next))))
+//! });
+//! ```
+//!
+//! [`PageParams`]: ./struct.PageParams.html
+//! [`Page`]: ./struct.Page.html
+use crate::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Parameters of a paginated method.
+///
+/// Accepts `{ "limit": 10, "cursor": "..." }`, where both fields are
+/// optional, or absent parameters.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PageParams {
+    #[serde(default)]
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub cursor: Option<Cursor>,
+}
+
+impl PageParams {
+    /// The requested limit, `default` if not given, capped at `max`.
+    pub fn limit_or(&self, default: u32, max: u32) -> u32 {
+        self.limit.unwrap_or(default).min(max)
+    }
+}
+
+/// A page of a list.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page. `None` if this is the last page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<Cursor>,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, next_cursor: Option<Cursor>) -> Page<T> {
+        Page { items, next_cursor }
+    }
+
+    /// The last page of a list.
+    pub fn last(items: Vec<T>) -> Page<T> {
+        Page::new(items, None)
+    }
+}
+
+/// An opaque position in a list.
+///
+/// A cursor encodes any serializable position (e.g. an offset or the key of
+/// the last item) as URL safe base64 of its JSON, so that clients do not
+/// depend on its meaning.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Cursor(String);
+
+impl Cursor {
+    pub fn encode<P>(position: &P) -> Cursor
+    where
+        P: Serialize,
+    {
+        let json = serde_json::to_vec(position).expect("position is serializable");
+        Cursor(base64::encode_config(json, base64::URL_SAFE_NO_PAD))
+    }
+
+    /// Decode the position.
+    ///
+    /// Fails with `INVALID_PARAMS` error if the cursor is not one encoded
+    /// from a `P`.
+    pub fn decode<P>(&self) -> Result<P, Error>
+    where
+        P: DeserializeOwned,
+    {
+        let json = base64::decode_config(&self.0, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::INVALID_PARAMS.with_data("invalid cursor"))?;
+        serde_json::from_slice(&json).map_err(|_| Error::INVALID_PARAMS.with_data("invalid cursor"))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursor_roundtrip() {
+        let cursor = Cursor::encode(&("key", 42));
+        assert_eq!(
+            cursor.decode::<(String, u64)>().ok(),
+            Some(("key".to_string(), 42))
+        );
+        assert!(cursor.decode::<u64>().is_err());
+        assert!(Cursor("!".to_string()).decode::<u64>().is_err());
+    }
+
+    #[test]
+    fn page_params() {
+        let params = crate::de::absent::<PageParams>().unwrap();
+        assert_eq!(params, PageParams::default());
+        assert_eq!(params.limit_or(10, 100), 10);
+
+        let params = serde_json::from_str::<PageParams>(r#"{ "limit": 1000 }"#).unwrap();
+        assert_eq!(params.limit_or(10, 100), 100);
+    }
+
+    #[test]
+    fn serialize_last_page() {
+        let page = Page::last(vec![1, 2]);
+        assert_eq!(
+            serde_json::to_value(&page).unwrap(),
+            serde_json::json!({ "items": [1, 2] })
+        );
+    }
+}
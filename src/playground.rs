@@ -0,0 +1,62 @@
+use crate::RpcRouter;
+use serde_json::Value;
+use std::sync::Arc;
+use warp::{reply::Html, Filter, Rejection};
+
+const PAGE: &str = include_str!("playground/index.html");
+
+impl RpcRouter {
+    /// Create a `Filter` serving a web page to try the methods registered so
+    /// far, by sending calls to `endpoint`.
+    ///
+    /// The page lists the methods of [`openrpc`], and fills the request of a
+    /// method by its first example if it is documented by [`doc`].
+    ///
+    /// [`openrpc`]: #method.openrpc
+    /// [`doc`]: #method.doc
+    ///
+    /// ```
+    /// # use warp_json_rpc::RpcRouter;
+    /// # use warp::Filter as _;
+    /// # let router = RpcRouter::new();
+    /// let playground = warp::path("playground").and(router.playground("/rpc"));
+    /// let rpc = warp::path("rpc").and(router.into_filter());
+    /// let routes = playground.or(rpc);
+    /// ```
+    pub fn playground(
+        &self,
+        endpoint: &str,
+    ) -> impl Filter<Extract = (Html<String>,), Error = Rejection> + Clone {
+        let page = Arc::new(
+            PAGE.replace("{{DOCUMENT}}", &script_json(&self.openrpc()))
+                .replace("{{ENDPOINT}}", &script_json(&Value::from(endpoint))),
+        );
+        warp::get()
+            .and(warp::path::end())
+            .map(move || warp::reply::html(page.to_string()))
+    }
+}
+
+/// JSON text which can be put in a `<script>` element.
+fn script_json(value: &Value) -> String {
+    value.to_string().replace("</", "<\\/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+
+    #[tokio::test]
+    async fn serve_page() {
+        let mut router = RpcRouter::new();
+        router.method("</script>", |(): ()| async move { Ok::<_, Error>(()) });
+        let res = warp::test::request()
+            .path("/")
+            .reply(&router.playground("/rpc"))
+            .await;
+        let page = std::str::from_utf8(res.body()).unwrap();
+        assert!(page.contains(r#"<script id="endpoint" type="application/json">"/rpc"</script>"#));
+        assert!(page.contains(r#"{"name":"<\/script>""#));
+    }
+}
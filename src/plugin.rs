@@ -0,0 +1,90 @@
+use crate::RpcRouter;
+use std::sync::Arc;
+
+/// A reusable set of RPC methods.
+///
+/// Large APIs can be split into plugins living in separate modules or crates,
+/// and assembled into one [`RpcRouter`] by [`RpcRouter::load`].
+///
+/// [`RpcRouter`]: ./struct.RpcRouter.html
+/// [`RpcRouter::load`]: ./struct.RpcRouter.html#method.load
+///
+/// ```
+/// # use warp_json_rpc::{Error, Plugin, RpcRouter};
+/// struct Math;
+///
+/// impl Plugin for Math {
+///     fn register(&self, router: &mut RpcRouter) {
+///         router.method("add", |(lhs, rhs): (i64, i64)| async move {
+///             Ok::<_, Error>(lhs + rhs)
+///         });
+///     }
+/// }
+///
+/// let plugins: Vec<Box<dyn Plugin>> = vec![
+///     Box::new(Math),
+///     // Plugins can be selected by feature flags.
+///     #[cfg(feature = "admin")]
+///     Box::new(Admin),
+/// ];
+///
+/// let mut router = RpcRouter::new();
+/// router.load(plugins);
+/// assert!(router.contains("add"));
+/// ```
+pub trait Plugin {
+    fn register(&self, router: &mut RpcRouter);
+}
+
+impl<P> Plugin for &P
+where
+    P: Plugin + ?Sized,
+{
+    fn register(&self, router: &mut RpcRouter) {
+        (**self).register(router)
+    }
+}
+
+impl<P> Plugin for Box<P>
+where
+    P: Plugin + ?Sized,
+{
+    fn register(&self, router: &mut RpcRouter) {
+        (**self).register(router)
+    }
+}
+
+impl<P> Plugin for Arc<P>
+where
+    P: Plugin + ?Sized,
+{
+    fn register(&self, router: &mut RpcRouter) {
+        (**self).register(router)
+    }
+}
+
+impl RpcRouter {
+    /// Register the methods of the plugin.
+    pub fn plugin<P>(&mut self, plugin: P) -> &mut RpcRouter
+    where
+        P: Plugin,
+    {
+        plugin.register(self);
+        self
+    }
+
+    /// Register the methods of every plugin in order.
+    ///
+    /// A method registered by a later plugin replaces the one with the same
+    /// name registered by an earlier plugin.
+    pub fn load<I>(&mut self, plugins: I) -> &mut RpcRouter
+    where
+        I: IntoIterator,
+        I::Item: Plugin,
+    {
+        for plugin in plugins {
+            plugin.register(self);
+        }
+        self
+    }
+}
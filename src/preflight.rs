@@ -0,0 +1,178 @@
+use crate::{RpcRouter, RPC_DISCOVER};
+use serde::Serialize;
+use std::{fmt, sync::Arc};
+
+/// How serious a [`Diagnostic`] is.
+///
+/// [`Diagnostic`]: ./struct.Diagnostic.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The router works, but likely not as intended.
+    Warning,
+    /// The router breaks the JSON RPC specification.
+    Error,
+}
+
+/// A problem of a router found by [`RpcRouter::preflight`].
+///
+/// [`RpcRouter::preflight`]: ./struct.RpcRouter.html#method.preflight
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The method the problem is about, if any.
+    pub method: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match &self.method {
+            Some(method) => write!(f, "{}: \"{}\": {}", severity, method, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+impl RpcRouter {
+    /// Check the router for mistakes before serving it.
+    ///
+    /// Finds
+    ///
+    /// - methods registered more than once, of which only the last is kept,
+    /// - methods whose names start with `rpc.`, which the specification
+    ///   reserves for extensions,
+    /// - gated methods which are never enabled since [`feature_flags`] is not
+    ///   called,
+    /// - methods without a parameter schema while [`rpc_methods`] or
+    ///   [`rpc_discover`] lists them to clients,
+    /// - priority classes no method is assigned to,
+    /// - allowed methods of the current [`DynamicConfig`] which are not
+    ///   registered.
+    ///
+    /// Diagnostics are sorted by method name.
+    ///
+    /// [`feature_flags`]: #method.feature_flags
+    /// [`rpc_methods`]: #method.rpc_methods
+    /// [`rpc_discover`]: #method.rpc_discover
+    /// [`DynamicConfig`]: ./struct.DynamicConfig.html
+    pub fn preflight(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut report = |severity, method: Option<&str>, message: String| {
+            diagnostics.push(Diagnostic {
+                severity,
+                method: method.map(str::to_string),
+                message,
+            })
+        };
+
+        for duplicate in &self.replaced {
+            report(
+                Severity::Warning,
+                Some(&duplicate.name),
+                format!(
+                    "registered at {} is replaced by the one at {}",
+                    duplicate.first, duplicate.again
+                ),
+            );
+        }
+        for (name, method) in self.methods.iter() {
+            if name.starts_with("rpc.") && name != RPC_DISCOVER {
+                report(
+                    Severity::Error,
+                    Some(name),
+                    "names starting with \"rpc.\" are reserved".to_string(),
+                );
+            }
+            if let (Some(flag), None) = (method.flag.as_ref(), self.flags.as_ref()) {
+                report(
+                    Severity::Warning,
+                    Some(name),
+                    format!("gated by \"{}\" but feature flags are not resolved", flag),
+                );
+            }
+            let listed = self.list_methods || self.discover.is_some();
+            if listed && method.params_schema.is_none() {
+                report(
+                    Severity::Warning,
+                    Some(name),
+                    "listed to clients without a parameter schema".to_string(),
+                );
+            }
+        }
+        for (class, permits) in &self.classes {
+            let assigned = self.methods.values().any(|method| {
+                method
+                    .class
+                    .as_ref()
+                    .map_or(false, |class| Arc::ptr_eq(class, permits))
+            });
+            if !assigned {
+                report(
+                    Severity::Warning,
+                    None,
+                    format!("no method is assigned to priority class \"{}\"", class),
+                );
+            }
+        }
+        if let Some(config) = self.config.as_ref() {
+            for name in config.get().allowed_methods.iter().flatten() {
+                if !self.methods.contains_key(name) {
+                    report(
+                        Severity::Warning,
+                        Some(name),
+                        "allowed by the config but not registered".to_string(),
+                    );
+                }
+            }
+        }
+
+        diagnostics
+            .sort_by(|lhs, rhs| (&lhs.method, &lhs.message).cmp(&(&rhs.method, &rhs.message)));
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn find_mistakes() {
+        let mut router = RpcRouter::new();
+        let line = line!() + 2;
+        router
+            .method("a", |(): ()| async move { Ok::<_, Error>(()) })
+            .method("a", |(): ()| async move { Ok::<_, Error>(()) })
+            .method("rpc.x", |(): ()| async move { Ok::<_, Error>(()) })
+            .gated("rpc.x", "beta")
+            .priority_class("bulk", 1);
+
+        let diagnostics = router
+            .preflight()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "warning: no method is assigned to priority class \"bulk\"".to_string(),
+                format!(
+                    "warning: \"a\": registered at {}:{}:14 is replaced by the one at {}:{}:14",
+                    file!(),
+                    line,
+                    file!(),
+                    line + 1
+                ),
+                "warning: \"rpc.x\": gated by \"beta\" but feature flags are not resolved"
+                    .to_string(),
+                "error: \"rpc.x\": names starting with \"rpc.\" are reserved".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use crate::RpcRouter;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+impl RpcRouter {
+    /// Declare a priority class running at most `concurrency` calls at once.
+    ///
+    /// Calls of methods assigned to the class by [`priority`] beyond the
+    /// limit wait for a running one to finish. Giving bulk methods a small
+    /// class of their own keeps them from starving latency sensitive ones.
+    ///
+    /// Declaring a class again replaces its limit for methods assigned
+    /// afterwards.
+    ///
+    /// [`priority`]: #method.priority
+    pub fn priority_class(
+        &mut self,
+        class: impl Into<String>,
+        concurrency: usize,
+    ) -> &mut RpcRouter {
+        self.classes
+            .insert(class.into(), Arc::new(Semaphore::new(concurrency)));
+        self
+    }
+
+    /// Assign the method to the priority class.
+    ///
+    /// Methods without a class are not limited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered or the class is not declared
+    /// by [`priority_class`].
+    ///
+    /// [`priority_class`]: #method.priority_class
+    pub fn priority(&mut self, name: &str, class: &str) -> &mut RpcRouter {
+        let permits = self
+            .classes
+            .get(class)
+            .unwrap_or_else(|| panic!("Priority class \"{}\" is not declared", class))
+            .clone();
+        self.registered(name).class = Some(permits);
+        self
+    }
+}
+
+/// Wait for a slot of the class, if any.
+pub(crate) async fn admit(class: Option<&Arc<Semaphore>>) -> Option<OwnedSemaphorePermit> {
+    // The semaphores are never closed.
+    class?.clone().acquire_owned().await.ok()
+}
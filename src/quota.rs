@@ -0,0 +1,379 @@
+use crate::{
+    kv::{self, KvStore},
+    Context, Error, RpcRouter,
+};
+use http::{header::RETRY_AFTER, HeaderMap, HeaderValue};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+/// Error code returned when a client has used up its byte quota.
+pub const QUOTA_EXCEEDED_CODE: i64 = -32005;
+
+/// HTTP header carrying the quota of the client, on rejected calls.
+pub const RATE_LIMIT_LIMIT: &str = "X-RateLimit-Limit";
+/// HTTP header carrying how much of the quota is left, on rejected calls.
+pub const RATE_LIMIT_REMAINING: &str = "X-RateLimit-Remaining";
+/// HTTP header carrying seconds until some of the quota is available again,
+/// on rejected calls.
+pub const RATE_LIMIT_RESET: &str = "X-RateLimit-Reset";
+
+type Client = Arc<dyn Fn(&Context) -> Option<String> + Send + Sync>;
+
+impl RpcRouter {
+    /// Limit how many response bytes each client can receive within a
+    /// rolling `window`.
+    ///
+    /// `client` identifies the client of a call, e.g. by an API key header.
+    /// Calls without a client are not limited. Once a client has received
+    /// `limit` bytes within the window, its calls are answered by
+    /// `QUOTA_EXCEEDED_CODE` error without calling the handler. The `data`
+    /// of the error is a [`QuotaExceeded`], which is also sent by the
+    /// `Retry-After`, `X-RateLimit-Limit`, `X-RateLimit-Remaining` and
+    /// `X-RateLimit-Reset` headers.
+    ///
+    /// The response exceeding the quota is still sent, since it is not known
+    /// how large a response is until it is built. The window rolls by a
+    /// sixteenth of it at once, and clients which received nothing within
+    /// the window are forgotten.
+    ///
+    /// [`QuotaExceeded`]: ./struct.QuotaExceeded.html
+    pub fn byte_quota<F>(&mut self, limit: u64, window: Duration, client: F) -> &mut RpcRouter
+    where
+        F: Fn(&Context) -> Option<String> + Send + Sync + 'static,
+    {
+        self.quota = Some(Arc::new(ByteQuota {
+            limit,
+            window,
+            client: Arc::new(client),
+            usage: Usage::Rolling(Rolling::new(window)),
+        }));
+        self
+    }
+
+    /// Limit how many response bytes each client can receive as
+    /// [`byte_quota`], counting them in `store`.
+    ///
+    /// Processes sharing a store enforce the quota together. Bytes are
+    /// counted in fixed windows starting at multiples of `window` since the
+    /// Unix epoch, so a client may receive up to twice `limit` around the
+    /// start of a window. If the store fails, calls are not limited.
+    ///
+    /// [`byte_quota`]: #method.byte_quota
+    pub fn byte_quota_in<F, S>(
+        &mut self,
+        limit: u64,
+        window: Duration,
+        client: F,
+        store: S,
+    ) -> &mut RpcRouter
+    where
+        F: Fn(&Context) -> Option<String> + Send + Sync + 'static,
+        S: KvStore + 'static,
+    {
+        self.quota = Some(Arc::new(ByteQuota {
+            limit,
+            window,
+            client: Arc::new(client),
+            usage: Usage::Fixed(Arc::new(store)),
+        }));
+        self
+    }
+}
+
+/// `data` of the error answered when a byte quota is exceeded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuotaExceeded {
+    pub limit: u64,
+    pub used: u64,
+    pub remaining: u64,
+    /// Seconds until some of the quota is available again.
+    pub retry_after: u64,
+}
+
+impl QuotaExceeded {
+    pub(crate) fn insert_headers(&self, headers: &mut HeaderMap) {
+        insert_retry_after(headers, self.retry_after);
+        headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(RATE_LIMIT_RESET, HeaderValue::from(self.retry_after));
+    }
+}
+
+impl From<QuotaExceeded> for Error {
+    fn from(exceeded: QuotaExceeded) -> Error {
+        Error::custom(QUOTA_EXCEEDED_CODE, "Byte quota exceeded").with_data(exceeded)
+    }
+}
+
+/// Usage of a byte quota, returned by `rpc_usage`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuotaUsage {
+    pub limit: u64,
+    pub used: u64,
+    pub remaining: u64,
+    /// Seconds of the rolling window.
+    pub window: u64,
+    /// Seconds until some of the used quota is available again.
+    pub reset: u64,
+}
+
+pub(crate) fn insert_retry_after(headers: &mut HeaderMap, seconds: u64) {
+    headers.insert(RETRY_AFTER, HeaderValue::from(seconds));
+}
+
+pub(crate) struct ByteQuota {
+    limit: u64,
+    window: Duration,
+    client: Client,
+    usage: Usage,
+}
+
+enum Usage {
+    /// Bytes sent to each client within the rolling window.
+    Rolling(Rolling),
+    /// Counters of bytes sent to each client within fixed windows.
+    Fixed(Arc<dyn KvStore>),
+}
+
+impl ByteQuota {
+    pub(crate) fn client(&self, ctx: &Context) -> Option<String> {
+        (self.client)(ctx)
+    }
+
+    /// Fail if `client` has used up its quota, which is `limit` if given.
+    pub(crate) async fn check(
+        &self,
+        client: &str,
+        limit: Option<u64>,
+    ) -> Result<(), QuotaExceeded> {
+        let usage = self.usage(client, limit).await;
+        if usage.used < usage.limit {
+            return Ok(());
+        }
+        Err(QuotaExceeded {
+            limit: usage.limit,
+            used: usage.used,
+            remaining: usage.remaining,
+            retry_after: usage.reset,
+        })
+    }
+
+    /// Usage of `client` within the current window.
+    pub(crate) async fn usage(&self, client: &str, limit: Option<u64>) -> QuotaUsage {
+        let limit = limit.unwrap_or(self.limit);
+        let window = self.window;
+        let (used, reset) = match &self.usage {
+            Usage::Rolling(rolling) => rolling.usage(client),
+            Usage::Fixed(store) => {
+                let (key, reset) = self.fixed_window(client);
+                let used = match store.get(&key).await {
+                    Ok(value) => kv::counter(value.as_deref()),
+                    Err(e) => Err(e),
+                };
+                let used = used.unwrap_or_else(|e| {
+                    log::error!(target: "warp_json_rpc", "Failed to get byte quota usage: {}", e);
+                    0
+                });
+                (used, if used == 0 { 0 } else { reset })
+            }
+        };
+        QuotaUsage {
+            limit,
+            used,
+            remaining: limit.saturating_sub(used),
+            window: window.as_secs(),
+            reset,
+        }
+    }
+
+    pub(crate) async fn record(&self, client: String, bytes: u64) {
+        match &self.usage {
+            Usage::Rolling(rolling) => rolling.record(client, bytes),
+            Usage::Fixed(store) => {
+                let (key, _) = self.fixed_window(&client);
+                if let Err(e) = store.increment(&key, bytes, self.window).await {
+                    log::error!(target: "warp_json_rpc", "Failed to record byte quota usage: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Key of the counter of `client` in the current fixed window, and
+    /// seconds until the window ends.
+    fn fixed_window(&self, client: &str) -> (String, u64) {
+        let window = self.window.as_secs().max(1);
+        let now = UNIX_EPOCH.elapsed().unwrap_or_default().as_secs();
+        let start = now - now % window;
+        (
+            format!("quota:{:?}:{}", client, start),
+            start + window - now,
+        )
+    }
+}
+
+/// Number of buckets a rolling window is counted in.
+const BUCKETS: usize = 16;
+
+/// Bytes sent to each client, counted in buckets of a sixteenth of the
+/// window, so the usage within the window is told in constant time. The
+/// window rolls by a bucket at once.
+struct Rolling {
+    origin: Instant,
+    bucket: Duration,
+    clients: Mutex<Clients>,
+}
+
+struct Clients {
+    buckets: HashMap<String, [(u64, u64); BUCKETS]>,
+    /// The bucket from which on clients which sent nothing within the window
+    /// are forgotten.
+    next_sweep: u64,
+}
+
+impl Rolling {
+    fn new(window: Duration) -> Rolling {
+        Rolling {
+            origin: Instant::now(),
+            bucket: (window / BUCKETS as u32).max(Duration::from_nanos(1)),
+            clients: Mutex::new(Clients {
+                buckets: HashMap::new(),
+                next_sweep: BUCKETS as u64,
+            }),
+        }
+    }
+
+    /// The bucket of now, counting from the origin.
+    fn now(&self) -> u64 {
+        (self.origin.elapsed().as_nanos() / self.bucket.as_nanos()) as u64
+    }
+
+    /// Bytes sent to `client` within the window, and seconds until some of
+    /// them are out of it.
+    fn usage(&self, client: &str) -> (u64, u64) {
+        let now = self.now();
+        let clients = self.clients.lock().unwrap();
+        let buckets = match clients.buckets.get(client) {
+            Some(buckets) => buckets,
+            None => return (0, 0),
+        };
+        let live = buckets
+            .iter()
+            .filter(|(index, bytes)| *bytes > 0 && index + BUCKETS as u64 > now);
+        let used = live.clone().map(|(_, bytes)| bytes).sum();
+        let reset = live.map(|(index, _)| *index).min().map_or(0, |oldest| {
+            let ends = self.bucket.as_nanos() * u128::from(oldest + BUCKETS as u64);
+            let left = ends.saturating_sub(self.origin.elapsed().as_nanos());
+            (left / 1_000_000_000) as u64 + 1
+        });
+        (used, reset)
+    }
+
+    fn record(&self, client: String, bytes: u64) {
+        let now = self.now();
+        let mut clients = self.clients.lock().unwrap();
+        if now >= clients.next_sweep {
+            clients.buckets.retain(|_, buckets| {
+                buckets
+                    .iter()
+                    .any(|(index, bytes)| *bytes > 0 && index + BUCKETS as u64 > now)
+            });
+            clients.next_sweep = now + BUCKETS as u64;
+        }
+        let buckets = clients.buckets.entry(client).or_insert([(0, 0); BUCKETS]);
+        let bucket = &mut buckets[(now % BUCKETS as u64) as usize];
+        if bucket.0 != now {
+            *bucket = (now, 0);
+        }
+        bucket.1 += bytes;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rolling(limit: u64, window: Duration) -> ByteQuota {
+        ByteQuota {
+            limit,
+            window,
+            client: Arc::new(|_| None),
+            usage: Usage::Rolling(Rolling::new(window)),
+        }
+    }
+
+    #[tokio::test]
+    async fn exceed_quota() {
+        let quota = rolling(100, Duration::from_secs(60));
+        assert!(quota.check("a", None).await.is_ok());
+
+        quota.record("a".to_string(), 60).await;
+        assert!(quota.check("a", None).await.is_ok());
+        quota.record("a".to_string(), 60).await;
+
+        let exceeded = quota.check("a", None).await.err().unwrap();
+        assert_eq!(exceeded.used, 120);
+        assert_eq!(exceeded.retry_after, 60);
+
+        let mut headers = HeaderMap::new();
+        exceeded.insert_headers(&mut headers);
+        assert_eq!(headers[RETRY_AFTER], "60");
+        assert_eq!(headers[RATE_LIMIT_LIMIT], "100");
+        assert_eq!(headers[RATE_LIMIT_REMAINING], "0");
+
+        let error = Error::from(exceeded);
+        assert_eq!(error.code, QUOTA_EXCEEDED_CODE);
+        let data = serde_json::to_value(error.data.unwrap()).unwrap();
+        assert_eq!(data["used"], 120);
+        assert!(quota.check("b", None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn usage_expires_after_window() {
+        let quota = rolling(100, Duration::from_millis(10));
+        quota.record("a".to_string(), 200).await;
+        assert!(quota.check("a", None).await.is_err());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(quota.check("a", None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn forget_idle_clients() {
+        let quota = rolling(100, Duration::from_millis(16));
+        quota.record("a".to_string(), 10).await;
+        quota.record("b".to_string(), 10).await;
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        quota.record("b".to_string(), 10).await;
+
+        let rolling = match &quota.usage {
+            Usage::Rolling(rolling) => rolling,
+            Usage::Fixed(_) => unreachable!(),
+        };
+        let clients = rolling.clients.lock().unwrap();
+        assert!(!clients.buckets.contains_key("a"));
+        assert!(clients.buckets.contains_key("b"));
+    }
+
+    #[tokio::test]
+    async fn share_usage_by_store() {
+        let store = Arc::new(crate::MemoryStore::new());
+        let quota = |store: &Arc<crate::MemoryStore>| ByteQuota {
+            limit: 100,
+            window: Duration::from_secs(3600),
+            client: Arc::new(|_| None),
+            usage: Usage::Fixed(Arc::new(store.clone())),
+        };
+        let (a, b) = (quota(&store), quota(&store));
+        a.record("c".to_string(), 60).await;
+        b.record("c".to_string(), 60).await;
+
+        let exceeded = a.check("c", None).await.err().unwrap();
+        assert_eq!(exceeded.used, 120);
+        assert!(exceeded.retry_after > 0 && exceeded.retry_after <= 3600);
+        assert!(b.check("c", None).await.is_err());
+        assert!(b.check("d", None).await.is_ok());
+    }
+}
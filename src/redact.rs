@@ -0,0 +1,159 @@
+use crate::{
+    diff::{self, Difference},
+    Error, RpcRouter,
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// What sensitive values are replaced with.
+pub const REDACTED: &str = "[REDACTED]";
+
+impl RpcRouter {
+    /// Mark values of the method at JSON pointers `pointers` as sensitive.
+    ///
+    /// Pointers are taken from a document of the call as
+    /// `{ "params": ..., "result": ..., "error": ... }`, so `/params/password`
+    /// marks `password` of the parameter, and `/error/data/card` marks `card`
+    /// of the `data` of errors. A `*` segment matches any member or index.
+    ///
+    /// Sensitive values are replaced with `"[REDACTED]"` in the `data` of
+    /// errors answered and sent to the [`error_reporter`], and in the
+    /// differences reported by [`shadow_diff`]. Other logging can do the same
+    /// by [`redact`].
+    ///
+    /// [`error_reporter`]: #method.error_reporter
+    /// [`shadow_diff`]: #method.shadow_diff
+    /// [`redact`]: #method.redact
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn sensitive(&mut self, name: &str, pointers: &[&str]) -> &mut RpcRouter {
+        let patterns = pointers
+            .iter()
+            .map(|pointer| diff::segments(pointer))
+            .collect();
+        self.registered(name).sensitive = Sensitive(Arc::new(patterns));
+        self
+    }
+
+    /// Replace values of `document` marked as sensitive for `method` by
+    /// [`sensitive`].
+    ///
+    /// [`sensitive`]: #method.sensitive
+    pub fn redact(&self, method: &str, document: &mut Value) {
+        if let Some(method) = self.methods.get(method) {
+            method.sensitive.redact(document);
+        }
+    }
+}
+
+/// Patterns of sensitive values of a method.
+#[derive(Clone, Default)]
+pub(crate) struct Sensitive(Arc<Vec<Vec<String>>>);
+
+impl Sensitive {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn redact(&self, document: &mut Value) {
+        self.redact_at(&mut Vec::new(), document);
+    }
+
+    /// Replace sensitive values in `data` of `error`.
+    pub(crate) fn redact_error(&self, mut error: Error) -> Error {
+        if self.is_empty() {
+            return error;
+        }
+        if let Some(data) = error.data.take() {
+            let mut document = serde_json::json!({ "error": { "data": data } });
+            self.redact(&mut document);
+            error = error.with_data(document["error"]["data"].take());
+        }
+        error
+    }
+
+    pub(crate) fn redact_difference(&self, difference: &mut Difference) {
+        let mut path = diff::segments(&difference.pointer);
+        for value in [&mut difference.primary, &mut difference.shadow] {
+            if let Some(value) = value.as_mut() {
+                self.redact_at(&mut path, value);
+            }
+        }
+    }
+
+    fn redact_at(&self, path: &mut Vec<String>, value: &mut Value) {
+        if self.0.iter().any(|pattern| matches(pattern, path)) {
+            *value = Value::from(REDACTED);
+            return;
+        }
+        match value {
+            Value::Object(members) => {
+                for (name, value) in members {
+                    path.push(name.clone());
+                    self.redact_at(path, value);
+                    path.pop();
+                }
+            }
+            Value::Array(elements) => {
+                for (index, value) in elements.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    self.redact_at(path, value);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether `path` is at or below `pattern`.
+fn matches(pattern: &[String], path: &[String]) -> bool {
+    pattern.len() <= path.len()
+        && pattern
+            .iter()
+            .zip(path)
+            .all(|(pattern, segment)| pattern == "*" || pattern == segment)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redact_sensitive_values() {
+        let sensitive = Sensitive(Arc::new(vec![
+            diff::segments("/params/password"),
+            diff::segments("/result/*/token"),
+            diff::segments("/error/data/card"),
+        ]));
+        let mut document = json!({
+            "params": { "user": "a", "password": "secret" },
+            "result": [{ "token": "t1", "id": 1 }, { "token": { "nested": 1 } }],
+        });
+        sensitive.redact(&mut document);
+        assert_eq!(
+            document,
+            json!({
+                "params": { "user": "a", "password": REDACTED },
+                "result": [{ "token": REDACTED, "id": 1 }, { "token": REDACTED }],
+            })
+        );
+
+        let error =
+            sensitive.redact_error(Error::INVALID_PARAMS.with_data(json!({ "card": "4111" })));
+        let data = serde_json::to_value(error.data.unwrap()).unwrap();
+        assert_eq!(data, json!({ "card": REDACTED }));
+
+        let mut difference = Difference {
+            pointer: "/result/0".to_string(),
+            primary: Some(json!({ "token": "t1" })),
+            shadow: Some(json!({ "token": "t2" })),
+        };
+        sensitive.redact_difference(&mut difference);
+        assert_eq!(difference.primary, Some(json!({ "token": REDACTED })));
+        assert_eq!(difference.shadow, Some(json!({ "token": REDACTED })));
+    }
+}
@@ -0,0 +1,78 @@
+//! Rejections returned by the filters in this crate.
+//!
+//! Use [`Rejection::find`] inside `recover` to turn them into responses.
+//!
+//! [`Rejection::find`]: https://docs.rs/warp/0.3.0/warp/reject/struct.Rejection.html#method.find
+use crate::Error;
+use std::{fmt, sync::Arc};
+use warp::reject::Reject;
+
+/// Rejected because the `Filter` is not wrapped by [`JsonRpcService`].
+///
+/// [`JsonRpcService`]: ../struct.JsonRpcService.html
+#[derive(Debug)]
+pub struct MissingService {
+    _p: (),
+}
+
+impl MissingService {
+    pub(crate) fn new() -> MissingService {
+        MissingService { _p: () }
+    }
+}
+
+impl fmt::Display for MissingService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Filter is not wrapped by `JsonRpcService`")
+    }
+}
+
+impl Reject for MissingService {}
+
+/// Rejected because the request body is not a valid JSON RPC request.
+///
+/// The body is parsed only once per request, so every `json_rpc` filter
+/// composed by `or` observes the same `InvalidRequest`.
+///
+/// [`service`] answers it by `PARSE_ERROR` error if the body is not JSON, or
+/// by `INVALID_REQUEST` error otherwise, with the status of its
+/// [`StatusMapping`].
+///
+/// [`service`]: ../fn.service.html
+/// [`StatusMapping`]: ../struct.StatusMapping.html
+#[derive(Debug, Clone)]
+pub struct InvalidRequest {
+    message: Arc<String>,
+    malformed: bool,
+}
+
+impl InvalidRequest {
+    pub(crate) fn from_json(e: &serde_json::Error) -> InvalidRequest {
+        InvalidRequest {
+            message: Arc::new(e.to_string()),
+            malformed: !e.is_data(),
+        }
+    }
+
+    /// The JSON RPC error answering this rejection.
+    pub(crate) fn error(&self) -> Error {
+        let error = if self.malformed {
+            Error::PARSE_ERROR
+        } else {
+            Error::INVALID_REQUEST
+        };
+        error.with_data(self.message.to_string())
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+impl fmt::Display for InvalidRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid JSON RPC request: {}", self.message)
+    }
+}
+
+impl Reject for InvalidRequest {}
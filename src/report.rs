@@ -0,0 +1,95 @@
+use crate::{sha256, Context, Error, RpcRouter};
+use std::{any::Any, sync::Arc};
+
+/// How a call failed, in an [`ErrorReport`].
+///
+/// [`ErrorReport`]: ./struct.ErrorReport.html
+pub enum Failure<'a> {
+    /// The handler returned an error.
+    Error(&'a Error),
+    /// The handler panicked with the message. The call is answered by
+    /// `INTERNAL_ERROR`.
+    Panic(&'a str),
+}
+
+/// A failed call, sent to an [`ErrorReporter`].
+///
+/// [`ErrorReporter`]: ./trait.ErrorReporter.html
+pub struct ErrorReport<'a> {
+    pub method: &'a str,
+    /// Hex HMAC-SHA256 of the JSON text of the RPC parameter by the key set
+    /// by [`RpcRouter::params_digest_key`], to group failures by their input
+    /// without reporting it.
+    ///
+    /// [`RpcRouter::params_digest_key`]: ./struct.RpcRouter.html#method.params_digest_key
+    pub params_digest: Option<String>,
+    pub context: &'a Context,
+    pub failure: Failure<'a>,
+}
+
+/// Receives handler errors and panics, e.g. to send them to an error
+/// tracking service.
+///
+/// Reports are made on the task answering the call, so an implementation
+/// sending them over the network should do so in the background.
+pub trait ErrorReporter: Send + Sync {
+    fn report(&self, report: &ErrorReport<'_>);
+}
+
+impl<F> ErrorReporter for F
+where
+    F: Fn(&ErrorReport<'_>) + Send + Sync,
+{
+    fn report(&self, report: &ErrorReport<'_>) {
+        self(report)
+    }
+}
+
+impl RpcRouter {
+    /// Send errors returned by handlers and panics of handlers to `reporter`.
+    ///
+    /// Panics are caught once this is called, so a panicking handler is
+    /// answered by `INTERNAL_ERROR` instead of dropping the connection.
+    pub fn error_reporter<R>(&mut self, reporter: R) -> &mut RpcRouter
+    where
+        R: ErrorReporter + 'static,
+    {
+        self.reporter = Some(Arc::new(reporter));
+        self
+    }
+
+    /// Digest parameters in error reports and audit records by `key`, a
+    /// secret of the server.
+    ///
+    /// Digests can only be compared with digests by the same key, and cannot
+    /// be reversed by guessing parameters without it. A random key is chosen
+    /// for each router by default, so set a key shared by every process of
+    /// the deployment to compare digests between them.
+    pub fn params_digest_key(&mut self, key: impl Into<Vec<u8>>) -> &mut RpcRouter {
+        self.digest_key = DigestKey(Arc::new(key.into()));
+        self
+    }
+}
+
+/// Key of the digests of parameters.
+#[derive(Clone)]
+pub(crate) struct DigestKey(Arc<Vec<u8>>);
+
+impl Default for DigestKey {
+    fn default() -> DigestKey {
+        DigestKey(Arc::new(rand::random::<[u8; 32]>().to_vec()))
+    }
+}
+
+pub(crate) fn params_digest(key: &DigestKey, raw: Option<&str>) -> Option<String> {
+    raw.map(|raw| sha256::hex(&sha256::hmac(&key.0, &[raw.as_bytes()])))
+}
+
+pub(crate) fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => panic
+            .downcast_ref::<String>()
+            .map_or("Box<dyn Any>", String::as_str),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::{de, intern::Name, Error, MethodId};
 use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
 use std::sync::Arc;
@@ -14,9 +15,11 @@ use std::sync::Arc;
 // So currently we wrap `method` and `params` by `Arc` separately.
 #[derive(Debug, Clone, Deserialize)]
 pub struct Request {
+    // Only checked while deserializing.
+    #[allow(dead_code)]
     jsonrpc: Version,
     id: Id,
-    method: Arc<String>,
+    method: Name,
     params: Arc<Option<Box<RawValue>>>,
 }
 
@@ -58,20 +61,89 @@ impl Request {
     }
 
     pub fn method(&self) -> &str {
-        self.method.as_str()
+        self.method.name.as_str()
     }
 
+    /// Identifier of the method, if it is registered.
+    pub fn method_id(&self) -> Option<MethodId> {
+        self.method.id
+    }
+
+    pub(crate) fn method_name(&self) -> Name {
+        self.method.clone()
+    }
+
+    pub(crate) fn name_mut(&mut self) -> &mut Name {
+        &mut self.method
+    }
+
+    pub fn params(&self) -> Params {
+        Params {
+            params: self.params.clone(),
+        }
+    }
+
+    /// Deserialize the RPC parameter into `T`.
+    ///
+    /// Absent parameter is regarded as empty, so `()`, `Option`, tuples of
+    /// `Option` and structs whose fields are all optional are deserialized
+    /// successfully. Missing trailing elements of positional parameters are
+    /// regarded as `null`, so they can be received by `Option`.
     pub fn deserialize_param<'de, T>(&'de self) -> Result<T, anyhow::Error>
     where
         T: Deserialize<'de>,
     {
         match self.params.as_ref() {
-            Some(params) => Ok(serde_json::from_str(params.get())?),
-            None => Err(anyhow::anyhow!("No parameter is presented")),
+            Some(params) => Ok(de::from_str(params.get())?),
+            None => Ok(de::absent()?),
         }
     }
 }
 
+/*
+ * ======
+ * Params
+ * ======
+ */
+/// RPC parameter of a request, not deserialized yet.
+#[derive(Debug, Clone)]
+pub struct Params {
+    params: Arc<Option<Box<RawValue>>>,
+}
+
+impl Params {
+    pub(crate) fn from_raw(params: Option<Box<RawValue>>) -> Params {
+        Params {
+            params: Arc::new(params),
+        }
+    }
+
+    /// Deserialize the parameter into `T` in the same way as
+    /// [`Request::deserialize_param`].
+    ///
+    /// Failure is reported as `INVALID_PARAMS` error whose `data` is a list of
+    /// a [`Violation`], pointing at the offending value and telling what was
+    /// expected there.
+    ///
+    /// [`Request::deserialize_param`]: ./struct.Request.html#method.deserialize_param
+    /// [`Violation`]: ./struct.Violation.html
+    pub fn parse<'de, T>(&'de self) -> Result<T, Error>
+    where
+        T: Deserialize<'de>,
+    {
+        let parsed = match self.params.as_ref() {
+            Some(params) => de::from_str(params.get()),
+            None => de::absent(),
+        };
+        parsed.map_err(|e| Error::INVALID_PARAMS.with_data(vec![de::violation(self.raw(), &e)]))
+    }
+
+    /// The raw JSON text of the parameter, if it is presented.
+    pub fn raw(&self) -> Option<&str> {
+        self.params.as_ref().as_ref().map(|params| params.get())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -106,6 +178,50 @@ mod test {
         );
     }
 
+    #[test]
+    fn point_at_invalid_params() {
+        #[derive(Debug, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct Item {
+            name: String,
+            count: u32,
+        }
+
+        let violation = |json: &str| {
+            let params = Params::from_raw(Some(RawValue::from_string(json.to_string()).unwrap()));
+            let error = params.parse::<(u64, Vec<Item>)>().err().unwrap();
+            assert_eq!(error.code, Error::INVALID_PARAMS.code);
+            let data = serde_json::to_value(error.data.unwrap()).unwrap();
+            (
+                data[0]["pointer"].as_str().unwrap().to_string(),
+                data[0]["expected"].as_str().map(str::to_string),
+            )
+        };
+
+        assert_eq!(violation(r#"["1", []]"#), ("/0".into(), Some("u64".into())));
+        assert_eq!(
+            violation(r#"[1, [{"name": "a", "count": 1}, {"name": "b", "count": -1}]]"#),
+            ("/1/1/count".into(), Some("u32".into()))
+        );
+        assert_eq!(
+            violation("[1, [\n  {\"name\": 2, \"count\": 1}\n]]"),
+            ("/1/0/name".into(), Some("a string".into()))
+        );
+        assert_eq!(
+            violation(r#"[1, [{"name": "a"}]]"#),
+            ("/1/0/count".into(), None)
+        );
+        assert_eq!(
+            violation(r#"[1, [{"name": "a", "count": 1, "extra": 0}]]"#).0,
+            "/1/0/extra"
+        );
+        assert_eq!(
+            violation(r#"[1, 2]"#),
+            ("/1".into(), Some("a sequence".into()))
+        );
+    }
+
     #[test]
     fn deserialize_by_pos_request() {
         let req_str = r#"{
@@ -176,4 +292,47 @@ mod test {
         }"#;
         assert!(serde_json::from_str::<Request>(req_str).is_err());
     }
+
+    #[test]
+    fn deserialize_absent_params() {
+        let req_str = r#"{
+            "jsonrpc": "2.0",
+            "method": "op",
+            "id": 1
+        }"#;
+        let req = serde_json::from_str::<Request>(req_str).unwrap();
+
+        #[derive(PartialEq, Eq, Debug, Deserialize)]
+        struct Param {
+            lhs: Option<i32>,
+            #[serde(default)]
+            rhs: i32,
+        }
+
+        req.deserialize_param::<()>().unwrap();
+        assert_eq!(req.deserialize_param::<Option<i32>>().unwrap(), None);
+        assert_eq!(req.deserialize_param::<(Option<i32>,)>().unwrap(), (None,));
+        assert_eq!(
+            req.deserialize_param::<Param>().unwrap(),
+            Param { lhs: None, rhs: 0 }
+        );
+        assert!(req.deserialize_param::<(i32,)>().is_err());
+        assert!(req.deserialize_param::<i32>().is_err());
+    }
+
+    #[test]
+    fn deserialize_trailing_optional_params() {
+        let req_str = r#"{
+            "jsonrpc": "2.0",
+            "method": "op",
+            "params": [24, "+"],
+            "id": 1
+        }"#;
+        let req = serde_json::from_str::<Request>(req_str).unwrap();
+
+        let (lhs, op, rhs) = req.deserialize_param::<(i32, &str, Option<i32>)>().unwrap();
+        assert_eq!((lhs, op, rhs), (24, "+", None));
+        assert!(req.deserialize_param::<(i32, &str, i32)>().is_err());
+        assert!(req.deserialize_param::<(i32,)>().is_err());
+    }
 }
@@ -1,7 +1,12 @@
-use crate::req::{Id, Version};
+use crate::{
+    meta::ExecutionMeta,
+    req::{Id, Version},
+    status::StatusMapping,
+};
+use http::StatusCode;
 use hyper::Body;
 use serde::Serialize;
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt};
 
 /*
  * ========
@@ -14,6 +19,8 @@ struct Response {
     id: Id,
     #[serde(flatten)]
     content: ResponseContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<ExecutionMeta>,
 }
 
 impl Response {
@@ -22,39 +29,75 @@ impl Response {
             jsonrpc: Version::V2,
             id,
             content,
+            meta: None,
         }
     }
 
     /// Currently `warp` does not expose `Reply` trait (it is guarded).
     /// So we need to convert this into something that implements `Reply`.
-    fn into_reply(self) -> anyhow::Result<http::Response<Body>> {
-        let body = Body::from(serde_json::to_vec(&self)?);
+    fn into_reply(self, status: StatusCode) -> anyhow::Result<http::Response<Body>> {
+        let body = serde_json::to_vec(&self)?;
         Ok(http::Response::builder()
-            .status(200)
+            .status(status)
             .header("Content-Type", "application/json")
-            .body(body)
+            .header("Content-Length", body.len())
+            .body(Body::from(body))
             .unwrap())
     }
 }
 
+#[derive(Clone)]
 pub struct Builder {
     id: Id,
+    status: StatusMapping,
+    meta: Option<ExecutionMeta>,
 }
 
 impl Builder {
-    pub(crate) fn new(id: Id) -> Builder {
-        Builder { id }
+    pub(crate) fn new(id: Id, status: StatusMapping) -> Builder {
+        Builder {
+            id,
+            status,
+            meta: None,
+        }
+    }
+
+    pub(crate) fn id(&self) -> &Id {
+        &self.id
+    }
+
+    /// Include `meta` in the `meta` field of the response.
+    pub fn with_meta(mut self, meta: ExecutionMeta) -> Builder {
+        self.meta = Some(meta);
+        self
     }
 
     pub fn success<S>(self, content: S) -> anyhow::Result<http::Response<Body>>
     where
         S: Serialize + 'static,
     {
-        Response::new(self.id, ResponseContent::Success(Box::new(content))).into_reply()
+        let mut res = Response::new(self.id, ResponseContent::Success(Box::new(content)));
+        res.meta = self.meta;
+        res.into_reply(StatusCode::OK)
+    }
+
+    /// Shorthand of `with_meta(meta).success(content)`.
+    pub fn success_with_meta<S>(
+        self,
+        content: S,
+        meta: ExecutionMeta,
+    ) -> anyhow::Result<http::Response<Body>>
+    where
+        S: Serialize + 'static,
+    {
+        self.with_meta(meta).success(content)
     }
 
     pub fn error(self, error: Error) -> anyhow::Result<http::Response<Body>> {
-        Response::new(self.id, ResponseContent::Error(error)).into_reply()
+        let status = self.status.status(&error);
+        let mut res = Response::new(self.id, ResponseContent::Error(error));
+        res.meta = self.meta;
+        res.into_reply(status)
     }
 
     pub fn result<S>(self, result: Result<S, Error>) -> anyhow::Result<http::Response<Body>>
@@ -80,7 +123,9 @@ enum ResponseContent {
 pub struct Error {
     pub code: i64,
     pub message: Cow<'static, str>,
-    pub data: Option<Box<dyn erased_serde::Serialize>>,
+    /// `Send` and `Sync` since 0.4, so errors can be kept and answered from
+    /// other tasks, e.g. by idempotency and coalescing.
+    pub data: Option<Box<dyn erased_serde::Serialize + Send + Sync>>,
 }
 
 impl Error {
@@ -125,15 +170,65 @@ impl Error {
         }
     }
 
+    /// Like [`custom`], but rejects codes reserved by the JSON RPC
+    /// specification (-32768 to -32000) other than those of the predefined
+    /// errors.
+    ///
+    /// [`custom`]: #method.custom
+    pub fn checked<S>(code: i64, message: S) -> Result<Error, ReservedCode>
+    where
+        Cow<'static, str>: From<S>,
+    {
+        if Error::is_reserved(code) {
+            return Err(ReservedCode(code));
+        }
+        Ok(Error::custom(code, message))
+    }
+
+    fn is_reserved(code: i64) -> bool {
+        let predefined = [
+            Error::PARSE_ERROR.code,
+            Error::INVALID_REQUEST.code,
+            Error::METHOD_NOT_FOUND.code,
+            Error::INVALID_PARAMS.code,
+            Error::INTERNAL_ERROR.code,
+        ];
+        (-32768..=-32000).contains(&code) && !predefined.contains(&code)
+    }
+
+    /// Attach `data` to the error.
+    ///
+    /// `data` must be `Send` and `Sync` since 0.4, which is a breaking change
+    /// from 0.3, where any `Serialize` type was accepted.
     pub fn with_data<S>(mut self, data: S) -> Error
     where
-        S: Serialize + 'static,
+        S: Serialize + Send + Sync + 'static,
     {
-        self.data = Some(Box::new(data) as Box<dyn erased_serde::Serialize>);
+        self.data = Some(Box::new(data) as Box<dyn erased_serde::Serialize + Send + Sync>);
         self
     }
 }
 
+/// A custom error code in the range reserved by the JSON RPC specification.
+///
+/// Returned by [`Error::checked`].
+///
+/// [`Error::checked`]: ./struct.Error.html#method.checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReservedCode(pub i64);
+
+impl fmt::Display for ReservedCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "error code {} is reserved by the JSON RPC specification",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ReservedCode {}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -193,4 +288,67 @@ mod test {
 
         assert_eq!(deserialized, expected);
     }
+
+    #[test]
+    fn error_response_status() {
+        let status = StatusMapping::new(|err: &Error| match err.code {
+            -32603 => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::OK,
+        });
+
+        let res = Builder::new(Id::Null, status.clone())
+            .error(Error::INTERNAL_ERROR)
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let res = Builder::new(Id::Null, status.clone())
+            .error(Error::INVALID_PARAMS)
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = Builder::new(Id::Null, status).success(42).unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn checked_custom_error() {
+        assert_eq!(Error::checked(1, "failed").ok().map(|e| e.code), Some(1));
+        assert_eq!(
+            Error::checked(-32602, "bad").ok().map(|e| e.code),
+            Some(-32602)
+        );
+        assert_eq!(
+            Error::checked(-32000, "x").err(),
+            Some(ReservedCode(-32000))
+        );
+        assert_eq!(
+            Error::checked(-32768, "x").err(),
+            Some(ReservedCode(-32768))
+        );
+        assert!(Error::checked(-31999, "x").is_ok());
+    }
+
+    #[test]
+    fn success_with_meta() {
+        use std::time::Duration;
+
+        let meta = ExecutionMeta {
+            server_time: 1000,
+            duration: Duration::from_millis(5),
+            node_id: None,
+        };
+        let res = Response {
+            meta: Some(meta),
+            ..Response::new(Id::Number(1), ResponseContent::Success(Box::new(42)))
+        };
+        assert_eq!(
+            serde_json::to_value(&res).unwrap(),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": 42,
+                "meta": { "server_time": 1000, "duration_ms": 5.0 },
+            })
+        );
+    }
 }
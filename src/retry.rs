@@ -0,0 +1,108 @@
+use crate::{
+    RpcRouter, DEADLINE_EXCEEDED_CODE, IDEMPOTENCY_IN_FLIGHT_CODE, QUOTA_EXCEEDED_CODE,
+    UNAVAILABLE_CODE,
+};
+use http::StatusCode;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Whether a failed call may succeed if it is made again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Retryability {
+    /// The call may succeed later, e.g. once a limit resets.
+    Transient,
+    /// The call fails the same way until it is changed.
+    Permanent,
+}
+
+impl Retryability {
+    /// Classify a failed call by its HTTP status and JSON RPC error code,
+    /// either of which may be missing.
+    ///
+    /// `408`, `429`, `502`, `503` and `504` statuses, and the codes of
+    /// exceeded quotas and deadlines, of open circuit breakers and of
+    /// idempotency keys in flight are transient. Everything else is permanent.
+    pub fn classify(status: Option<StatusCode>, code: Option<i64>) -> Retryability {
+        let transient_status = matches!(
+            status,
+            Some(StatusCode::REQUEST_TIMEOUT)
+                | Some(StatusCode::TOO_MANY_REQUESTS)
+                | Some(StatusCode::BAD_GATEWAY)
+                | Some(StatusCode::SERVICE_UNAVAILABLE)
+                | Some(StatusCode::GATEWAY_TIMEOUT)
+        );
+        let transient_code = matches!(
+            code,
+            Some(QUOTA_EXCEEDED_CODE)
+                | Some(DEADLINE_EXCEEDED_CODE)
+                | Some(UNAVAILABLE_CODE)
+                | Some(IDEMPOTENCY_IN_FLIGHT_CODE)
+        );
+        if transient_status || transient_code {
+            Retryability::Transient
+        } else {
+            Retryability::Permanent
+        }
+    }
+}
+
+pub(crate) type RetryHook = Arc<dyn Fn(i64) -> Option<Retryability> + Send + Sync>;
+
+impl RpcRouter {
+    /// Classify error codes by `hook` before [`Retryability::classify`], e.g.
+    /// to mark a custom code as transient. `None` leaves the code to the
+    /// built-in rules.
+    ///
+    /// The classification is listed by `rpc_errors` for every registered
+    /// error, so clients can decide which calls to retry.
+    ///
+    /// [`Retryability::classify`]: ./enum.Retryability.html#method.classify
+    pub fn retryability<F>(&mut self, hook: F) -> &mut RpcRouter
+    where
+        F: Fn(i64) -> Option<Retryability> + Send + Sync + 'static,
+    {
+        self.retry_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Classify a failed call by the hook and the built-in rules.
+    pub fn classify(&self, status: Option<StatusCode>, code: Option<i64>) -> Retryability {
+        let hooked = code.and_then(|code| self.retry_hook.as_ref().and_then(|hook| hook(code)));
+        hooked.unwrap_or_else(|| Retryability::classify(status, code))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn classify_by_status_code_and_hook() {
+        assert_eq!(
+            Retryability::classify(Some(StatusCode::SERVICE_UNAVAILABLE), None),
+            Retryability::Transient
+        );
+        assert_eq!(
+            Retryability::classify(Some(StatusCode::OK), Some(QUOTA_EXCEEDED_CODE)),
+            Retryability::Transient
+        );
+        assert_eq!(
+            Retryability::classify(None, Some(Error::INVALID_PARAMS.code)),
+            Retryability::Permanent
+        );
+
+        let mut router = RpcRouter::new();
+        router.retryability(|code| match code {
+            1 => Some(Retryability::Transient),
+            _ => None,
+        });
+        assert_eq!(router.classify(None, Some(1)), Retryability::Transient);
+        assert_eq!(router.classify(None, Some(2)), Retryability::Permanent);
+        assert_eq!(
+            router.classify(None, Some(UNAVAILABLE_CODE)),
+            Retryability::Transient
+        );
+    }
+}
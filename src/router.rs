@@ -0,0 +1,1678 @@
+use crate::{
+    access::{self, LogFormat},
+    audit::{AuditRecord, Auditor},
+    breaker::Breaker,
+    catalog::ErrorCatalog,
+    coalesce::Coalescer,
+    config::LiveConfig,
+    continuation::Continuations,
+    duplicate::{Duplicate, Duplicates},
+    filters,
+    flags::FlagResolver,
+    idempotency::{Found, IdempotencyCache, IDEMPOTENCY_KEY},
+    intern::Methods,
+    locale::{self, Localizer},
+    meta::{self, ExecutionMeta, MetaPlacement},
+    multipart::{self, MultipartHandler},
+    ndjson::{self, StreamHandler},
+    outcome::Outcome,
+    priority,
+    quota::ByteQuota,
+    redact::Sensitive,
+    report::{self, DigestKey, ErrorReport, ErrorReporter, Failure},
+    retry::RetryHook,
+    rules::Predicate,
+    sampling::LogSampling,
+    shadow::{self, ShadowDiff},
+    shard::Shards,
+    signing::{self, Signer},
+    store,
+    time::Timestamp,
+    transaction::Transactions,
+    transform::ResultTransform,
+    visibility::Visibility,
+    Builder, Context, Error, Id, Journal, JournalEntry, MethodDoc, Params, Request, Schema,
+};
+use futures::{
+    future::{BoxFuture, Future, FutureExt as _},
+    stream::StreamExt as _,
+};
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Value};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    panic::{AssertUnwindSafe, Location},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Semaphore;
+use warp::{path::FullPath, reject, reply::Response, Filter, Rejection};
+
+/// Type erased handler of an RPC method.
+///
+/// Unlike handlers registered by [`RpcRouter::method`], a `BoxedHandler` can be
+/// built at runtime (e.g. from plugins or configuration) and registered by
+/// [`RpcRouter::register`].
+///
+/// [`RpcRouter::method`]: ./struct.RpcRouter.html#method.method
+/// [`RpcRouter::register`]: ./struct.RpcRouter.html#method.register
+///
+/// ```
+/// # use warp_json_rpc::{BoxedHandler, Context, Params, RpcRouter};
+/// use futures::future::FutureExt as _;
+/// use std::sync::Arc;
+///
+/// let echo: BoxedHandler = Arc::new(|params: Params, ctx: Context| {
+///     async move {
+///         let value = params.parse::<serde_json::Value>()?;
+///         Ok(serde_json::json!({ "method": ctx.method(), "params": value }))
+///     }
+///     .boxed()
+/// });
+///
+/// let mut router = RpcRouter::new();
+/// for name in &["echo", "echo2"] {
+///     router.register(*name, echo.clone());
+/// }
+/// ```
+pub type BoxedHandler =
+    Arc<dyn Fn(Params, Context) -> BoxFuture<'static, Result<Value, Error>> + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct Method {
+    handler: BoxedHandler,
+    pub(crate) params_schema: Option<Schema>,
+    pub(crate) result_schema: Option<Schema>,
+    journaled: bool,
+    pub(crate) coalesced: bool,
+    pub(crate) stream: Option<StreamHandler>,
+    pub(crate) multipart: Option<MultipartHandler>,
+    pub(crate) flag: Option<String>,
+    pub(crate) shadow: Option<BoxedHandler>,
+    pub(crate) class: Option<Arc<Semaphore>>,
+    pub(crate) breaker: Option<Arc<Breaker>>,
+    location: &'static Location<'static>,
+    pub(crate) doc: Option<MethodDoc>,
+    pub(crate) sensitive: Sensitive,
+    pub(crate) transform: Option<ResultTransform>,
+    pub(crate) visibility: Visibility,
+    pub(crate) rules: Vec<(Predicate, BoxedHandler)>,
+    pub(crate) shards: Option<Arc<Shards>>,
+}
+
+/// A set of RPC methods dispatched by a single hash lookup.
+///
+/// Chaining many [`method`] filters by `or` compares the method name once per
+/// branch. `RpcRouter` instead looks the handler up in a `HashMap`, which
+/// matters when hundreds of methods are registered.
+///
+/// Requests whose method is not registered are rejected, so the filter can be
+/// composed with other filters by `or`.
+///
+/// [`method`]: ./filters/fn.method.html
+///
+/// ```
+/// # use warp_json_rpc::{Error, RpcRouter};
+/// # use warp::Filter as _;
+///
+/// let mut router = RpcRouter::new();
+/// router
+///     .method("add", |(lhs, rhs): (i64, i64)| async move { Ok::<_, Error>(lhs + rhs) })
+///     .method("greet", |(name,): (String,)| async move {
+///         Ok::<_, Error>(format!("Hello, {}", name))
+///     });
+/// let route = warp::path("rpc").and(router.into_filter());
+/// ```
+///
+/// # Several listeners
+///
+/// Clones of a router share the handlers of its methods, but each clone is
+/// configured on its own. Serving clones on different listeners gives each
+/// listener its own policies, such as stricter limits on a public port and
+/// extra methods on a local one.
+///
+/// ```no_run
+/// # use warp_json_rpc::{Error, RpcRouter};
+/// # use warp::Filter as _;
+/// # use futures::future;
+/// # use std::{convert::Infallible, time::Duration};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut public = RpcRouter::new();
+/// public.method("add", |(lhs, rhs): (i64, i64)| async move { Ok::<_, Error>(lhs + rhs) });
+///
+/// let mut local = public.clone();
+/// local.method("flush", |(): ()| async move { Ok::<_, Error>(()) });
+/// public.byte_quota(1 << 20, Duration::from_secs(60), |ctx| {
+///     let key = ctx.headers().get("X-Api-Key")?;
+///     Some(key.to_str().ok()?.to_string())
+/// });
+///
+/// let serve = |router: RpcRouter, addr: ([u8; 4], u16)| {
+///     let svc = warp_json_rpc::service(warp::path("rpc").and(router.into_filter()));
+///     let make_svc = hyper::service::make_service_fn(move |_| {
+///         future::ok::<_, Infallible>(svc.clone())
+///     });
+///     hyper::Server::bind(&addr.into()).serve(make_svc)
+/// };
+/// let (public, local) = future::join(
+///     serve(public, ([0, 0, 0, 0], 8080)),
+///     serve(local, ([127, 0, 0, 1], 8081)),
+/// )
+/// .await;
+/// public.unwrap();
+/// local.unwrap();
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct RpcRouter {
+    pub(crate) methods: Methods<Method>,
+    journal: Option<Arc<dyn Journal>>,
+    pub(crate) idempotency: Option<Arc<IdempotencyCache>>,
+    coalescer: Arc<Coalescer>,
+    pub(crate) auditor: Option<Auditor>,
+    pub(crate) errors: ErrorCatalog,
+    pub(crate) localizer: Option<Localizer>,
+    pub(crate) meta: Option<(MetaPlacement, Option<Arc<String>>)>,
+    pub(crate) quota: Option<Arc<ByteQuota>>,
+    pub(crate) continuations: Option<Arc<Continuations>>,
+    default_timeout: Option<Duration>,
+    pub(crate) config: Option<Arc<LiveConfig>>,
+    pub(crate) flags: Option<FlagResolver>,
+    pub(crate) list_methods: bool,
+    pub(crate) shadow_diff: Arc<ShadowDiff>,
+    pub(crate) classes: HashMap<String, Arc<Semaphore>>,
+    pub(crate) report_usage: bool,
+    pub(crate) replaced: Vec<Duplicate>,
+    pub(crate) duplicates: Duplicates,
+    pub(crate) discover: Option<(String, String)>,
+    pub(crate) access_log: Option<LogFormat>,
+    pub(crate) sampling: Option<LogSampling>,
+    pub(crate) reporter: Option<Arc<dyn ErrorReporter>>,
+    pub(crate) digest_key: DigestKey,
+    pub(crate) signer: Option<Arc<dyn Signer>>,
+    pub(crate) exposed: Option<Vec<Visibility>>,
+    pub(crate) retry_hook: Option<RetryHook>,
+    pub(crate) transactions: Option<Arc<Transactions>>,
+}
+
+impl RpcRouter {
+    pub fn new() -> RpcRouter {
+        RpcRouter::default()
+    }
+
+    /// Register a handler for the RPC method.
+    ///
+    /// The RPC parameter is deserialized into `P`. If it fails, the request is
+    /// answered by `INVALID_PARAMS` error, whose `data` lists a [`Violation`]
+    /// pointing at the offending value, without calling the handler.
+    ///
+    /// [`Violation`]: ./struct.Violation.html
+    #[track_caller]
+    pub fn method<P, R, F, Fut>(&mut self, name: impl Into<String>, handler: F) -> &mut RpcRouter
+    where
+        for<'de> P: Deserialize<'de> + Send + 'static,
+        R: Serialize,
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.register(
+            name,
+            Arc::new(move |params: Params, _ctx: Context| {
+                let handler = handler.clone();
+                async move {
+                    let result = handler(params.parse::<P>()?).await?;
+                    serde_json::to_value(result).map_err(|e| {
+                        log::error!(target: "warp_json_rpc", "Failed to serialize result: {}", e);
+                        Error::INTERNAL_ERROR
+                    })
+                }
+                .boxed()
+            }),
+        )
+    }
+
+    /// Register a type erased handler for the RPC method.
+    ///
+    /// A handler already registered for the method is replaced, unless
+    /// [`on_duplicate`] says otherwise.
+    ///
+    /// [`on_duplicate`]: #method.on_duplicate
+    #[track_caller]
+    pub fn register(&mut self, name: impl Into<String>, handler: BoxedHandler) -> &mut RpcRouter {
+        let method = Method {
+            handler,
+            params_schema: None,
+            result_schema: None,
+            journaled: false,
+            coalesced: false,
+            stream: None,
+            multipart: None,
+            flag: None,
+            shadow: None,
+            class: None,
+            breaker: None,
+            location: Location::caller(),
+            doc: None,
+            sensitive: Sensitive::default(),
+            transform: None,
+            visibility: Visibility::default(),
+            rules: Vec::new(),
+            shards: None,
+        };
+        let name = name.into();
+        let again = method.location;
+        if let Some(first) = self.methods.insert(name.clone(), method) {
+            self.duplicated(Duplicate {
+                name,
+                first: first.location,
+                again,
+            });
+        }
+        self
+    }
+
+    /// Validate the RPC parameter of the method against `schema` before
+    /// calling the handler.
+    ///
+    /// Invalid parameters are answered by `INVALID_PARAMS` error whose `data`
+    /// lists the [`Violation`]s. Absent parameters are validated as `null`.
+    ///
+    /// [`Violation`]: ./struct.Violation.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn params_schema(&mut self, name: &str, schema: Schema) -> &mut RpcRouter {
+        self.registered(name).params_schema = Some(schema);
+        self
+    }
+
+    /// Validate the result of the method against `schema`.
+    ///
+    /// Results are validated only in debug builds. An invalid result is
+    /// replaced by `INTERNAL_ERROR` error whose `data` lists the
+    /// [`Violation`]s.
+    ///
+    /// [`Violation`]: ./struct.Violation.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn result_schema(&mut self, name: &str, schema: Schema) -> &mut RpcRouter {
+        self.registered(name).result_schema = Some(schema);
+        self
+    }
+
+    /// Give calls without the `Request-Timeout` header a deadline of
+    /// `timeout` after they are received.
+    ///
+    /// Handlers bound their work by the deadline through
+    /// [`Context::timeout`].
+    ///
+    /// [`Context::timeout`]: ./struct.Context.html#method.timeout
+    pub fn default_timeout(&mut self, timeout: Duration) -> &mut RpcRouter {
+        self.default_timeout = Some(timeout);
+        self
+    }
+
+    /// Use `journal` to record calls of journaled methods.
+    pub fn journal<J>(&mut self, journal: J) -> &mut RpcRouter
+    where
+        J: Journal + 'static,
+    {
+        self.journal = Some(Arc::new(journal));
+        self
+    }
+
+    /// Record calls of the (mutating) method in the [`Journal`] before
+    /// calling the handler.
+    ///
+    /// If the call cannot be appended to the journal, the request is answered
+    /// by `INTERNAL_ERROR` error without calling the handler.
+    ///
+    /// [`Journal`]: ./trait.Journal.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn journaled(&mut self, name: &str) -> &mut RpcRouter {
+        self.registered(name).journaled = true;
+        self
+    }
+
+    /// Call the handlers again for every incomplete entry in the journal,
+    /// and return how many entries are re-driven.
+    ///
+    /// Call this at startup, before serving requests. Entries of methods which
+    /// are no longer registered are left in the journal.
+    pub async fn recover(&self) -> anyhow::Result<usize> {
+        let journal = match self.journal.as_ref() {
+            Some(journal) => journal,
+            None => return Ok(0),
+        };
+
+        let mut recovered = 0;
+        for (seq, entry) in journal.incomplete().await? {
+            let method = match self.methods.get(entry.method.as_str()) {
+                Some(method) => method,
+                None => {
+                    log::warn!(target: "warp_json_rpc", "Cannot recover unknown \"{}\" RPC", entry.method);
+                    continue;
+                }
+            };
+            let params = match entry.params {
+                Some(params) => Some(RawValue::from_string(params)?),
+                None => None,
+            };
+            let name = entry.method;
+            let ctx = Context::from_parts(entry.request_id, &name, HeaderMap::new());
+            if let Err(e) = method.call(Params::from_raw(params), ctx).await {
+                log::warn!(target: "warp_json_rpc", "Recovered \"{}\" RPC failed: {}", name, e.message);
+            }
+            journal.complete(seq).await?;
+            recovered += 1;
+        }
+        Ok(recovered)
+    }
+
+    pub(crate) async fn dispatch(
+        self: &Arc<Self>,
+        res: Builder,
+        mut req: Request,
+        headers: HeaderMap,
+        remote: Option<SocketAddr>,
+    ) -> Result<Response, Rejection> {
+        let method = match self.methods.resolve(req.name_mut()) {
+            Some(method) => method,
+            None => return Err(reject::reject()),
+        };
+        if self.sampling.is_none() {
+            log::info!(target: "warp_json_rpc", "\"{}\" RPC", req.method());
+        }
+        let started = Instant::now();
+        let ctx = self.context(&req, headers, remote);
+
+        let config = self.config.as_ref().map(|config| config.get());
+        if let Some(config) = config.as_ref() {
+            if let Err(e) = config.admit(req.method(), ctx.headers()) {
+                log::info!(target: "warp_json_rpc", "\"{}\" RPC is not admitted", req.method());
+                return Ok(reply(res, Err(e)));
+            }
+        }
+        if !self.enabled(method, &ctx) {
+            log::info!(target: "warp_json_rpc", "\"{}\" RPC is disabled by its feature flag", req.method());
+            return Ok(reply(res, Err(Error::METHOD_NOT_FOUND)));
+        }
+
+        let languages = match self.localizer.as_ref() {
+            Some(_) => locale::languages(ctx.headers()),
+            None => Vec::new(),
+        };
+        let localize = |result| match self.localizer.as_ref() {
+            Some(localizer) => localizer.localize(&languages, result),
+            None => result,
+        };
+
+        let replay = |found| match found {
+            Found::Kept(result, attachments) => {
+                log::debug!(target: "warp_json_rpc", "Replay \"{}\" RPC for idempotency key", req.method());
+                match localize(method.transform(result, &ctx)) {
+                    Ok(result) if method.multipart.is_some() => {
+                        multipart::reply(res.clone(), result, attachments)
+                    }
+                    result => reply(res.clone(), result),
+                }
+            }
+            Found::InFlight => {
+                log::info!(target: "warp_json_rpc", "\"{}\" RPC with idempotency key is in flight", req.method());
+                reply(res.clone(), localize(Err(Found::in_flight())))
+            }
+        };
+        let idempotency = self.idempotency.as_ref().and_then(|cache| {
+            let key = ctx.headers().get(IDEMPOTENCY_KEY)?.to_str().ok()?;
+            Some(cache.slot(req.method(), key, req.params().raw(), &ctx))
+        });
+        if let Some(slot) = idempotency.as_ref() {
+            if let Some(found) = slot.get().await {
+                return Ok(replay(found));
+            }
+        }
+        // A call with an idempotency key is answered by a single response,
+        // whose outcome can be kept.
+        let streamed = method
+            .stream
+            .clone()
+            .filter(|_| idempotency.is_none() && ndjson::accepts(ctx.headers()));
+
+        let params = req.params();
+        let caller = self
+            .auditor
+            .as_ref()
+            .and_then(|auditor| auditor.caller(&ctx));
+        let params_bytes = params.raw().map(str::len).unwrap_or(0);
+        let (params_recorded, params_digest) = self
+            .auditor
+            .as_ref()
+            .map(|auditor| auditor.params(params.raw(), &method.sensitive, &self.digest_key))
+            .unwrap_or_default();
+        let client = self.quota.as_ref().and_then(|quota| quota.client(&ctx));
+        if let (Some(quota), Some(client)) = (self.quota.as_ref(), client.as_ref()) {
+            let limit = config.as_ref().and_then(|config| config.byte_quota);
+            if let Err(exceeded) = quota.check(client, limit).await {
+                log::info!(target: "warp_json_rpc", "Byte quota of \"{}\" is exceeded", client);
+                let mut response = reply(res, localize(Err(exceeded.clone().into())));
+                exceeded.insert_headers(response.headers_mut());
+                return Ok(response);
+            }
+        }
+        if let Some(Err(unavailable)) = method.breaker.as_ref().map(|breaker| breaker.check()) {
+            log::info!(target: "warp_json_rpc", "Circuit breaker of \"{}\" RPC is open", req.method());
+            let mut response = reply(res, localize(Err(unavailable.clone().into())));
+            unavailable.insert_headers(response.headers_mut());
+            return Ok(response);
+        }
+        let transaction = match self.transactions.as_ref() {
+            Some(transactions) => match transactions.joined(req.method(), &ctx) {
+                Ok(id) => id,
+                Err(e) => return Ok(reply(res, localize(Err(e)))),
+            },
+            None => None,
+        };
+        let reservation = match idempotency {
+            Some(slot) => match slot.reserve().await {
+                Ok(reservation) => reservation,
+                Err(found) => return Ok(replay(found)),
+            },
+            None => None,
+        };
+        let journal_seq = match self.journal.as_ref().filter(|_| method.journaled) {
+            Some(journal) => {
+                let entry = JournalEntry {
+                    request_id: req.id(),
+                    method: req.method().to_string(),
+                    params: params.raw().map(str::to_string),
+                };
+                match journal.append(entry).await {
+                    Ok(seq) => Some(seq),
+                    Err(e) => {
+                        log::error!(target: "warp_json_rpc", "Failed to append to journal: {}", e);
+                        return Ok(reply(res, Err(Error::INTERNAL_ERROR)));
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let settle = Settle {
+            router: self.clone(),
+            method: req.method().to_string(),
+            request_id: req.id(),
+            started,
+            languages,
+            caller,
+            params_bytes,
+            params_recorded,
+            params_digest,
+            client,
+            journal_seq,
+            transaction,
+            reported: self.reporter.as_ref().map(|_| {
+                (
+                    report::params_digest(&self.digest_key, params.raw()),
+                    ctx.clone(),
+                )
+            }),
+        };
+        let permit = priority::admit(method.class.as_ref()).await;
+
+        if let Some(stream) = streamed {
+            let items = match method.validate(&params) {
+                Ok(()) => stream(params, ctx.clone()),
+                Err(e) => futures::stream::once(futures::future::ready(Err(e))).boxed(),
+            };
+            let (sensitive, transform) = (method.sensitive.clone(), method.transform.clone());
+            let items = items
+                .map(move |item| {
+                    let item = item.map_err(|e| sensitive.redact_error(e));
+                    match transform.as_ref() {
+                        Some(transform) => item.and_then(|value| transform(value, &ctx)),
+                        None => item,
+                    }
+                })
+                .boxed();
+            return Ok(ndjson::reply(res, items, settle, permit));
+        }
+
+        let shadowed = method.shadow.clone().map(|shadow| {
+            (
+                shadow,
+                params.clone(),
+                ctx.clone(),
+                method.sensitive.clone(),
+            )
+        });
+        let transformed = method.transform.as_ref().map(|_| ctx.clone());
+        let mut attachments = None;
+        let call = async {
+            if method.multipart.is_some() {
+                let (result, parts) = method.call_multipart(params, ctx).await?;
+                attachments = Some(parts);
+                Ok(result)
+            } else if method.coalesced {
+                let raw = params.raw().unwrap_or("").to_string();
+                let method = method.clone();
+                self.coalescer
+                    .run(req.method(), &raw, move || {
+                        async move { method.call(params, ctx).await }.boxed()
+                    })
+                    .await
+            } else {
+                method.call(params, ctx).await
+            }
+        };
+        let result = if self.reporter.is_some() {
+            match AssertUnwindSafe(call).catch_unwind().await {
+                Ok(result) => settle.settle(result, None).await,
+                Err(panic) => {
+                    let panic = report::panic_message(panic.as_ref()).to_string();
+                    settle
+                        .settle(Err(Error::INTERNAL_ERROR), Some(&panic))
+                        .await
+                }
+            }
+        } else {
+            settle.settle(call.await, None).await
+        };
+        drop(permit);
+        if let Some((shadow, params, ctx, sensitive)) = shadowed {
+            let compare = self.shadow_diff.clone();
+            let primary = Outcome::from_result(&result);
+            shadow::spawn(shadow, params, ctx, primary, compare, sensitive);
+        }
+        if let Some(reservation) = reservation {
+            let kept = attachments.as_deref().unwrap_or_default();
+            reservation.complete(&result, kept).await;
+        }
+
+        let error_code = result.as_ref().err().map(|e| e.code);
+        let result = match transformed.as_ref() {
+            Some(ctx) => method.transform(result, ctx),
+            None => result,
+        };
+        let result = settle.localize(result);
+        let meta = self.meta.as_ref().map(|(placement, node_id)| {
+            (
+                *placement,
+                ExecutionMeta::new(started.elapsed(), node_id.clone()),
+            )
+        });
+        let mut response = match (attachments, result) {
+            (Some(attachments), Ok(result)) => multipart::reply(res, result, attachments),
+            (_, result) => match meta.as_ref() {
+                Some((MetaPlacement::Field, meta)) => reply(res.with_meta(meta.clone()), result),
+                _ => reply(res, result),
+            },
+        };
+        if let Some((MetaPlacement::Headers, meta)) = meta.as_ref() {
+            meta::insert_headers(response.headers_mut(), meta);
+        }
+
+        let response_bytes = response
+            .headers()
+            .get("Content-Length")
+            .and_then(|len| len.to_str().ok()?.parse().ok())
+            .unwrap_or(0);
+        settle.account(error_code, response_bytes).await;
+        Ok(response)
+    }
+
+    fn context(&self, req: &Request, headers: HeaderMap, remote: Option<SocketAddr>) -> Context {
+        Context::new(req, headers)
+            .with_remote_addr(remote)
+            .or_timeout(self.default_timeout)
+    }
+
+    pub(crate) fn registered(&mut self, name: &str) -> &mut Method {
+        self.methods
+            .get_mut(name)
+            .unwrap_or_else(|| panic!("RPC method \"{}\" is not registered", name))
+    }
+
+    /// Register methods whose handlers receive a clone of `state`.
+    ///
+    /// ```
+    /// # use warp_json_rpc::{Error, RpcRouter};
+    /// use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    ///
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let mut router = RpcRouter::new();
+    /// router
+    ///     .with_state(counter)
+    ///     .method("incr", |counter: Arc<AtomicUsize>, (): ()| async move {
+    ///         Ok::<_, Error>(counter.fetch_add(1, Ordering::SeqCst) + 1)
+    ///     })
+    ///     .method("get", |counter: Arc<AtomicUsize>, (): ()| async move {
+    ///         Ok::<_, Error>(counter.load(Ordering::SeqCst))
+    ///     });
+    /// ```
+    pub fn with_state<S>(&mut self, state: S) -> WithState<'_, S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        WithState {
+            router: self,
+            state,
+        }
+    }
+
+    /// Whether a handler is registered for the RPC method.
+    pub fn contains(&self, name: &str) -> bool {
+        self.methods.contains_key(name)
+    }
+
+    /// Drop the methods which are not exposed, and register the built-in
+    /// methods enabled on the router, once it is complete.
+    pub(crate) fn register_builtins(&mut self) {
+        self.hide_unexposed();
+        self.register_rpc_errors();
+        self.register_rpc_usage();
+        self.register_rpc_discover();
+        self.register_rpc_methods();
+    }
+
+    /// Create a `Filter` that dispatches the request to the registered handler.
+    ///
+    /// This filter includes [`json_rpc`] filter, so you don't need to call it.
+    ///
+    /// [`json_rpc`]: ./filters/fn.json_rpc.html
+    pub fn into_filter(mut self) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+        self.register_builtins();
+        let router = Arc::new(self);
+        filters::json_rpc()
+            .and(store::stored_req())
+            .and(warp::header::headers_cloned())
+            .and(warp::addr::remote())
+            .and(warp::path::full())
+            .and_then(
+                move |res: Builder, req: Request, headers: HeaderMap, remote, path: FullPath| {
+                    let router = router.clone();
+                    async move {
+                        let entry = router.access_log.map(|format| {
+                            access::Entry::new(format, &req, &headers, remote, path.as_str())
+                        });
+                        let mut response = router.dispatch(res, req, headers, remote).await;
+                        if let Some(signer) = router.signer.as_ref() {
+                            if let Ok(unsigned) = response {
+                                response = Ok(signing::sign(signer.as_ref(), unsigned).await);
+                            }
+                        }
+                        if let (Some(entry), Ok(response)) = (entry, response.as_ref()) {
+                            entry.log(response);
+                        }
+                        response
+                    }
+                },
+            )
+    }
+}
+
+/// What is left to do for a call once its result is known.
+///
+/// Shared by ordinary and streamed answers, so both are accounted the same.
+pub(crate) struct Settle {
+    router: Arc<RpcRouter>,
+    method: String,
+    request_id: Id,
+    started: Instant,
+    languages: Vec<String>,
+    caller: Option<String>,
+    params_bytes: usize,
+    params_recorded: Option<String>,
+    params_digest: Option<String>,
+    client: Option<String>,
+    journal_seq: Option<u64>,
+    transaction: Option<String>,
+    reported: Option<(Option<String>, Context)>,
+}
+
+impl Settle {
+    /// Record the result (or the panic answered by it) with the breaker,
+    /// error reporter, transaction and journal, and check its error against
+    /// the catalog.
+    pub(crate) async fn settle(
+        &self,
+        result: Result<Value, Error>,
+        panic: Option<&str>,
+    ) -> Result<Value, Error> {
+        let router = self.router.as_ref();
+        if let (Some(reporter), Some((params_digest, context))) =
+            (router.reporter.as_ref(), self.reported.as_ref())
+        {
+            let failure = match (panic, result.as_ref()) {
+                (Some(panic), _) => Some(Failure::Panic(panic)),
+                (None, Err(e)) => Some(Failure::Error(e)),
+                (None, Ok(_)) => None,
+            };
+            if let Some(failure) = failure {
+                reporter.report(&ErrorReport {
+                    method: &self.method,
+                    params_digest: params_digest.clone(),
+                    context,
+                    failure,
+                });
+            }
+        }
+        if let Some(breaker) = router
+            .methods
+            .get(&self.method)
+            .and_then(|m| m.breaker.as_ref())
+        {
+            breaker.record(&result);
+        }
+        if let (Some(transactions), Some(id)) =
+            (router.transactions.as_ref(), self.transaction.as_ref())
+        {
+            transactions.record(id, &self.method, &result);
+        }
+        let result = router.errors.check(&self.method, result);
+
+        if let (Some(journal), Some(seq)) = (router.journal.as_ref(), self.journal_seq) {
+            if let Err(e) = journal.complete(seq).await {
+                log::error!(target: "warp_json_rpc", "Failed to complete journal entry: {}", e);
+            }
+        }
+        if let Some(sampling) = router.sampling.as_ref() {
+            let error_code = result.as_ref().err().map(|e| e.code);
+            if sampling.sample(&self.method, error_code.is_some()) {
+                match error_code {
+                    Some(code) => {
+                        log::info!(target: "warp_json_rpc", "\"{}\" RPC failed with {}", self.method, code)
+                    }
+                    None => {
+                        log::info!(target: "warp_json_rpc", "\"{}\" RPC succeeded", self.method)
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    pub(crate) fn localize(&self, result: Result<Value, Error>) -> Result<Value, Error> {
+        match self.router.localizer.as_ref() {
+            Some(localizer) => localizer.localize(&self.languages, result),
+            None => result,
+        }
+    }
+
+    /// Count the bytes sent against the quota, and audit the call.
+    pub(crate) async fn account(self, error_code: Option<i64>, response_bytes: u64) {
+        let router = self.router.as_ref();
+        if let (Some(quota), Some(client)) = (router.quota.as_ref(), self.client) {
+            quota.record(client, response_bytes).await;
+        }
+        if let Some(auditor) = router.auditor.as_ref() {
+            auditor.record(AuditRecord {
+                request_id: self.request_id,
+                method: self.method,
+                caller: self.caller,
+                error_code,
+                latency: self.started.elapsed(),
+                time: Timestamp::now(),
+                params_bytes: self.params_bytes,
+                params: self.params_recorded,
+                params_digest: self.params_digest,
+                response_bytes: response_bytes as usize,
+            });
+        }
+    }
+}
+
+/// Registers methods sharing a state to [`RpcRouter`].
+///
+/// Created by [`RpcRouter::with_state`].
+///
+/// [`RpcRouter`]: ./struct.RpcRouter.html
+/// [`RpcRouter::with_state`]: ./struct.RpcRouter.html#method.with_state
+pub struct WithState<'a, S> {
+    router: &'a mut RpcRouter,
+    state: S,
+}
+
+impl<'a, S> WithState<'a, S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    /// Register a handler for the RPC method, which receives the state as the
+    /// first argument.
+    ///
+    /// See [`RpcRouter::method`] for details.
+    ///
+    /// [`RpcRouter::method`]: ./struct.RpcRouter.html#method.method
+    #[track_caller]
+    pub fn method<P, R, F, Fut>(&mut self, name: impl Into<String>, handler: F) -> &mut Self
+    where
+        for<'de> P: Deserialize<'de> + Send + 'static,
+        R: Serialize,
+        F: Fn(S, P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + 'static,
+    {
+        let state = self.state.clone();
+        self.router
+            .method(name, move |params: P| handler(state.clone(), params));
+        self
+    }
+}
+
+impl Method {
+    /// Validate the parameter against the schema of the method, if any.
+    pub(crate) fn validate(&self, params: &Params) -> Result<(), Error> {
+        if let Some(schema) = self.params_schema.as_ref() {
+            let params = params.parse::<Value>().unwrap_or(Value::Null);
+            schema
+                .validate(&params)
+                .map_err(|violations| Error::INVALID_PARAMS.with_data(violations))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn call(&self, params: Params, ctx: Context) -> Result<Value, Error> {
+        self.validate(&params)?;
+
+        let _turn = ctx.timeout(self.turn(&params)).await?;
+        let handler = self.route(&params).unwrap_or(&self.handler);
+        let result = handler(params, ctx)
+            .await
+            .map_err(|e| self.sensitive.redact_error(e))?;
+
+        #[cfg(debug_assertions)]
+        {
+            if let Some(schema) = self.result_schema.as_ref() {
+                if let Err(violations) = schema.validate(&result) {
+                    log::error!(target: "warp_json_rpc", "Result violates its schema: {:?}", violations);
+                    return Err(Error::INTERNAL_ERROR.with_data(violations));
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+fn reply(res: Builder, result: Result<Value, Error>) -> Response {
+    res.clone().result(result).unwrap_or_else(|e| {
+        log::error!(target: "warp_json_rpc", "Failed to serialize response: {}", e);
+        res.error(Error::INTERNAL_ERROR)
+            .expect("INTERNAL_ERROR is always serializable")
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{store::LazyReqStore, Id, Retryability};
+
+    fn router() -> RpcRouter {
+        let mut router = RpcRouter::new();
+        router
+            .method("add", |(lhs, rhs): (i64, i64)| async move {
+                Ok::<_, Error>(lhs + rhs)
+            })
+            .method("fail", |_: Value| async move {
+                Err::<(), _>(Error::custom(1, "failed"))
+            });
+        router
+    }
+
+    async fn call(router: RpcRouter, body: &str) -> Option<Value> {
+        let res = warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .extension(LazyReqStore::empty())
+            .body(body)
+            .filter(&router.into_filter())
+            .await
+            .ok()?;
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        Some(serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn dispatch() {
+        let res = call(
+            router(),
+            r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["result"], 3);
+    }
+
+    #[tokio::test]
+    async fn dispatch_error() {
+        let res = call(
+            router(),
+            r#"{"jsonrpc": "2.0", "method": "fail", "params": [], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["error"]["code"], 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_params() {
+        let res = call(
+            router(),
+            r#"{"jsonrpc": "2.0", "method": "add", "params": ["1"], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn params_schema() {
+        let mut router = router();
+        router.params_schema(
+            "add",
+            Schema::new(serde_json::json!({
+                "type": "array",
+                "items": { "type": "integer", "maximum": 10 },
+            })),
+        );
+
+        let res = call(
+            router,
+            r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 20], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["error"]["code"], -32602);
+        assert_eq!(res["error"]["data"][0]["pointer"], "/1");
+    }
+
+    #[tokio::test]
+    async fn result_schema() {
+        let mut router = router();
+        router.result_schema("add", Schema::new(serde_json::json!({ "type": "string" })));
+
+        let res = call(
+            router,
+            r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["error"]["code"], -32603);
+    }
+
+    #[tokio::test]
+    async fn with_state() {
+        let mut router = router();
+        router
+            .with_state(10_i64)
+            .method("add_state", |state: i64, (n,): (i64,)| async move {
+                Ok::<_, Error>(state + n)
+            });
+
+        let res = call(
+            router,
+            r#"{"jsonrpc": "2.0", "method": "add_state", "params": [1], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["result"], 11);
+    }
+
+    #[tokio::test]
+    async fn register_boxed_handler() {
+        let mut router = router();
+        router.register(
+            "whoami",
+            Arc::new(|_: Params, ctx: Context| {
+                async move { Ok(Value::from(ctx.method())) }.boxed()
+            }),
+        );
+
+        let res = call(router, r#"{"jsonrpc": "2.0", "method": "whoami", "id": 1}"#)
+            .await
+            .unwrap();
+        assert_eq!(res["result"], "whoami");
+    }
+
+    #[tokio::test]
+    async fn recover_journaled_calls() {
+        use crate::MemoryJournal;
+        use std::sync::atomic::{AtomicI64, Ordering};
+
+        let total = Arc::new(AtomicI64::new(0));
+        let journal = Arc::new(MemoryJournal::new());
+        journal
+            .append(JournalEntry {
+                request_id: Id::Number(1),
+                method: "deposit".to_string(),
+                params: Some("[5]".to_string()),
+            })
+            .await
+            .unwrap();
+
+        let mut router = RpcRouter::new();
+        router.with_state(total.clone()).method(
+            "deposit",
+            |total: Arc<AtomicI64>, (n,): (i64,)| async move {
+                Ok::<_, Error>(total.fetch_add(n, Ordering::SeqCst) + n)
+            },
+        );
+        router.journal(journal.clone()).journaled("deposit");
+
+        assert_eq!(router.recover().await.unwrap(), 1);
+        assert_eq!(total.load(Ordering::SeqCst), 5);
+        assert!(journal.incomplete().await.unwrap().is_empty());
+
+        let res = call(
+            router,
+            r#"{"jsonrpc": "2.0", "method": "deposit", "params": [2], "id": 2}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["result"], 7);
+        assert!(journal.incomplete().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn idempotency_key() {
+        use std::sync::atomic::{AtomicI64, Ordering};
+        use std::time::Duration;
+
+        let mut router = RpcRouter::new();
+        router.with_state(Arc::new(AtomicI64::new(0))).method(
+            "incr",
+            |n: Arc<AtomicI64>, (): ()| async move {
+                Ok::<_, Error>(n.fetch_add(1, Ordering::SeqCst) + 1)
+            },
+        );
+        router.idempotency(Duration::from_secs(60));
+        let filter = router.into_filter();
+
+        let call = |key: &'static str| {
+            let filter = filter.clone();
+            async move {
+                let res = warp::test::request()
+                    .method("POST")
+                    .header("Content-Type", "application/json")
+                    .header(IDEMPOTENCY_KEY, key)
+                    .extension(LazyReqStore::empty())
+                    .body(r#"{"jsonrpc": "2.0", "method": "incr", "id": 1}"#)
+                    .filter(&filter)
+                    .await
+                    .unwrap();
+                let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                serde_json::from_slice::<Value>(&body).unwrap()["result"].clone()
+            }
+        };
+
+        assert_eq!(call("a").await, 1);
+        assert_eq!(call("a").await, 1);
+        assert_eq!(call("b").await, 2);
+    }
+
+    #[tokio::test]
+    async fn idempotency_scope() {
+        use crate::IDEMPOTENCY_IN_FLIGHT_CODE;
+        use std::sync::atomic::{AtomicI64, Ordering};
+        use std::time::Duration;
+
+        let mut router = RpcRouter::new();
+        router.with_state(Arc::new(AtomicI64::new(0))).method(
+            "incr",
+            |n: Arc<AtomicI64>, (by,): (i64,)| async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok::<_, Error>(n.fetch_add(by, Ordering::SeqCst) + by)
+            },
+        );
+        router
+            .idempotency(Duration::from_secs(60))
+            .idempotency_caller(|ctx| {
+                Some(ctx.headers().get("X-Client")?.to_str().ok()?.to_string())
+            });
+        let filter = router.into_filter();
+
+        let call = |client: &'static str, by: i64| {
+            let filter = filter.clone();
+            async move {
+                let res = warp::test::request()
+                    .method("POST")
+                    .header("Content-Type", "application/json")
+                    .header(IDEMPOTENCY_KEY, "a")
+                    .header("X-Client", client)
+                    .extension(LazyReqStore::empty())
+                    .body(format!(
+                        r#"{{"jsonrpc": "2.0", "method": "incr", "params": [{}], "id": 1}}"#,
+                        by
+                    ))
+                    .filter(&filter)
+                    .await
+                    .ok()
+                    .unwrap();
+                let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                serde_json::from_slice::<Value>(&body).unwrap()
+            }
+        };
+
+        let (first, retry) = futures::join!(call("x", 1), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            call("x", 1).await
+        });
+        assert_eq!(first["result"], 1);
+        assert_eq!(retry["error"]["code"], IDEMPOTENCY_IN_FLIGHT_CODE);
+        assert_eq!(call("x", 1).await["result"], 1);
+        assert_eq!(call("y", 1).await["result"], 2);
+        assert_eq!(call("x", 10).await["result"], 12);
+    }
+
+    #[tokio::test]
+    async fn rpc_errors() {
+        let mut router = router();
+        router
+            .rpc_errors()
+            .error(2, "Second", "The second error")
+            .error(1, "First", "The first error")
+            .retryability(|code| match code {
+                2 => Some(Retryability::Transient),
+                _ => None,
+            });
+
+        let res = call(
+            router,
+            r#"{"jsonrpc": "2.0", "method": "rpc_errors", "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            res["result"],
+            serde_json::json!([
+                {
+                    "code": 1,
+                    "name": "First",
+                    "description": "The first error",
+                    "retryability": "permanent",
+                },
+                {
+                    "code": 2,
+                    "name": "Second",
+                    "description": "The second error",
+                    "retryability": "transient",
+                },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn route_by_params() {
+        let mut router = router();
+        router.route_when(
+            "add",
+            |params| params[0] == 0,
+            Arc::new(|_, _| futures::future::ready(Ok(Value::from("zero"))).boxed()),
+        );
+
+        let res = call(
+            router.clone(),
+            r#"{"jsonrpc": "2.0", "method": "add", "params": [0, 2], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["result"], "zero");
+        let res = call(
+            router,
+            r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["result"], 3);
+    }
+
+    #[tokio::test]
+    async fn execution_meta_field() {
+        let mut router = router();
+        router.execution_meta(MetaPlacement::Field, Some("node-1".to_string()));
+
+        let res = call(
+            router,
+            r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["result"], 3);
+        assert_eq!(res["meta"]["node_id"], "node-1");
+        assert!(res["meta"]["duration_ms"].is_f64());
+    }
+
+    #[tokio::test]
+    async fn execution_meta_headers() {
+        let mut router = router();
+        router.execution_meta(MetaPlacement::Headers, Some("node-1".to_string()));
+
+        let res = warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .extension(LazyReqStore::empty())
+            .body(r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#)
+            .filter(&router.into_filter())
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(res.headers()[meta::NODE_ID], "node-1");
+        assert!(res.headers().contains_key(meta::SERVER_TIME));
+        assert!(res.headers().contains_key(meta::DURATION));
+    }
+
+    #[tokio::test]
+    async fn streaming() {
+        let mut router = RpcRouter::new();
+        router.streaming("count", |(n,): (u64,)| {
+            futures::stream::iter((0..n).map(Ok::<_, Error>))
+        });
+        let filter = router.into_filter();
+        let req = r#"{"jsonrpc": "2.0", "method": "count", "params": [2], "id": 1}"#;
+
+        let res = warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("Accept", ndjson::NDJSON)
+            .extension(LazyReqStore::empty())
+            .body(req)
+            .filter(&filter)
+            .await
+            .ok()
+            .unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let lines = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["method"], ndjson::CHUNK_METHOD);
+        assert_eq!(
+            lines[1]["params"],
+            serde_json::json!({ "id": 1, "item": 1 })
+        );
+        assert_eq!(lines[2]["id"], 1);
+        assert_eq!(lines[2]["result"], Value::Null);
+
+        let res = warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .extension(LazyReqStore::empty())
+            .body(req)
+            .filter(&filter)
+            .await
+            .ok()
+            .unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let res = serde_json::from_slice::<Value>(&body).unwrap();
+        assert_eq!(res["result"], serde_json::json!([0, 1]));
+    }
+
+    #[tokio::test]
+    async fn streaming_counts_against_quota() {
+        let mut router = RpcRouter::new();
+        router
+            .streaming("count", |(n,): (u64,)| {
+                futures::stream::iter((0..n).map(Ok::<_, Error>))
+            })
+            .byte_quota(10, std::time::Duration::from_secs(60), |_| {
+                Some("a".to_string())
+            });
+        let filter = router.into_filter();
+        let call = || {
+            warp::test::request()
+                .method("POST")
+                .header("Content-Type", "application/json")
+                .header("Accept", ndjson::NDJSON)
+                .extension(LazyReqStore::empty())
+                .body(r#"{"jsonrpc": "2.0", "method": "count", "params": [2], "id": 1}"#)
+                .filter(&filter)
+        };
+
+        let res = call().await.ok().unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(std::str::from_utf8(&body).unwrap().lines().count(), 3);
+
+        let res = call().await.ok().unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let res = serde_json::from_slice::<Value>(&body).unwrap();
+        assert_eq!(res["error"]["code"], crate::QUOTA_EXCEEDED_CODE);
+    }
+
+    #[tokio::test]
+    async fn method_id_in_context() {
+        let mut router = RpcRouter::new();
+        router.register(
+            "whoami",
+            Arc::new(|_: Params, ctx: Context| {
+                let id = ctx.method_id().map(|id| format!("{:?}", id));
+                async move { Ok(serde_json::json!(id)) }.boxed()
+            }),
+        );
+        let id = router.method_id("whoami").unwrap();
+        assert_eq!(router.method_id("unknown"), None);
+
+        let res = call(router, r#"{"jsonrpc": "2.0", "method": "whoami", "id": 1}"#)
+            .await
+            .unwrap();
+        assert_eq!(res["result"], format!("{:?}", id));
+    }
+
+    #[tokio::test]
+    async fn remote_addr_in_context() {
+        let mut router = RpcRouter::new();
+        router.register(
+            "whereami",
+            Arc::new(|_: Params, ctx: Context| {
+                let addr = ctx.remote_addr().map(|addr| addr.to_string());
+                async move { Ok(serde_json::json!(addr)) }.boxed()
+            }),
+        );
+
+        let res = warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .remote_addr(([127, 0, 0, 1], 8080).into())
+            .extension(LazyReqStore::empty())
+            .body(r#"{"jsonrpc": "2.0", "method": "whereami", "id": 1}"#)
+            .filter(&router.into_filter())
+            .await
+            .ok()
+            .unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let res = serde_json::from_slice::<Value>(&body).unwrap();
+        assert_eq!(res["result"], "127.0.0.1:8080");
+    }
+
+    #[tokio::test]
+    async fn feature_flags() {
+        let mut router = router();
+        router
+            .gated("fail", "beta")
+            .rpc_methods()
+            .feature_flags(|flag, ctx| flag == "beta" && ctx.headers().contains_key("X-Beta"));
+        let filter = router.into_filter();
+
+        let call = |method: &str, beta: bool| {
+            let mut req = warp::test::request()
+                .method("POST")
+                .header("Content-Type", "application/json")
+                .extension(LazyReqStore::empty())
+                .body(format!(
+                    r#"{{"jsonrpc": "2.0", "method": "{}", "id": 1}}"#,
+                    method
+                ));
+            if beta {
+                req = req.header("X-Beta", "1");
+            }
+            let filter = filter.clone();
+            async move {
+                let res = req.filter(&filter).await.ok().unwrap();
+                let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                serde_json::from_slice::<Value>(&body).unwrap()
+            }
+        };
+
+        assert_eq!(call("fail", false).await["error"]["code"], -32601);
+        assert_eq!(call("fail", true).await["error"]["code"], 1);
+        assert_eq!(
+            call("rpc_methods", false).await["result"],
+            serde_json::json!(["add", "rpc_methods"])
+        );
+        assert_eq!(
+            call("rpc_methods", true).await["result"],
+            serde_json::json!(["add", "fail", "rpc_methods"])
+        );
+    }
+
+    #[tokio::test]
+    async fn shadow_is_called() {
+        use std::sync::atomic::{AtomicI64, Ordering};
+
+        let shadowed = Arc::new(AtomicI64::new(0));
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut router = router();
+        router.shadow_diff(Vec::new(), {
+            let reported = reported.clone();
+            move |_: &str, differences: &[crate::diff::Difference]| {
+                reported.lock().unwrap().extend_from_slice(differences);
+            }
+        });
+        router.shadow("add", {
+            let shadowed = shadowed.clone();
+            Arc::new(move |params: Params, _| {
+                let shadowed = shadowed.clone();
+                async move {
+                    let (lhs, rhs) = params.parse::<(i64, i64)>()?;
+                    shadowed.store(lhs * rhs, Ordering::SeqCst);
+                    Ok(Value::from(lhs * rhs))
+                }
+                .boxed()
+            })
+        });
+
+        let res = call(
+            router,
+            r#"{"jsonrpc": "2.0", "method": "add", "params": [2, 3], "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["result"], 5);
+        for _ in 0..100 {
+            if !reported.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        assert_eq!(shadowed.load(Ordering::SeqCst), 6);
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported[0].pointer, "/result");
+        assert_eq!(reported[0].shadow, Some(Value::from(6)));
+    }
+
+    #[tokio::test]
+    async fn priority_class_limits_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        let mut router = router();
+        router
+            .method("export", {
+                let (running, max) = (running.clone(), max.clone());
+                move |(): ()| {
+                    let (running, max) = (running.clone(), max.clone());
+                    async move {
+                        max.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        Ok::<_, Error>(())
+                    }
+                }
+            })
+            .priority_class("bulk", 1)
+            .priority("export", "bulk");
+
+        let filter = router.into_filter();
+        let calls = (0..3).map(|_| {
+            warp::test::request()
+                .method("POST")
+                .header("Content-Type", "application/json")
+                .extension(LazyReqStore::empty())
+                .body(r#"{"jsonrpc": "2.0", "method": "export", "id": 1}"#)
+                .filter(&filter)
+        });
+        for res in futures::future::join_all(calls).await {
+            assert!(res.is_ok());
+        }
+        assert_eq!(max.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn error_reporter() {
+        use std::sync::Mutex;
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut router = router();
+        router
+            .method("boom", |(): ()| async move {
+                if true {
+                    panic!("boom");
+                }
+                Ok::<_, Error>(())
+            })
+            .method("fail", |(): ()| async move {
+                Err::<(), _>(Error::custom(1, "failed"))
+            })
+            .error_reporter({
+                let reports = reports.clone();
+                move |report: &ErrorReport<'_>| {
+                    let failure = match report.failure {
+                        Failure::Error(e) => e.code.to_string(),
+                        Failure::Panic(message) => message.to_string(),
+                    };
+                    let digest = report.params_digest.is_some();
+                    reports
+                        .lock()
+                        .unwrap()
+                        .push((report.method.to_string(), failure, digest));
+                }
+            });
+
+        let res = call(
+            router.clone(),
+            r#"{"jsonrpc": "2.0", "method": "boom", "id": 1}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res["error"]["code"], Error::INTERNAL_ERROR.code);
+        call(
+            router.clone(),
+            r#"{"jsonrpc": "2.0", "method": "fail", "id": 2}"#,
+        )
+        .await;
+        call(
+            router,
+            r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 3}"#,
+        )
+        .await;
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![
+                ("boom".to_string(), "boom".to_string(), false),
+                ("fail".to_string(), "1".to_string(), false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn transform_result() {
+        let mut router = router();
+        router.transform_result("add", |value, ctx: &Context| {
+            match ctx.headers().get("X-Recipient") {
+                Some(_) => Ok(Value::from(format!("sealed:{}", value))),
+                None => Err(Error::INVALID_REQUEST),
+            }
+        });
+        let filter = router.into_filter();
+        let res = warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-Recipient", "a")
+            .extension(LazyReqStore::empty())
+            .body(r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#)
+            .filter(&filter)
+            .await
+            .ok()
+            .unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let res = serde_json::from_slice::<Value>(&body).unwrap();
+        assert_eq!(res["result"], "sealed:3");
+
+        let res = warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .extension(LazyReqStore::empty())
+            .body(r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#)
+            .filter(&filter)
+            .await
+            .ok()
+            .unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let res = serde_json::from_slice::<Value>(&body).unwrap();
+        assert_eq!(res["error"]["code"], Error::INVALID_REQUEST.code);
+    }
+
+    #[tokio::test]
+    async fn expose_by_visibility() {
+        let mut router = router();
+        router
+            .method("dump", |(): ()| async move { Ok::<_, Error>(()) })
+            .visibility("dump", Visibility::Admin)
+            .rpc_methods();
+        let mut public = router.clone();
+        public.expose(&[Visibility::Public]);
+
+        let dump = r#"{"jsonrpc": "2.0", "method": "dump", "id": 1}"#;
+        let list = r#"{"jsonrpc": "2.0", "method": "rpc_methods", "id": 2}"#;
+        assert!(call(public.clone(), dump).await.is_none());
+        let methods = call(public, list).await.unwrap();
+        assert!(!methods["result"]
+            .as_array()
+            .unwrap()
+            .contains(&Value::from("dump")));
+        assert!(call(router, dump).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn rpc_usage() {
+        let mut router = router();
+        router
+            .rpc_usage()
+            .byte_quota(1000, std::time::Duration::from_secs(60), |ctx| {
+                Some(ctx.headers().get("X-Client")?.to_str().ok()?.to_string())
+            });
+        let filter = router.into_filter();
+        let call = |client: Option<&str>| {
+            let mut req = warp::test::request()
+                .method("POST")
+                .header("Content-Type", "application/json")
+                .extension(LazyReqStore::empty())
+                .body(r#"{"jsonrpc": "2.0", "method": "rpc_usage", "id": 1}"#);
+            if let Some(client) = client {
+                req = req.header("X-Client", client);
+            }
+            let filter = filter.clone();
+            async move {
+                let res = req.filter(&filter).await.ok().unwrap();
+                let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                serde_json::from_slice::<Value>(&body).unwrap()
+            }
+        };
+
+        assert_eq!(call(None).await["error"]["code"], crate::UNAUTHORIZED_CODE);
+        let first = call(Some("a")).await;
+        assert_eq!(first["result"]["byte_quota"]["used"], 0);
+        let second = call(Some("a")).await;
+        let usage = &second["result"]["byte_quota"];
+        assert_eq!(usage["limit"], 1000);
+        assert!(usage["used"].as_u64().unwrap() > 0);
+        assert_eq!(usage["window"], 60);
+    }
+
+    #[tokio::test]
+    async fn unknown_method_is_rejected() {
+        let res = call(router(), r#"{"jsonrpc": "2.0", "method": "sub", "id": 1}"#).await;
+        assert!(res.is_none());
+    }
+}
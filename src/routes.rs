@@ -0,0 +1,139 @@
+use crate::RpcRouter;
+use warp::{
+    filters::BoxedFilter,
+    reply::{Reply as _, Response},
+    Filter, Rejection,
+};
+
+/// Paths served by [`RpcRouter::into_routes`], as `/` separated segments
+/// such as `api/rpc`.
+///
+/// [`RpcRouter::into_routes`]: ./struct.RpcRouter.html#method.into_routes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Routes {
+    /// Where calls are posted.
+    pub rpc: String,
+    /// Where the HTML page of [`RpcRouter::docs_page`] is served, if any.
+    ///
+    /// [`RpcRouter::docs_page`]: ./struct.RpcRouter.html#method.docs_page
+    pub docs: Option<String>,
+    /// Where `GET` is answered by `200 OK` while the process is serving,
+    /// for liveness and readiness probes, if any.
+    pub health: Option<String>,
+}
+
+impl Default for Routes {
+    /// `rpc` for calls and `health` for probes, without docs.
+    fn default() -> Routes {
+        Routes {
+            rpc: "rpc".to_string(),
+            docs: None,
+            health: Some("health".to_string()),
+        }
+    }
+}
+
+impl RpcRouter {
+    /// Convert into a `Filter` serving calls, docs and health probes on the
+    /// paths of `routes`, so a single port serves them all.
+    ///
+    /// Requests of other paths are rejected. As the filter of
+    /// [`into_filter`], the filter must be served by [`service`].
+    ///
+    /// ```
+    /// # use warp_json_rpc::{Error, Routes, RpcRouter};
+    /// let mut router = RpcRouter::new();
+    /// router.method("add", |(lhs, rhs): (i64, i64)| async move { Ok::<_, Error>(lhs + rhs) });
+    /// let routes = router.into_routes(Routes {
+    ///     rpc: "api/rpc".to_string(),
+    ///     docs: Some("api/docs".to_string()),
+    ///     ..Routes::default()
+    /// });
+    /// let svc = warp_json_rpc::service(routes);
+    /// ```
+    ///
+    /// [`into_filter`]: #method.into_filter
+    /// [`service`]: ./fn.service.html
+    pub fn into_routes(
+        self,
+        routes: Routes,
+    ) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+        let docs = routes.docs.as_ref().map(|docs| {
+            path(docs)
+                .and(self.docs_page())
+                .map(|page: warp::reply::Html<String>| page.into_response())
+                .boxed()
+        });
+        let health = routes.health.as_ref().map(|health| {
+            path(health)
+                .and(warp::get())
+                .and(warp::path::end())
+                .map(|| warp::reply::json(&serde_json::json!({ "status": "ok" })).into_response())
+                .boxed()
+        });
+        let mut filter = path(&routes.rpc).and(self.into_filter()).boxed();
+        for other in docs.into_iter().chain(health) {
+            filter = filter.or(other).unify().boxed();
+        }
+        filter
+    }
+}
+
+/// Match the segments of `path` and leave the rest of the path.
+fn path(path: &str) -> BoxedFilter<()> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .fold(warp::any().boxed(), |filter, segment| {
+            filter.and(warp::path(segment.to_string())).boxed()
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{store::LazyReqStore, Error};
+
+    #[tokio::test]
+    async fn serve_on_paths() {
+        let mut router = RpcRouter::new();
+        router.method("add", |(lhs, rhs): (i64, i64)| async move {
+            Ok::<_, Error>(lhs + rhs)
+        });
+        let routes = router.into_routes(Routes {
+            rpc: "api/rpc".to_string(),
+            docs: Some("docs".to_string()),
+            ..Routes::default()
+        });
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/api/rpc")
+            .header("Content-Type", "application/json")
+            .extension(LazyReqStore::empty())
+            .body(r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#)
+            .filter(&routes)
+            .await
+            .ok()
+            .unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let res = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        assert_eq!(res["result"], 3);
+
+        for (path, status) in [("/health", 200), ("/docs", 200)] {
+            let res = warp::test::request()
+                .path(path)
+                .extension(LazyReqStore::empty())
+                .filter(&routes)
+                .await
+                .ok()
+                .unwrap();
+            assert_eq!(res.status(), status);
+        }
+        let res = warp::test::request()
+            .path("/rpc")
+            .extension(LazyReqStore::empty())
+            .filter(&routes)
+            .await;
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{router::Method, BoxedHandler, Params, RpcRouter};
+use serde_json::Value;
+use std::sync::Arc;
+
+pub(crate) type Predicate = Arc<dyn Fn(&Value) -> bool + Send + Sync>;
+
+impl RpcRouter {
+    /// Call `handler` instead of the registered handler for calls of the
+    /// method whose RPC parameter satisfies `predicate`.
+    ///
+    /// `predicate` receives the parameter as JSON (`null` if absent). Rules
+    /// are tried in the order they are added and the first match wins; calls
+    /// matching none go to the registered handler. `handler` may call an
+    /// upstream server, e.g. an archive node for old blocks:
+    ///
+    /// ```
+    /// # use warp_json_rpc::{BoxedHandler, Error, RpcRouter};
+    /// # use futures::future::FutureExt as _;
+    /// # use std::sync::Arc;
+    /// # let archive: BoxedHandler = Arc::new(|_, _| async { Ok(().into()) }.boxed());
+    /// let mut router = RpcRouter::new();
+    /// router
+    ///     .method("getBlock", |(height,): (u64,)| async move { Ok::<_, Error>(height) })
+    ///     .route_when(
+    ///         "getBlock",
+    ///         |params| matches!(params[0].as_u64(), Some(height) if height < 1_000_000),
+    ///         archive,
+    ///     );
+    /// ```
+    ///
+    /// Schemas, timeouts and the other settings of the method apply to every
+    /// rule.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn route_when<F>(
+        &mut self,
+        name: &str,
+        predicate: F,
+        handler: BoxedHandler,
+    ) -> &mut RpcRouter
+    where
+        F: Fn(&Value) -> bool + Send + Sync + 'static,
+    {
+        self.registered(name)
+            .rules
+            .push((Arc::new(predicate), handler));
+        self
+    }
+}
+
+impl Method {
+    /// The handler of the first rule matching `params`, if any.
+    pub(crate) fn route(&self, params: &Params) -> Option<&BoxedHandler> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        let params = params.parse::<Value>().unwrap_or(Value::Null);
+        self.rules
+            .iter()
+            .find(|(predicate, _)| predicate(&params))
+            .map(|(_, handler)| handler)
+    }
+}
@@ -0,0 +1,143 @@
+use futures::future::{BoxFuture, Future, FutureExt as _};
+use tokio::runtime::Handle;
+
+/// Compensations of the steps of a workflow done so far, undone unless the
+/// workflow completes.
+///
+/// A handler mutating several backends runs each mutation as a [`step`],
+/// giving the action which undoes it. If a later step fails and the handler
+/// returns early (by `?`, a panic or its call being dropped), the `Saga` is
+/// dropped and the compensations of the earlier steps run in the background,
+/// latest first. [`abort`] runs them before returning instead, and
+/// [`complete`] keeps the steps.
+///
+/// [`step`]: #method.step
+/// [`abort`]: #method.abort
+/// [`complete`]: #method.complete
+///
+/// ```
+/// # use warp_json_rpc::{Error, Saga};
+/// # async fn reserve(_: u64) -> Result<u64, Error> { Ok(1) }
+/// # async fn release(_: u64) {}
+/// # async fn charge(_: u64) -> Result<(), Error> { Ok(()) }
+/// async fn order(item: u64) -> Result<u64, Error> {
+///     let mut saga = Saga::new();
+///     let reservation = saga
+///         .step(reserve(item), |&reservation| release(reservation))
+///         .await?;
+///     // Releases the reservation if charging fails.
+///     charge(item).await?;
+///     saga.complete();
+///     Ok(reservation)
+/// }
+/// ```
+#[derive(Default)]
+pub struct Saga {
+    compensations: Vec<BoxFuture<'static, ()>>,
+}
+
+impl Saga {
+    pub fn new() -> Saga {
+        Saga::default()
+    }
+
+    /// Run `action`, and keep `compensate` of its output if it succeeds.
+    ///
+    /// Nothing is kept if `action` fails, since there is nothing to undo.
+    pub async fn step<T, E, A, C, Fut>(&mut self, action: A, compensate: C) -> Result<T, E>
+    where
+        A: Future<Output = Result<T, E>>,
+        C: FnOnce(&T) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let output = action.await?;
+        self.compensate(compensate(&output));
+        Ok(output)
+    }
+
+    /// Keep `compensation` of a step done outside of [`step`].
+    ///
+    /// [`step`]: #method.step
+    pub fn compensate<Fut>(&mut self, compensation: Fut) -> &mut Saga
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.compensations.push(compensation.boxed());
+        self
+    }
+
+    /// Keep the steps done so far, dropping their compensations.
+    pub fn complete(mut self) {
+        self.compensations.clear();
+    }
+
+    /// Run the compensations now, latest first.
+    pub async fn abort(mut self) {
+        for compensation in self.compensations.drain(..).rev() {
+            compensation.await;
+        }
+    }
+}
+
+impl Drop for Saga {
+    fn drop(&mut self) {
+        if self.compensations.is_empty() {
+            return;
+        }
+        let compensations = std::mem::take(&mut self.compensations);
+        match Handle::try_current() {
+            Ok(handle) => {
+                log::info!(target: "warp_json_rpc", "Compensating {} steps of an incomplete saga", compensations.len());
+                handle.spawn(async move {
+                    for compensation in compensations.into_iter().rev() {
+                        compensation.await;
+                    }
+                });
+            }
+            Err(_) => {
+                log::error!(target: "warp_json_rpc", "Cannot compensate an incomplete saga outside of a runtime");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn compensate_incomplete_saga() {
+        let undone = Arc::new(Mutex::new(Vec::new()));
+        let undo = |n: u32| {
+            let undone = undone.clone();
+            async move { undone.lock().unwrap().push(n) }
+        };
+
+        let mut saga = Saga::new();
+        saga.step(async { Ok::<_, ()>(1) }, |&n| undo(n))
+            .await
+            .unwrap();
+        saga.step(async { Ok::<_, ()>(2) }, |&n| undo(n))
+            .await
+            .unwrap();
+        assert!(saga
+            .step(async { Err::<u32, _>(()) }, |&n| undo(n))
+            .await
+            .is_err());
+        saga.abort().await;
+        assert_eq!(*undone.lock().unwrap(), vec![2, 1]);
+
+        let mut saga = Saga::new();
+        saga.compensate(undo(3));
+        drop(saga);
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        assert_eq!(*undone.lock().unwrap(), vec![2, 1, 3]);
+
+        let mut saga = Saga::new();
+        saga.compensate(undo(4));
+        saga.complete();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        assert_eq!(*undone.lock().unwrap(), vec![2, 1, 3]);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::RpcRouter;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+/// Rates at which calls are logged by the router, which can be changed while
+/// it is serving.
+///
+/// Clones share the rates, so a clone kept by e.g. an admin method adjusts
+/// the logging of the router.
+///
+/// ```
+/// # use warp_json_rpc::{LogSampling, RpcRouter};
+/// // Log 1% of successful calls, and every failed call.
+/// let sampling = LogSampling::new(0.01);
+/// let mut router = RpcRouter::new();
+/// router.log_sampling(&sampling);
+///
+/// // Later, log every call of "transfer".
+/// sampling.set_method("transfer", 1.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogSampling {
+    rates: Arc<RwLock<Rates>>,
+}
+
+#[derive(Debug)]
+struct Rates {
+    success: f64,
+    error: f64,
+    methods: HashMap<String, f64>,
+}
+
+impl LogSampling {
+    /// Log `success` of successful calls, from 0.0 to 1.0, and every failed
+    /// call.
+    pub fn new(success: f64) -> LogSampling {
+        LogSampling {
+            rates: Arc::new(RwLock::new(Rates {
+                success: clamp(success),
+                error: 1.0,
+                methods: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Change the rate of successful calls of methods without their own rate.
+    pub fn set_success_rate(&self, rate: f64) {
+        self.rates.write().unwrap().success = clamp(rate);
+    }
+
+    /// Change the rate of failed calls.
+    pub fn set_error_rate(&self, rate: f64) {
+        self.rates.write().unwrap().error = clamp(rate);
+    }
+
+    /// Log `rate` of successful calls of `method`, instead of the success
+    /// rate.
+    pub fn set_method(&self, method: impl Into<String>, rate: f64) {
+        let mut rates = self.rates.write().unwrap();
+        rates.methods.insert(method.into(), clamp(rate));
+    }
+
+    /// Log successful calls of `method` by the success rate again.
+    pub fn clear_method(&self, method: &str) {
+        self.rates.write().unwrap().methods.remove(method);
+    }
+
+    /// Rate at which a call of `method` is logged.
+    pub fn rate(&self, method: &str, failed: bool) -> f64 {
+        let rates = self.rates.read().unwrap();
+        if failed {
+            rates.error
+        } else {
+            rates.methods.get(method).copied().unwrap_or(rates.success)
+        }
+    }
+
+    pub(crate) fn sample(&self, method: &str, failed: bool) -> bool {
+        let rate = self.rate(method, failed);
+        rate >= 1.0 || (rate > 0.0 && rand::random::<f64>() < rate)
+    }
+}
+
+fn clamp(rate: f64) -> f64 {
+    if rate.is_nan() {
+        return 0.0;
+    }
+    rate.clamp(0.0, 1.0)
+}
+
+impl RpcRouter {
+    /// Log calls once they are answered, at the rates of `sampling`, instead
+    /// of logging every call when it is received.
+    pub fn log_sampling(&mut self, sampling: &LogSampling) -> &mut RpcRouter {
+        self.sampling = Some(sampling.clone());
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn override_rates() {
+        let sampling = LogSampling::new(0.0);
+        assert!(!sampling.sample("a", false));
+        assert!(sampling.sample("a", true));
+
+        let shared = sampling.clone();
+        shared.set_method("a", 1.0);
+        assert!(sampling.sample("a", false));
+        assert!(!sampling.sample("b", false));
+        shared.clear_method("a");
+        assert!(!sampling.sample("a", false));
+
+        shared.set_error_rate(-1.0);
+        assert_eq!(sampling.rate("a", true), 0.0);
+        shared.set_success_rate(2.0);
+        assert_eq!(sampling.rate("a", false), 1.0);
+    }
+}
@@ -0,0 +1,300 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// A JSON Schema used to validate RPC parameters and results.
+///
+/// Only a subset of JSON Schema is supported:
+///
+/// - `type` (a name or an array of names)
+/// - `enum` and `const`
+/// - `properties`, `required` and `additionalProperties`
+/// - `items` (a schema, or an array of schemas for positional parameters),
+///   `additionalItems`, `minItems` and `maxItems`
+/// - `minimum` and `maximum`
+/// - `minLength` and `maxLength`
+///
+/// Unknown keywords are ignored.
+///
+/// ```
+/// # use warp_json_rpc::Schema;
+/// use serde_json::json;
+///
+/// let schema = Schema::new(json!({
+///     "type": "array",
+///     "items": [{ "type": "integer" }, { "type": "integer" }],
+///     "additionalItems": false,
+/// }));
+/// assert!(schema.validate(&json!([1, 2])).is_ok());
+///
+/// let violations = schema.validate(&json!([1, "2"])).unwrap_err();
+/// assert_eq!(violations[0].pointer, "/1");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    schema: Value,
+}
+
+/// A place where a value does not conform to a [`Schema`].
+///
+/// [`Schema`]: ./struct.Schema.html
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    /// JSON Pointer to the invalid value.
+    pub pointer: String,
+    pub message: String,
+    /// What was expected instead, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+}
+
+impl Schema {
+    pub fn new(schema: Value) -> Schema {
+        Schema { schema }
+    }
+
+    pub fn as_value(&self) -> &Value {
+        &self.schema
+    }
+
+    pub fn validate(&self, value: &Value) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        check(&self.schema, value, "", &mut violations);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+fn check(schema: &Value, value: &Value, pointer: &str, out: &mut Vec<Violation>) {
+    let schema = match schema {
+        Value::Object(schema) => schema,
+        Value::Bool(false) => return violation(out, pointer, "no value is allowed".into()),
+        _ => return,
+    };
+
+    if let Some(ty) = schema.get("type") {
+        let names = match ty {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|name| is_type(value, name)) {
+            let expected = names.join(" or ");
+            return out.push(Violation {
+                pointer: pointer.to_string(),
+                message: format!("expected {}", expected),
+                expected: Some(expected),
+            });
+        }
+    }
+
+    if let Some(Value::Array(candidates)) = schema.get("enum") {
+        if !candidates.contains(value) {
+            violation(
+                out,
+                pointer,
+                "value is not one of the allowed values".into(),
+            );
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            violation(out, pointer, format!("expected {}", expected));
+        }
+    }
+
+    match value {
+        Value::Object(map) => check_object(schema, map, pointer, out),
+        Value::Array(items) => check_array(schema, items, pointer, out),
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(f64::NAN);
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    violation(out, pointer, format!("must be at least {}", min));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    violation(out, pointer, format!("must be at most {}", max));
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if len < min {
+                    violation(out, pointer, format!("must be at least {} characters", min));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if len > max {
+                    violation(out, pointer, format!("must be at most {} characters", max));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_object(
+    schema: &Map<String, Value>,
+    map: &Map<String, Value>,
+    pointer: &str,
+    out: &mut Vec<Violation>,
+) {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !map.contains_key(name) {
+                violation(
+                    out,
+                    &child(pointer, name),
+                    "missing required property".into(),
+                );
+            }
+        }
+    }
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, value) in map {
+        let prop = properties
+            .and_then(|props| props.get(name))
+            .or_else(|| schema.get("additionalProperties"));
+        if let Some(prop) = prop {
+            check(prop, value, &child(pointer, name), out);
+        }
+    }
+}
+
+fn check_array(
+    schema: &Map<String, Value>,
+    items: &[Value],
+    pointer: &str,
+    out: &mut Vec<Violation>,
+) {
+    let len = items.len() as u64;
+    if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+        if len < min {
+            violation(out, pointer, format!("must have at least {} items", min));
+        }
+    }
+    if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+        if len > max {
+            violation(out, pointer, format!("must have at most {} items", max));
+        }
+    }
+
+    match schema.get("items") {
+        Some(Value::Array(positional)) => {
+            for (i, item) in items.iter().enumerate() {
+                let item_pointer = child(pointer, &i.to_string());
+                match positional.get(i) {
+                    Some(item_schema) => check(item_schema, item, &item_pointer, out),
+                    None => {
+                        if let Some(additional) = schema.get("additionalItems") {
+                            check(additional, item, &item_pointer, out);
+                        }
+                    }
+                }
+            }
+        }
+        Some(item_schema) => {
+            for (i, item) in items.iter().enumerate() {
+                check(item_schema, item, &child(pointer, &i.to_string()), out);
+            }
+        }
+        None => {}
+    }
+}
+
+fn is_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64()
+                || value.is_u64()
+                || value.as_f64().map(|n| n.fract() == 0.0).unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn child(pointer: &str, token: &str) -> String {
+    format!(
+        "{}/{}",
+        pointer,
+        token.replace('~', "~0").replace('/', "~1")
+    )
+}
+
+fn violation(out: &mut Vec<Violation>, pointer: &str, message: String) {
+    out.push(Violation {
+        pointer: pointer.to_string(),
+        message,
+        expected: None,
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn pointers(schema: &Schema, value: Value) -> Vec<String> {
+        match schema.validate(&value) {
+            Ok(()) => Vec::new(),
+            Err(violations) => violations.into_iter().map(|v| v.pointer).collect(),
+        }
+    }
+
+    #[test]
+    fn validate_object() {
+        let schema = Schema::new(json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "age": { "type": "integer", "minimum": 0 },
+            },
+            "required": ["name"],
+            "additionalProperties": false,
+        }));
+
+        assert!(pointers(&schema, json!({ "name": "alice", "age": 3 })).is_empty());
+        assert_eq!(
+            pointers(&schema, json!({ "age": -1 })),
+            vec!["/name", "/age"]
+        );
+        assert_eq!(
+            pointers(&schema, json!({ "name": "", "x/y": 1 })),
+            vec!["/name", "/x~1y"]
+        );
+        assert_eq!(pointers(&schema, json!([])), vec![""]);
+    }
+
+    #[test]
+    fn validate_array() {
+        let schema = Schema::new(json!({
+            "type": "array",
+            "items": { "enum": ["a", "b"] },
+            "maxItems": 2,
+        }));
+
+        assert!(pointers(&schema, json!(["a", "b"])).is_empty());
+        assert_eq!(pointers(&schema, json!(["a", "c"])), vec!["/1"]);
+        assert_eq!(pointers(&schema, json!(["a", "b", "a"])), vec![""]);
+    }
+
+    #[test]
+    fn integer_type() {
+        let schema = Schema::new(json!({ "type": "integer" }));
+        assert!(schema.validate(&json!(1)).is_ok());
+        assert!(schema.validate(&json!(1.0)).is_ok());
+        assert!(schema.validate(&json!(1.5)).is_err());
+    }
+}
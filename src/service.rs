@@ -1,9 +1,9 @@
-use crate::store::LazyReqStore;
+use crate::{rejection::InvalidRequest, status::StatusMapping, store::LazyReqStore, Builder, Id};
 use core::{
     convert::Infallible,
     task::{Context, Poll},
 };
-use futures::future::{Future, TryFuture};
+use futures::future::Future;
 use http::Request;
 use hyper::{service::Service, Body};
 use warp::{
@@ -11,6 +11,21 @@ use warp::{
     Filter, Rejection,
 };
 
+/// Answer a request body which failed to parse with the status decided by
+/// `status`, and leave other rejections to warp.
+async fn answer_invalid(
+    rejection: Rejection,
+    status: StatusMapping,
+) -> Result<Response, Rejection> {
+    let error = match rejection.find::<InvalidRequest>() {
+        Some(invalid) => invalid.error(),
+        None => return Err(rejection),
+    };
+    Builder::new(Id::Null, status)
+        .error(error)
+        .map_err(|_| warp::reject())
+}
+
 #[derive(Clone, Copy)]
 pub struct JsonRpcService<S> {
     service: S,
@@ -45,11 +60,40 @@ impl<S> JsonRpcService<S> {
     }
 }
 
+/// Installs a `StatusMapping` for the requests it passes to `service`.
+#[derive(Clone)]
+struct WithStatus<S> {
+    service: S,
+    status: StatusMapping,
+}
+
+impl<S> Service<Request<Body>> for WithStatus<S>
+where
+    S: Service<Request<Body>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        req.extensions_mut().insert(self.status.clone());
+        self.service.call(req)
+    }
+}
+
 /// Convert a `Filter` into a `Service`.
 ///
 /// # Note
 ///
 /// You **MUST** call this method to use filters in this crate.
+///
+/// A request body which is not a JSON RPC request is answered by
+/// `PARSE_ERROR` or `INVALID_REQUEST` error, with `200 OK` status like other
+/// error responses.
 pub fn service<F>(
     filter: F,
 ) -> impl Service<
@@ -59,9 +103,116 @@ pub fn service<F>(
     Future = impl Future<Output = Result<Response, Infallible>>,
 > + Clone
 where
-    F: Filter + Clone,
-    F::Future: TryFuture<Error = Rejection>,
-    <F::Future as TryFuture>::Ok: Reply,
+    F: Filter<Error = Rejection> + Clone,
+    F::Extract: Reply,
 {
-    JsonRpcService::new(warp::service(filter))
+    JsonRpcService::new(warp::service(
+        filter.recover(|rejection| answer_invalid(rejection, StatusMapping::default())),
+    ))
+}
+
+/// Convert a `Filter` into a `Service` which answers error responses with the
+/// HTTP status decided by `status`.
+///
+/// Request bodies which are not JSON RPC requests are answered with the
+/// status `status` decides for `PARSE_ERROR` or `INVALID_REQUEST`.
+///
+/// # Note
+///
+/// Same as [`service`], you **MUST** call this method (or [`service`]) to use
+/// filters in this crate.
+///
+/// [`service`]: ./fn.service.html
+pub fn service_with_status<F>(
+    filter: F,
+    status: StatusMapping,
+) -> impl Service<
+    Request<Body>,
+    Response = Response,
+    Error = Infallible,
+    Future = impl Future<Output = Result<Response, Infallible>>,
+> + Clone
+where
+    F: Filter<Error = Rejection> + Clone,
+    F::Extract: Reply,
+{
+    let mapping = status.clone();
+    JsonRpcService::new(WithStatus {
+        service: warp::service(
+            filter.recover(move |rejection| answer_invalid(rejection, mapping.clone())),
+        ),
+        status,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{filters, Builder, Error};
+    use http::StatusCode;
+
+    async fn post(body: &'static str, status: Option<StatusMapping>) -> Response {
+        let filter = filters::json_rpc()
+            .and(filters::method("add"))
+            .map(|res: Builder| res.success(0).unwrap());
+        let req = Request::post("/")
+            .header("Content-Type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        match status {
+            Some(status) => service_with_status(filter, status).call(req).await,
+            None => service(filter).call(req).await,
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn service_is_copy() {
+        fn assert_copy<T: Copy>() {}
+        assert_copy::<JsonRpcService<()>>();
+    }
+
+    #[tokio::test]
+    async fn answer_invalid_body() {
+        let custom = || {
+            StatusMapping::new(|err: &Error| match err.code {
+                -32700 => StatusCode::BAD_REQUEST,
+                -32600 => StatusCode::UNPROCESSABLE_ENTITY,
+                _ => StatusCode::OK,
+            })
+        };
+        let cases = [
+            ("{ invalid }", -32700, None, StatusCode::OK),
+            (r#"{"method": 1}"#, -32600, None, StatusCode::OK),
+            (
+                "{ invalid }",
+                -32700,
+                Some(custom()),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                r#"{"method": 1}"#,
+                -32600,
+                Some(custom()),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+        ];
+        for (body, code, mapping, status) in cases.iter().cloned() {
+            let res = post(body, mapping).await;
+            assert_eq!(res.status(), status);
+            let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            let body = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+            assert_eq!(body["error"]["code"], code);
+            assert_eq!(body["id"], serde_json::Value::Null);
+        }
+
+        let res = post(r#"{"jsonrpc": "2.0", "method": "add", "id": 1}"#, None).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = post(
+            r#"{"jsonrpc": "2.0", "method": "add", "id": 1}"#,
+            Some(custom()),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
 }
@@ -0,0 +1,98 @@
+use crate::{
+    diff::{self, DiffReporter, LogReporter},
+    outcome::Outcome,
+    redact::Sensitive,
+    BoxedHandler, Context, Params, RpcRouter,
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+impl RpcRouter {
+    /// Call `shadow` as well as the handler for every call of the method.
+    ///
+    /// The shadow handler is called in the background with the same parameter
+    /// and context after the handler returns, and its result is discarded. If
+    /// it differs from the result of the handler, the difference is reported
+    /// as configured by [`shadow_diff`] (logged by default).
+    /// This validates a new implementation of the method (or an upstream
+    /// server, called from `shadow`) under real traffic without affecting
+    /// clients.
+    ///
+    /// [`shadow_diff`]: #method.shadow_diff
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn shadow(&mut self, name: &str, shadow: BoxedHandler) -> &mut RpcRouter {
+        self.registered(name).shadow = Some(shadow);
+        self
+    }
+
+    /// Compare results of shadowed methods ignoring values at `ignore`, and
+    /// send the differences to `reporter`.
+    ///
+    /// Results are compared as `{ "result": ... }` or `{ "error": ... }`, so
+    /// patterns of [`diff::diff`] start with `/result` or `/error`.
+    ///
+    /// [`diff::diff`]: ./diff/fn.diff.html
+    pub fn shadow_diff<R>(&mut self, ignore: Vec<String>, reporter: R) -> &mut RpcRouter
+    where
+        R: DiffReporter + 'static,
+    {
+        self.shadow_diff = Arc::new(ShadowDiff {
+            ignore,
+            reporter: Box::new(reporter),
+        });
+        self
+    }
+}
+
+pub(crate) struct ShadowDiff {
+    ignore: Vec<String>,
+    reporter: Box<dyn DiffReporter>,
+}
+
+impl Default for ShadowDiff {
+    fn default() -> ShadowDiff {
+        ShadowDiff {
+            ignore: Vec::new(),
+            reporter: Box::new(LogReporter),
+        }
+    }
+}
+
+/// Call `shadow` in the background, and compare its outcome to `primary`.
+pub(crate) fn spawn(
+    shadow: BoxedHandler,
+    params: Params,
+    ctx: Context,
+    primary: Outcome,
+    compare: Arc<ShadowDiff>,
+    sensitive: Sensitive,
+) {
+    tokio::spawn(async move {
+        let method = ctx.method().to_string();
+        let shadow = Outcome::from_result(&shadow(params, ctx).await);
+        let mut differences = diff::diff(&primary.to_value(), &shadow.to_value(), &compare.ignore);
+        for difference in differences.iter_mut() {
+            sensitive.redact_difference(difference);
+        }
+        compare.reporter.report(&method, &differences);
+    });
+}
+
+impl Outcome {
+    /// JSON of the outcome, as the `result` or `error` member of a response.
+    pub(crate) fn to_value(&self) -> Value {
+        match self {
+            Outcome::Success(value) => serde_json::json!({ "result": value }),
+            Outcome::Error {
+                code,
+                message,
+                data,
+            } => serde_json::json!({
+                "error": { "code": code, "message": message, "data": data },
+            }),
+        }
+    }
+}
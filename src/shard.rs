@@ -0,0 +1,116 @@
+use crate::{router::Method, Params, RpcRouter};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+};
+use tokio::sync::{Mutex as Queue, OwnedMutexGuard};
+
+type KeyExtractor = Arc<dyn Fn(&Value) -> Option<String> + Send + Sync>;
+
+impl RpcRouter {
+    /// Run calls of the method with the same key one at a time, in the order
+    /// they arrive.
+    ///
+    /// `key` receives the RPC parameter as JSON (`null` if absent), e.g. to
+    /// take an account id, so a stateful handler never runs concurrently for
+    /// the same account. Calls with different keys, and calls for which
+    /// `key` returns `None`, still run concurrently. A call still waiting for
+    /// its turn at its [deadline] fails with `DEADLINE_EXCEEDED_CODE` error.
+    ///
+    /// [deadline]: ./struct.Context.html#method.deadline
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn serialize_by<F>(&mut self, name: &str, key: F) -> &mut RpcRouter
+    where
+        F: Fn(&Value) -> Option<String> + Send + Sync + 'static,
+    {
+        self.registered(name).shards = Some(Arc::new(Shards {
+            key: Arc::new(key),
+            queues: Mutex::new(HashMap::new()),
+        }));
+        self
+    }
+}
+
+pub(crate) struct Shards {
+    key: KeyExtractor,
+    /// Queue of each key with a call running or waiting.
+    queues: Mutex<HashMap<String, Weak<Queue<()>>>>,
+}
+
+impl Shards {
+    /// Wait until the calls with the same key as `params` which arrived
+    /// earlier are done. The turn lasts until the guard is dropped.
+    pub(crate) async fn turn(&self, params: &Params) -> Option<OwnedMutexGuard<()>> {
+        let params = params.parse::<Value>().unwrap_or(Value::Null);
+        let key = (self.key)(&params)?;
+        let queue = {
+            let mut queues = self.queues.lock().unwrap();
+            match queues.get(&key).and_then(Weak::upgrade) {
+                Some(queue) => queue,
+                None => {
+                    queues.retain(|_, queue| queue.strong_count() > 0);
+                    let queue = Arc::new(Queue::new(()));
+                    queues.insert(key, Arc::downgrade(&queue));
+                    queue
+                }
+            }
+        };
+        Some(queue.lock_owned().await)
+    }
+}
+
+impl Method {
+    pub(crate) async fn turn(&self, params: &Params) -> Option<OwnedMutexGuard<()>> {
+        match self.shards.as_ref() {
+            Some(shards) => shards.turn(params).await,
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::value::RawValue;
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    fn params(json: &str) -> Params {
+        Params::from_raw(Some(RawValue::from_string(json.to_string()).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn serialize_same_key() {
+        let shards = Arc::new(Shards {
+            key: Arc::new(|params| params[0].as_str().map(str::to_string)),
+            queues: Mutex::new(HashMap::new()),
+        });
+        let first = shards.turn(&params(r#"["a"]"#)).await.unwrap();
+        assert!(shards.turn(&params("[1]")).await.is_none());
+        let other = shards.turn(&params(r#"["b"]"#)).await.unwrap();
+
+        let turned = Arc::new(AtomicBool::new(false));
+        let waiting = tokio::spawn({
+            let (shards, turned) = (shards.clone(), turned.clone());
+            async move {
+                let turn = shards.turn(&params(r#"["a"]"#)).await;
+                turned.store(true, Ordering::SeqCst);
+                turn.is_some()
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!turned.load(Ordering::SeqCst));
+        drop(first);
+        assert!(waiting.await.unwrap());
+
+        drop(other);
+        assert!(shards.turn(&params(r#"["c"]"#)).await.is_some());
+        assert_eq!(shards.queues.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{sha256, RpcRouter};
+use http::{header::CONTENT_LENGTH, HeaderMap, HeaderValue};
+use std::sync::Arc;
+use warp::{hyper::Body, reply::Response};
+
+/// HTTP header carrying the signature of a response body, as
+/// `{algorithm}={base64 of signature}`.
+pub const SIGNATURE: &str = "X-Signature";
+
+/// Signs response bodies, e.g. by an HMAC or ed25519 key.
+pub trait Signer: Send + Sync {
+    /// Name of the algorithm put in the `X-Signature` header, such as
+    /// `hmac-sha256` or `ed25519`.
+    fn algorithm(&self) -> &str;
+
+    fn sign(&self, body: &[u8]) -> Vec<u8>;
+}
+
+/// Signs response bodies by HMAC-SHA256 of a shared key, as `hmac-sha256`.
+///
+/// Clients check the signature by [`verify_hmac_sha256`] of the same key.
+///
+/// [`verify_hmac_sha256`]: ./fn.verify_hmac_sha256.html
+#[derive(Clone)]
+pub struct HmacSha256 {
+    key: Vec<u8>,
+}
+
+impl HmacSha256 {
+    pub fn new(key: impl Into<Vec<u8>>) -> HmacSha256 {
+        HmacSha256 { key: key.into() }
+    }
+}
+
+impl Signer for HmacSha256 {
+    fn algorithm(&self) -> &str {
+        "hmac-sha256"
+    }
+
+    fn sign(&self, body: &[u8]) -> Vec<u8> {
+        sha256::hmac(&self.key, &[body]).to_vec()
+    }
+}
+
+impl RpcRouter {
+    /// Sign the body of every response by `signer`, and send the signature
+    /// by the `X-Signature` header, so clients can tell whether a response
+    /// was changed on its way by [`verify_signature`].
+    ///
+    /// Streamed responses, which have no `Content-Length`, are not signed.
+    ///
+    /// [`verify_signature`]: ./fn.verify_signature.html
+    pub fn sign_responses<S>(&mut self, signer: S) -> &mut RpcRouter
+    where
+        S: Signer + 'static,
+    {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+}
+
+/// Whether the `X-Signature` header of a response is a signature of `body`
+/// by `algorithm`, as checked by `verify` with the body and the signature.
+///
+/// ```
+/// # use warp_json_rpc::{verify_hmac_sha256, verify_signature};
+/// # use http::HeaderMap;
+/// # let headers = HeaderMap::new();
+/// # let body = b"";
+/// let verified = verify_signature(&headers, body, "hmac-sha256", verify_hmac_sha256(b"key"));
+/// # assert!(!verified);
+/// ```
+pub fn verify_signature<F>(headers: &HeaderMap, body: &[u8], algorithm: &str, verify: F) -> bool
+where
+    F: FnOnce(&[u8], &[u8]) -> bool,
+{
+    let header = match headers.get(SIGNATURE).and_then(|value| value.to_str().ok()) {
+        Some(header) => header,
+        None => return false,
+    };
+    let signature = match header.split_once('=') {
+        Some((name, signature)) if name == algorithm => signature,
+        _ => return false,
+    };
+    match base64::decode(signature) {
+        Ok(signature) => verify(body, &signature),
+        Err(_) => false,
+    }
+}
+
+/// A `verify` function for [`verify_signature`] checking HMAC-SHA256 of
+/// `key`, as signed by [`HmacSha256`].
+///
+/// Signatures are compared in constant time.
+///
+/// [`verify_signature`]: ./fn.verify_signature.html
+/// [`HmacSha256`]: ./struct.HmacSha256.html
+pub fn verify_hmac_sha256(key: &[u8]) -> impl Fn(&[u8], &[u8]) -> bool + '_ {
+    move |body, signature| {
+        let expected = sha256::hmac(key, &[body]);
+        expected.len() == signature.len()
+            && expected
+                .iter()
+                .zip(signature)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Value of the `X-Signature` header of `body`.
+pub(crate) fn signature(signer: &dyn Signer, body: &[u8]) -> String {
+    format!(
+        "{}={}",
+        signer.algorithm(),
+        base64::encode(signer.sign(body))
+    )
+}
+
+pub(crate) async fn sign(signer: &dyn Signer, response: Response) -> Response {
+    if !response.headers().contains_key(CONTENT_LENGTH) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!(target: "warp_json_rpc", "Failed to read response body to sign: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    match HeaderValue::from_str(&signature(signer, &body)) {
+        Ok(signature) => {
+            parts.headers.insert(SIGNATURE, signature);
+        }
+        Err(e) => {
+            log::error!(target: "warp_json_rpc", "Signature is not a valid header value: {}", e)
+        }
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{store::LazyReqStore, Error};
+
+    #[tokio::test]
+    async fn sign_and_verify() {
+        let mut router = RpcRouter::new();
+        router
+            .method("add", |(lhs, rhs): (i64, i64)| async move {
+                Ok::<_, Error>(lhs + rhs)
+            })
+            .sign_responses(HmacSha256::new(&b"key"[..]));
+        let res = warp::test::request()
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .extension(LazyReqStore::empty())
+            .body(r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#)
+            .filter(&router.into_filter())
+            .await
+            .ok()
+            .unwrap();
+        let headers = res.headers().clone();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+
+        let verify = verify_hmac_sha256(b"key");
+        assert!(verify_signature(&headers, &body, "hmac-sha256", &verify));
+        assert!(!verify_signature(&headers, b"{}", "hmac-sha256", &verify));
+        assert!(!verify_signature(&headers, &body, "ed25519", &verify));
+        assert!(!verify_signature(
+            &headers,
+            &body,
+            "hmac-sha256",
+            verify_hmac_sha256(b"other key")
+        ));
+        assert!(!verify_signature(
+            &HeaderMap::new(),
+            &body,
+            "hmac-sha256",
+            &verify
+        ));
+        assert!(!verify(&body, &[0; 31]));
+    }
+}
@@ -0,0 +1,60 @@
+use crate::res::Error;
+use http::StatusCode;
+use std::{fmt, sync::Arc};
+
+/// Decides which HTTP status code carries a JSON RPC error response.
+///
+/// JSON RPC over HTTP always answers `200 OK`, which is what the default
+/// mapping does. Some clients and load balancers want to see other statuses
+/// for certain error classes (e.g. `429` for rate limits), so a custom
+/// mapping can be installed by [`service_with_status`].
+///
+/// Successful responses are always sent with `200 OK`.
+///
+/// [`service_with_status`]: ./fn.service_with_status.html
+///
+/// ```
+/// # use warp_json_rpc::{Error, StatusMapping};
+/// use http::StatusCode;
+///
+/// let mapping = StatusMapping::new(|err: &Error| match err.code {
+///     -32603 => StatusCode::INTERNAL_SERVER_ERROR,
+///     -32001 => StatusCode::TOO_MANY_REQUESTS,
+///     _ => StatusCode::OK,
+/// });
+/// assert_eq!(mapping.status(&Error::INTERNAL_ERROR), StatusCode::INTERNAL_SERVER_ERROR);
+/// ```
+#[derive(Clone)]
+pub struct StatusMapping {
+    map: Arc<dyn Fn(&Error) -> StatusCode + Send + Sync>,
+}
+
+impl StatusMapping {
+    pub fn new<F>(map: F) -> StatusMapping
+    where
+        F: Fn(&Error) -> StatusCode + Send + Sync + 'static,
+    {
+        StatusMapping { map: Arc::new(map) }
+    }
+
+    /// The spec compliant mapping which always returns `200 OK`.
+    pub fn spec() -> StatusMapping {
+        StatusMapping::new(|_| StatusCode::OK)
+    }
+
+    pub fn status(&self, error: &Error) -> StatusCode {
+        (self.map)(error)
+    }
+}
+
+impl Default for StatusMapping {
+    fn default() -> StatusMapping {
+        StatusMapping::spec()
+    }
+}
+
+impl fmt::Debug for StatusMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatusMapping").finish()
+    }
+}
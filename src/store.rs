@@ -1,18 +1,29 @@
-use crate::req::Request;
+use crate::{
+    rejection::{InvalidRequest, MissingService},
+    req::Request,
+};
 use futures::future;
 use lazycell::AtomicLazyCell;
 use std::sync::Arc;
 use warp::{filters, reject, Filter, Rejection};
 
+/// Result of parsing the request body.
+pub type Parsed = Result<Request, InvalidRequest>;
+
 #[derive(Clone)]
 pub struct LazyReqStore {
-    store: Arc<AtomicLazyCell<Request>>,
+    store: Arc<AtomicLazyCell<Parsed>>,
+    /// How many times the body was parsed, shared by clones.
+    #[cfg(test)]
+    pub(crate) parses: Arc<std::sync::atomic::AtomicUsize>,
 }
 
 impl LazyReqStore {
     pub fn empty() -> LazyReqStore {
         LazyReqStore {
             store: Arc::new(AtomicLazyCell::NONE),
+            #[cfg(test)]
+            parses: Arc::default(),
         }
     }
 
@@ -20,30 +31,38 @@ impl LazyReqStore {
         self.store.filled()
     }
 
-    pub fn fill(&self, req: Request) -> Result<(), Request> {
-        self.store.fill(req)
+    pub fn fill(&self, parsed: Parsed) -> Result<(), Parsed> {
+        self.store.fill(parsed)
     }
 
-    pub fn borrow(&self) -> Option<&Request> {
+    pub fn borrow(&self) -> Option<&Parsed> {
         self.store.borrow()
     }
+
+    /// RPC method of the stored request, if it is parsed successfully.
+    pub fn method(&self) -> Option<&str> {
+        match self.borrow() {
+            Some(Ok(req)) => Some(req.method()),
+            _ => None,
+        }
+    }
 }
 
 /// Create a `Filter` that extracts `LazyReqStore`.
 pub(crate) fn store() -> impl Filter<Extract = (LazyReqStore,), Error = Rejection> + Copy {
-    filters::ext::optional::<LazyReqStore>().and_then(|opt| {
-        match opt {
-            Some(store) => future::ok(store),
-            None => {
-                log::error!("Your Filter has to be wrapped by `JsonRpcService`");
-                // TODO return custom error
-                future::err(reject::reject())
-            }
+    filters::ext::optional::<LazyReqStore>().and_then(|opt| match opt {
+        Some(store) => future::ok(store),
+        None => {
+            log::error!("Your Filter has to be wrapped by `JsonRpcService`");
+            future::err(reject::custom(MissingService::new()))
         }
     })
 }
 
 /// Create a `Filter` that requires the `LazyReqStore` is already filled.
+///
+/// Note that a store filled with a parse failure is also regarded as filled,
+/// so that the body is never read twice.
 pub(crate) fn filled() -> impl Filter<Extract = (), Error = Rejection> + Copy {
     store()
         .and_then(|store: LazyReqStore| {
@@ -57,8 +76,14 @@ pub(crate) fn filled() -> impl Filter<Extract = (), Error = Rejection> + Copy {
 }
 
 /// Create a `Filter` that extracts stored `Request`.
+///
+/// Rejects with the cached `InvalidRequest` if the body failed to parse.
 pub(crate) fn stored_req() -> impl Filter<Extract = (Request,), Error = Rejection> + Copy {
     store().and_then(|store: LazyReqStore| {
-        future::ready(store.borrow().cloned().ok_or_else(reject::reject))
+        future::ready(match store.borrow() {
+            Some(Ok(req)) => Ok(req.clone()),
+            Some(Err(err)) => Err(reject::custom(err.clone())),
+            None => Err(reject::reject()),
+        })
     })
 }
@@ -0,0 +1,412 @@
+//! Time parameters.
+//!
+//! [`Timestamp`] accepts RFC 3339 strings or seconds since the Unix epoch, and
+//! [`DurationMs`] accepts milliseconds. Malformed values fail
+//! deserialization, so they are answered by `INVALID_PARAMS` error.
+//!
+//! [`Timestamp`]: ./struct.Timestamp.html
+//! [`DurationMs`]: ./struct.DurationMs.html
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize, Serialize, Serializer,
+};
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A point in time.
+///
+/// Deserialized from an RFC 3339 string (e.g. `"2021-02-03T04:05:06.789Z"` or
+/// `"2021-02-03T13:05:06+09:00"`) or a number of seconds since the Unix
+/// epoch. Serialized as an RFC 3339 string in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Timestamp(pub SystemTime);
+
+impl Timestamp {
+    pub fn now() -> Timestamp {
+        Timestamp(SystemTime::now())
+    }
+
+    /// Parse an RFC 3339 date-time.
+    pub fn parse_rfc3339(s: &str) -> Result<Timestamp, String> {
+        let invalid = || format!("invalid RFC 3339 timestamp {:?}", s);
+        if !s.is_ascii() {
+            return Err(invalid());
+        }
+        let b = s.as_bytes();
+        if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || b[13] != b':' || b[16] != b':' {
+            return Err(invalid());
+        }
+        if !matches!(b[10], b'T' | b't' | b' ') {
+            return Err(invalid());
+        }
+        let num = |range: std::ops::Range<usize>| -> Result<i64, String> {
+            let digits = &s[range];
+            if !digits.bytes().all(|c| c.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            digits.parse().map_err(|_| invalid())
+        };
+        let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+        let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+        if !(1..=12).contains(&month)
+            || day < 1
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            // Leap seconds are accepted, and read as the next second.
+            || second > 60
+        {
+            return Err(invalid());
+        }
+
+        let mut rest = &s[19..];
+        let mut nanos = 0_u32;
+        if let Some(frac) = rest.strip_prefix('.') {
+            let len = frac.bytes().take_while(u8::is_ascii_digit).count();
+            if len == 0 {
+                return Err(invalid());
+            }
+            for (i, c) in frac[..len.min(9)].bytes().enumerate() {
+                nanos += (c - b'0') as u32 * 10_u32.pow(8 - i as u32);
+            }
+            rest = &frac[len..];
+        }
+        let offset = match rest {
+            "Z" | "z" => 0,
+            _ => {
+                let b = rest.as_bytes();
+                if b.len() != 6
+                    || b[3] != b':'
+                    || ![b[1], b[2], b[4], b[5]].iter().all(u8::is_ascii_digit)
+                {
+                    return Err(invalid());
+                }
+                let sign = match b[0] {
+                    b'+' => 1,
+                    b'-' => -1,
+                    _ => return Err(invalid()),
+                };
+                let hours = rest[1..3].parse::<i64>().map_err(|_| invalid())?;
+                let minutes = rest[4..6].parse::<i64>().map_err(|_| invalid())?;
+                if hours > 23 || minutes > 59 {
+                    return Err(invalid());
+                }
+                sign * (hours * 3600 + minutes * 60)
+            }
+        };
+
+        let secs =
+            days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+        Timestamp::from_unix(secs, nanos).ok_or_else(invalid)
+    }
+
+    /// Format as an RFC 3339 date-time in UTC.
+    pub fn to_rfc3339(&self) -> String {
+        let (secs, nanos) = match self.0.duration_since(UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+            Err(e) => {
+                let before = e.duration();
+                let secs = -(before.as_secs() as i64);
+                match before.subsec_nanos() {
+                    0 => (secs, 0),
+                    nanos => (secs - 1, 1_000_000_000 - nanos),
+                }
+            }
+        };
+        let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+        let (year, month, day) = civil_from_days(days);
+        let mut s = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        );
+        if nanos != 0 {
+            let frac = format!("{:09}", nanos);
+            s.push('.');
+            s.push_str(frac.trim_end_matches('0'));
+        }
+        s.push('Z');
+        s
+    }
+
+    /// Format as the time of the Common Log Format, such as
+    /// `10/Oct/2000:13:55:36 +0000`.
+    pub(crate) fn to_common_log(self) -> String {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let secs = match self.0.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+        };
+        let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+        let (year, month, day) = civil_from_days(days);
+        format!(
+            "{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000",
+            day,
+            MONTHS[month as usize - 1],
+            year,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )
+    }
+
+    /// `None` if the time is out of the range of `SystemTime`.
+    fn from_unix(secs: i64, nanos: u32) -> Option<Timestamp> {
+        let time = if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))?
+        } else {
+            UNIX_EPOCH
+                .checked_sub(Duration::from_secs(secs.unsigned_abs()))?
+                .checked_add(Duration::from_nanos(nanos as u64))?
+        };
+        Some(Timestamp(time))
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Timestamp {
+        Timestamp(time)
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> SystemTime {
+        timestamp.0
+    }
+}
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the Unix epoch of a proleptic Gregorian date.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html>.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_rfc3339())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Timestamp, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TimestampVisitor;
+
+        impl<'de> Visitor<'de> for TimestampVisitor {
+            type Value = Timestamp;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an RFC 3339 timestamp or seconds since the Unix epoch")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Timestamp, E> {
+                Timestamp::parse_rfc3339(s).map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, secs: i64) -> Result<Timestamp, E> {
+                Timestamp::from_unix(secs, 0).ok_or_else(|| out_of_range(secs))
+            }
+
+            fn visit_u64<E: de::Error>(self, secs: u64) -> Result<Timestamp, E> {
+                UNIX_EPOCH
+                    .checked_add(Duration::from_secs(secs))
+                    .map(Timestamp)
+                    .ok_or_else(|| out_of_range(secs))
+            }
+
+            fn visit_f64<E: de::Error>(self, secs: f64) -> Result<Timestamp, E> {
+                if !secs.is_finite() || secs.abs() > i64::MAX as f64 {
+                    return Err(E::custom(format!("invalid epoch seconds {}", secs)));
+                }
+                let whole = secs.floor();
+                let nanos = ((secs - whole) * 1e9).round().min(999_999_999.0) as u32;
+                Timestamp::from_unix(whole as i64, nanos).ok_or_else(|| out_of_range(secs))
+            }
+        }
+
+        fn out_of_range<E: de::Error>(secs: impl fmt::Display) -> E {
+            E::custom(format!("epoch seconds {} are out of range", secs))
+        }
+
+        deserializer.deserialize_any(TimestampVisitor)
+    }
+}
+
+/// A duration in milliseconds.
+///
+/// Deserialized from a non-negative number of milliseconds, which may have a
+/// fraction. Serialized as a number of milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct DurationMs(pub Duration);
+
+impl From<Duration> for DurationMs {
+    fn from(duration: Duration) -> DurationMs {
+        DurationMs(duration)
+    }
+}
+
+impl From<DurationMs> for Duration {
+    fn from(duration: DurationMs) -> Duration {
+        duration.0
+    }
+}
+
+impl Serialize for DurationMs {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if self.0.subsec_nanos() % 1_000_000 == 0 {
+            serializer.serialize_u64(self.0.as_millis() as u64)
+        } else {
+            serializer.serialize_f64(self.0.as_secs_f64() * 1000.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DurationMs {
+    fn deserialize<D>(deserializer: D) -> Result<DurationMs, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DurationVisitor;
+
+        impl<'de> Visitor<'de> for DurationVisitor {
+            type Value = DurationMs;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a non-negative number of milliseconds")
+            }
+
+            fn visit_u64<E: de::Error>(self, ms: u64) -> Result<DurationMs, E> {
+                Ok(DurationMs(Duration::from_millis(ms)))
+            }
+
+            fn visit_i64<E: de::Error>(self, ms: i64) -> Result<DurationMs, E> {
+                Err(E::custom(format!("negative duration {} ms", ms)))
+            }
+
+            fn visit_f64<E: de::Error>(self, ms: f64) -> Result<DurationMs, E> {
+                if !ms.is_finite() || ms < 0.0 || ms / 1000.0 > u64::MAX as f64 {
+                    return Err(E::custom(format!("invalid duration {} ms", ms)));
+                }
+                Ok(DurationMs(Duration::from_secs_f64(ms / 1000.0)))
+            }
+        }
+
+        deserializer.deserialize_any(DurationVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(secs: u64, nanos: u32) -> Timestamp {
+        Timestamp(UNIX_EPOCH + Duration::new(secs, nanos))
+    }
+
+    #[test]
+    fn parse_rfc3339() {
+        assert_eq!(
+            Timestamp::parse_rfc3339("2021-02-03T04:05:06.789Z").unwrap(),
+            at(1612325106, 789_000_000)
+        );
+        assert_eq!(
+            Timestamp::parse_rfc3339("2021-02-03T13:05:06+09:00").unwrap(),
+            at(1612325106, 0)
+        );
+        assert_eq!(
+            Timestamp::parse_rfc3339("1969-12-31T23:59:59Z").unwrap(),
+            Timestamp(UNIX_EPOCH - Duration::from_secs(1))
+        );
+        assert!(Timestamp::parse_rfc3339("2021-02-29T00:00:00Z").is_err());
+        assert!(Timestamp::parse_rfc3339("2021-02-03T04:05:06").is_err());
+        assert!(Timestamp::parse_rfc3339("2021-02-03").is_err());
+        assert!(Timestamp::parse_rfc3339("2021-02-03T04:05:0€Z").is_err());
+        assert!(Timestamp::parse_rfc3339("2021-02-03T04:05:06.€Z").is_err());
+        assert!(Timestamp::parse_rfc3339("2021-02-03T04:05:06+-1:00").is_err());
+        assert!(Timestamp::parse_rfc3339("2021-02-03T04:05:06++1:00").is_err());
+        assert!(Timestamp::parse_rfc3339("2021-02-03T04:05:06+01:+1").is_err());
+    }
+
+    #[test]
+    fn timestamp_roundtrip() {
+        let timestamp = at(1612325106, 789_000_000);
+        assert_eq!(timestamp.to_rfc3339(), "2021-02-03T04:05:06.789Z");
+        assert_eq!(
+            Timestamp(UNIX_EPOCH - Duration::from_millis(500)).to_rfc3339(),
+            "1969-12-31T23:59:59.5Z"
+        );
+
+        let json = serde_json::to_string(&timestamp).unwrap();
+        assert_eq!(serde_json::from_str::<Timestamp>(&json).unwrap(), timestamp);
+        assert_eq!(
+            serde_json::from_str::<Timestamp>("1612325106.5").unwrap(),
+            at(1612325106, 500_000_000)
+        );
+        let error = serde_json::from_str::<Timestamp>(r#""yesterday""#).unwrap_err();
+        assert!(error.to_string().contains("invalid RFC 3339 timestamp"));
+
+        let error = serde_json::from_str::<Timestamp>("18446744073709551615").unwrap_err();
+        assert!(error.to_string().contains("out of range"));
+        assert!(serde_json::from_str::<Timestamp>("1e30").is_err());
+    }
+
+    #[test]
+    fn duration_ms() {
+        let duration = serde_json::from_str::<DurationMs>("1500").unwrap();
+        assert_eq!(duration.0, Duration::from_millis(1500));
+        assert_eq!(serde_json::to_string(&duration).unwrap(), "1500");
+        assert_eq!(
+            serde_json::from_str::<DurationMs>("0.5").unwrap().0,
+            Duration::from_micros(500)
+        );
+        assert!(serde_json::from_str::<DurationMs>("-1").is_err());
+    }
+}
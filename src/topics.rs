@@ -0,0 +1,305 @@
+//! Named topics of events, streamed by generated subscribe methods.
+//!
+//! Application code publishes events to [`Topics`], and loading it as a
+//! [`Plugin`] registers a `<topic>_subscribe` method for each topic. The
+//! methods are streaming methods (see [`ndjson`]), so subscribers receive
+//! events as `rpc_chunk` notifications for as long as they keep the response
+//! open. Events missed by lagging subscribers are notified as described in
+//! [`channel::Notification`].
+//!
+//! ```
+//! use warp_json_rpc::{topics::Topics, RpcRouter};
+//!
+//! let topics = Topics::new();
+//! topics.declare("blocks", 64);
+//!
+//! let mut router = RpcRouter::new();
+//! router.plugin(&topics);
+//! assert!(router.contains("blocks_subscribe"));
+//!
+//! // Later, from anywhere the application holds a clone of `topics`.
+//! topics.publish("blocks", &serde_json::json!({ "number": 1 }));
+//! ```
+//!
+//! Behind a load balancer, subscribers of one topic are spread over
+//! instances. Connecting the `Topics` of each instance to a [`Broker`] makes an
+//! event published on any instance reach all of them.
+//!
+//! [`Topics`]: ./struct.Topics.html
+//! [`Broker`]: ./trait.Broker.html
+//! [`Plugin`]: ../trait.Plugin.html
+//! [`ndjson`]: ../ndjson/index.html
+//! [`channel::Notification`]: ../channel/enum.Notification.html
+use crate::{
+    channel::{self, Notification},
+    Error, Plugin, RpcRouter,
+};
+use futures::{
+    future::{BoxFuture, FutureExt as _},
+    stream::{BoxStream, Stream, StreamExt as _},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::broadcast;
+
+/// A registry of topics.
+///
+/// Cloning a `Topics` shares the registry.
+#[derive(Clone, Default)]
+pub struct Topics {
+    topics: Arc<Mutex<BTreeMap<String, Topic>>>,
+    broker: Arc<Mutex<Option<Arc<dyn Broker>>>>,
+}
+
+/// A message broker carrying events between the [`Topics`] of instances, such
+/// as NATS or Redis pub/sub.
+///
+/// [`Topics`]: ./struct.Topics.html
+pub trait Broker: Send + Sync {
+    /// Send `event`, the JSON text of an event of `topic`, to every instance
+    /// including this one.
+    fn publish(&self, topic: &str, event: Vec<u8>) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    /// Events published by any instance, as topics and JSON texts.
+    fn subscribe(&self) -> BoxStream<'static, (String, Vec<u8>)>;
+}
+
+/// A `Broker` within this process, which connects `Topics` of the same
+/// process. Useful for tests.
+#[derive(Debug, Clone)]
+pub struct MemoryBroker {
+    tx: broadcast::Sender<(String, Vec<u8>)>,
+}
+
+impl MemoryBroker {
+    /// A broker buffering up to `capacity` events per connected `Topics`.
+    pub fn new(capacity: usize) -> MemoryBroker {
+        MemoryBroker {
+            tx: broadcast::channel(capacity).0,
+        }
+    }
+}
+
+impl Broker for MemoryBroker {
+    fn publish(&self, topic: &str, event: Vec<u8>) -> BoxFuture<'_, anyhow::Result<()>> {
+        // Fails only if no `Topics` is connected.
+        let _ = self.tx.send((topic.to_string(), event));
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    fn subscribe(&self) -> BoxStream<'static, (String, Vec<u8>)> {
+        channel::broadcast(self.tx.subscribe())
+            .filter_map(|item| async move {
+                match item {
+                    Ok(Notification::Event(event)) => Some(event),
+                    _ => None,
+                }
+            })
+            .boxed()
+    }
+}
+
+#[derive(Clone)]
+struct Topic {
+    tx: broadcast::Sender<Value>,
+    lagged: Arc<AtomicU64>,
+}
+
+impl Topics {
+    pub fn new() -> Topics {
+        Topics::default()
+    }
+
+    /// Declare a topic buffering up to `capacity` events per subscriber.
+    ///
+    /// A subscriber falling behind by more than `capacity` events misses the
+    /// oldest ones, which are counted by [`lagged`]. Declaring an existing
+    /// topic does nothing.
+    ///
+    /// [`lagged`]: #method.lagged
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn declare(&self, name: impl Into<String>, capacity: usize) -> &Topics {
+        let mut topics = self.topics.lock().unwrap();
+        topics.entry(name.into()).or_insert_with(|| Topic {
+            tx: broadcast::channel(capacity).0,
+            lagged: Arc::new(AtomicU64::new(0)),
+        });
+        self
+    }
+
+    /// Publish `event` to the subscribers of the topic, and return how many
+    /// subscribers there are.
+    ///
+    /// Events of undeclared topics are dropped. Once connected to a broker,
+    /// the event is sent to the broker, which delivers it to the subscribers
+    /// of every instance, and this must be called within a `tokio` runtime.
+    pub fn publish<T>(&self, topic: &str, event: &T) -> usize
+    where
+        T: Serialize,
+    {
+        let tx = match self.topics.lock().unwrap().get(topic) {
+            Some(topic) => topic.tx.clone(),
+            None => {
+                log::warn!(target: "warp_json_rpc", "Event of undeclared topic \"{}\" is dropped", topic);
+                return 0;
+            }
+        };
+        let broker = self.broker.lock().unwrap().clone();
+        if let Some(broker) = broker {
+            let event = match serde_json::to_vec(event) {
+                Ok(event) => event,
+                Err(e) => {
+                    log::error!(target: "warp_json_rpc", "Failed to serialize event: {}", e);
+                    return 0;
+                }
+            };
+            let topic = topic.to_string();
+            tokio::spawn(async move {
+                if let Err(e) = broker.publish(&topic, event).await {
+                    log::error!(target: "warp_json_rpc", "Failed to publish event to broker: {}", e);
+                }
+            });
+            return tx.receiver_count();
+        }
+        let event = match serde_json::to_value(event) {
+            Ok(event) => event,
+            Err(e) => {
+                log::error!(target: "warp_json_rpc", "Failed to serialize event: {}", e);
+                return 0;
+            }
+        };
+        // Fails only if there is no subscriber.
+        tx.send(event).unwrap_or(0)
+    }
+
+    /// Publish events by `broker` from now on, and deliver events received
+    /// from it to the subscribers of this instance.
+    ///
+    /// This must be called within a `tokio` runtime. Events of topics not
+    /// declared on this instance are dropped.
+    pub fn connect<B>(&self, broker: B) -> &Topics
+    where
+        B: Broker + 'static,
+    {
+        let broker = Arc::new(broker);
+        let mut events = broker.subscribe();
+        *self.broker.lock().unwrap() = Some(broker);
+        let topics = Arc::downgrade(&self.topics);
+        tokio::spawn(async move {
+            while let Some((topic, event)) = events.next().await {
+                let topics = match topics.upgrade() {
+                    Some(topics) => topics,
+                    None => return,
+                };
+                let tx = match topics.lock().unwrap().get(&topic) {
+                    Some(topic) => topic.tx.clone(),
+                    None => continue,
+                };
+                match serde_json::from_slice(&event) {
+                    // Fails only if there is no subscriber.
+                    Ok(event) => drop(tx.send(event)),
+                    Err(e) => {
+                        log::error!(target: "warp_json_rpc", "Event from broker is not JSON: {}", e)
+                    }
+                }
+            }
+        });
+        self
+    }
+
+    /// Number of current subscribers of the topic.
+    pub fn subscribers(&self, topic: &str) -> usize {
+        self.topics
+            .lock()
+            .unwrap()
+            .get(topic)
+            .map_or(0, |topic| topic.tx.receiver_count())
+    }
+
+    /// Total number of events missed by lagging subscribers of the topic.
+    pub fn lagged(&self, topic: &str) -> u64 {
+        self.topics
+            .lock()
+            .unwrap()
+            .get(topic)
+            .map_or(0, |topic| topic.lagged.load(Ordering::Relaxed))
+    }
+
+    fn subscribe(
+        topic: &Topic,
+    ) -> impl Stream<Item = Result<Notification<Value>, Error>> + Send + 'static {
+        let lagged = topic.lagged.clone();
+        channel::broadcast(topic.tx.subscribe()).inspect(move |item| {
+            if let Ok(Notification::Missed { missed_events }) = item {
+                lagged.fetch_add(*missed_events, Ordering::Relaxed);
+            }
+        })
+    }
+}
+
+impl Plugin for Topics {
+    /// Register `<topic>_subscribe` methods of the topics declared so far.
+    fn register(&self, router: &mut RpcRouter) {
+        let topics = self.topics.lock().unwrap();
+        for (name, topic) in topics.iter() {
+            let topic = topic.clone();
+            router.streaming(format!("{}_subscribe", name), move |(): ()| {
+                Topics::subscribe(&topic)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn publish_to_subscribers() {
+        let topics = Topics::new();
+        topics.declare("a", 2);
+        assert_eq!(topics.publish("a", &0), 0);
+        assert_eq!(topics.publish("b", &0), 0);
+
+        let topic = topics.topics.lock().unwrap()["a"].clone();
+        let events = Topics::subscribe(&topic);
+        futures::pin_mut!(events);
+        assert_eq!(topics.subscribers("a"), 1);
+
+        for n in 1..=4 {
+            assert_eq!(topics.publish("a", &n), 1);
+        }
+        let received = events.next().await.unwrap().ok();
+        assert_eq!(received, Some(Notification::Missed { missed_events: 2 }));
+        assert_eq!(topics.lagged("a"), 2);
+        let received = events.next().await.unwrap().ok();
+        assert_eq!(received, Some(Notification::Event(Value::from(3))));
+    }
+
+    #[tokio::test]
+    async fn fan_out_by_broker() {
+        let broker = MemoryBroker::new(16);
+        let (a, b) = (Topics::new(), Topics::new());
+        for topics in [&a, &b] {
+            topics.declare("blocks", 16).connect(broker.clone());
+        }
+
+        let topic = b.topics.lock().unwrap()["blocks"].clone();
+        let events = Topics::subscribe(&topic);
+        futures::pin_mut!(events);
+        assert_eq!(a.publish("blocks", &1), 0);
+
+        let received = events.next().await.unwrap().ok();
+        assert_eq!(received, Some(Notification::Event(Value::from(1))));
+    }
+}
@@ -0,0 +1,332 @@
+//! Groups of calls committed or rolled back together.
+//!
+//! A client opens a transaction by the built-in `rpc_begin` method, tags
+//! calls with the returned id by the `Transaction-Id` header, and ends the
+//! transaction by `rpc_commit` or `rpc_abort`. Handlers stage their work
+//! under the [`id`] of the transaction, and the application applies or
+//! discards it in its [`Hooks`].
+//!
+//! Committing is two-phase: [`Hooks::prepare`] checks that the calls can be
+//! committed, then [`Hooks::commit`] applies them. If a tagged call failed or
+//! preparing fails, the transaction is rolled back instead.
+//!
+//! [`id`]: ./fn.id.html
+//! [`Hooks`]: ./trait.Hooks.html
+//! [`Hooks::prepare`]: ./trait.Hooks.html#method.prepare
+//! [`Hooks::commit`]: ./trait.Hooks.html#tymethod.commit
+//!
+//! ```
+//! use futures::future::{BoxFuture, FutureExt as _};
+//! use std::time::Duration;
+//! use warp_json_rpc::{transaction::{Call, Hooks}, RpcRouter};
+//!
+//! struct Staging;
+//!
+//! impl Hooks for Staging {
+//!     fn commit<'a>(&'a self, id: &'a str, calls: &'a [Call]) -> BoxFuture<'a, ()> {
+//!         async move { println!("apply {} calls staged under {}", calls.len(), id) }.boxed()
+//!     }
+//!
+//!     fn rollback<'a>(&'a self, id: &'a str, _: &'a [Call]) -> BoxFuture<'a, ()> {
+//!         async move { println!("discard {}", id) }.boxed()
+//!     }
+//! }
+//!
+//! let mut router = RpcRouter::new();
+//! router.transactions(Duration::from_secs(300), Staging);
+//! ```
+use crate::{continuation, Context, Error, RpcRouter};
+use futures::future::{BoxFuture, FutureExt as _};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// HTTP header carrying the id of the transaction a call belongs to.
+pub const TRANSACTION_ID: &str = "Transaction-Id";
+
+/// Name of the method opening a transaction.
+pub const RPC_BEGIN: &str = "rpc_begin";
+/// Name of the method committing a transaction.
+pub const RPC_COMMIT: &str = "rpc_commit";
+/// Name of the method rolling a transaction back.
+pub const RPC_ABORT: &str = "rpc_abort";
+
+/// Error code returned when a transaction is unknown, expired or rolled
+/// back instead of committed.
+pub const TRANSACTION_ABORTED_CODE: i64 = -32010;
+
+/// The id of the transaction the call belongs to, if any.
+pub fn id(ctx: &Context) -> Option<&str> {
+    ctx.headers().get(TRANSACTION_ID)?.to_str().ok()
+}
+
+/// A call made within a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Call {
+    pub method: String,
+    /// Code of the error the call failed with, if it failed.
+    pub error_code: Option<i64>,
+}
+
+/// Hooks of the application ending transactions.
+///
+/// `calls` are in the order they completed.
+pub trait Hooks: Send + Sync {
+    /// Check that the calls can be committed. An error rolls the transaction
+    /// back and is answered to `rpc_commit`.
+    fn prepare<'a>(&'a self, id: &'a str, calls: &'a [Call]) -> BoxFuture<'a, Result<(), Error>> {
+        let _ = (id, calls);
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    /// Apply the staged work of a prepared transaction.
+    fn commit<'a>(&'a self, id: &'a str, calls: &'a [Call]) -> BoxFuture<'a, ()>;
+
+    /// Discard the staged work of an aborted, failed or expired transaction.
+    fn rollback<'a>(&'a self, id: &'a str, calls: &'a [Call]) -> BoxFuture<'a, ()>;
+}
+
+impl<H: Hooks + ?Sized> Hooks for Arc<H> {
+    fn prepare<'a>(&'a self, id: &'a str, calls: &'a [Call]) -> BoxFuture<'a, Result<(), Error>> {
+        (**self).prepare(id, calls)
+    }
+
+    fn commit<'a>(&'a self, id: &'a str, calls: &'a [Call]) -> BoxFuture<'a, ()> {
+        (**self).commit(id, calls)
+    }
+
+    fn rollback<'a>(&'a self, id: &'a str, calls: &'a [Call]) -> BoxFuture<'a, ()> {
+        (**self).rollback(id, calls)
+    }
+}
+
+impl RpcRouter {
+    /// Enable transactions, registering the `rpc_begin`, `rpc_commit` and
+    /// `rpc_abort` methods.
+    ///
+    /// `rpc_begin` takes no parameter and returns the id of a new
+    /// transaction. `rpc_commit` and `rpc_abort` take `[id]`. A transaction
+    /// not ended within `ttl` is rolled back. Calls tagged with an unknown
+    /// or expired transaction are answered by `TRANSACTION_ABORTED_CODE`
+    /// error without calling the handler.
+    ///
+    /// Tagged calls should complete before the transaction is ended; calls
+    /// completing afterwards are not passed to the hooks.
+    pub fn transactions<H>(&mut self, ttl: Duration, hooks: H) -> &mut RpcRouter
+    where
+        H: Hooks + 'static,
+    {
+        let transactions = Arc::new(Transactions {
+            ttl,
+            hooks: Arc::new(hooks),
+            open: Mutex::new(HashMap::new()),
+        });
+        let begin = transactions.clone();
+        let commit = transactions.clone();
+        let abort = transactions.clone();
+        self.method(RPC_BEGIN, move |(): ()| {
+            let transactions = begin.clone();
+            async move { Ok::<_, Error>(transactions.begin().await) }
+        })
+        .method(RPC_COMMIT, move |(id,): (String,)| {
+            let transactions = commit.clone();
+            async move { transactions.commit(&id).await }
+        })
+        .method(RPC_ABORT, move |(id,): (String,)| {
+            let transactions = abort.clone();
+            async move { transactions.abort(&id).await }
+        });
+        self.transactions = Some(transactions);
+        self
+    }
+}
+
+pub(crate) struct Transactions {
+    ttl: Duration,
+    hooks: Arc<dyn Hooks>,
+    /// When each open transaction began, and its completed calls.
+    open: Mutex<HashMap<String, (Instant, Vec<Call>)>>,
+}
+
+fn aborted(message: &str) -> Error {
+    Error::custom(TRANSACTION_ABORTED_CODE, "Transaction aborted").with_data(message.to_string())
+}
+
+impl Transactions {
+    /// The transaction a call of `method` belongs to, failing if it is not
+    /// open.
+    pub(crate) fn joined(&self, method: &str, ctx: &Context) -> Result<Option<String>, Error> {
+        if [RPC_BEGIN, RPC_COMMIT, RPC_ABORT].contains(&method) {
+            return Ok(None);
+        }
+        let id = match id(ctx) {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let open = self.open.lock().unwrap();
+        match open.get(id) {
+            Some((began, _)) if began.elapsed() <= self.ttl => Ok(Some(id.to_string())),
+            _ => Err(aborted("unknown or expired transaction")),
+        }
+    }
+
+    pub(crate) fn record(&self, id: &str, method: &str, result: &Result<serde_json::Value, Error>) {
+        if let Some((_, calls)) = self.open.lock().unwrap().get_mut(id) {
+            calls.push(Call {
+                method: method.to_string(),
+                error_code: result.as_ref().err().map(|e| e.code),
+            });
+        }
+    }
+
+    async fn begin(&self) -> String {
+        let id = continuation::new_token();
+        let expired = {
+            let mut open = self.open.lock().unwrap();
+            let expired = open
+                .iter()
+                .filter(|(_, (began, _))| began.elapsed() > self.ttl)
+                .map(|(id, _)| id.clone())
+                .collect::<Vec<_>>();
+            let expired = expired
+                .into_iter()
+                .filter_map(|id| open.remove_entry(&id))
+                .collect::<Vec<_>>();
+            open.insert(id.clone(), (Instant::now(), Vec::new()));
+            expired
+        };
+        for (id, (_, calls)) in expired {
+            log::info!(target: "warp_json_rpc", "Transaction {} expired", id);
+            self.hooks.rollback(&id, &calls).await;
+        }
+        id
+    }
+
+    /// End the transaction, rolling it back if it expired.
+    async fn take_or_rollback(&self, id: &str) -> Result<Vec<Call>, Error> {
+        let taken = self.open.lock().unwrap().remove(id);
+        match taken {
+            Some((began, calls)) if began.elapsed() <= self.ttl => Ok(calls),
+            Some((_, calls)) => {
+                self.hooks.rollback(id, &calls).await;
+                Err(aborted("unknown or expired transaction"))
+            }
+            None => Err(aborted("unknown or expired transaction")),
+        }
+    }
+
+    async fn commit(&self, id: &str) -> Result<(), Error> {
+        let calls = self.take_or_rollback(id).await?;
+        let prepared = match calls.iter().find(|call| call.error_code.is_some()) {
+            Some(failed) => Err(aborted(&format!("\"{}\" RPC failed", failed.method))),
+            None => self.hooks.prepare(id, &calls).await,
+        };
+        match prepared {
+            Ok(()) => {
+                self.hooks.commit(id, &calls).await;
+                Ok(())
+            }
+            Err(e) => {
+                self.hooks.rollback(id, &calls).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn abort(&self, id: &str) -> Result<(), Error> {
+        let calls = self.take_or_rollback(id).await?;
+        self.hooks.rollback(id, &calls).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Id;
+    use http::HeaderMap;
+
+    #[derive(Default)]
+    struct Recorded(Mutex<Vec<(&'static str, String, usize)>>);
+
+    impl Hooks for Recorded {
+        fn prepare<'a>(
+            &'a self,
+            id: &'a str,
+            calls: &'a [Call],
+        ) -> BoxFuture<'a, Result<(), Error>> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(("prepare", id.to_string(), calls.len()));
+            futures::future::ready(Ok(())).boxed()
+        }
+
+        fn commit<'a>(&'a self, id: &'a str, calls: &'a [Call]) -> BoxFuture<'a, ()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(("commit", id.to_string(), calls.len()));
+            futures::future::ready(()).boxed()
+        }
+
+        fn rollback<'a>(&'a self, id: &'a str, calls: &'a [Call]) -> BoxFuture<'a, ()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(("rollback", id.to_string(), calls.len()));
+            futures::future::ready(()).boxed()
+        }
+    }
+
+    fn tagged(id: &str) -> Context {
+        let mut headers = HeaderMap::new();
+        headers.insert(TRANSACTION_ID, id.parse().unwrap());
+        Context::from_parts(Id::Null, "set", headers)
+    }
+
+    #[tokio::test]
+    async fn commit_or_rollback() {
+        let hooks = Arc::new(Recorded::default());
+        let transactions = Transactions {
+            ttl: Duration::from_secs(60),
+            hooks: Arc::new(hooks.clone()),
+            open: Mutex::new(HashMap::new()),
+        };
+
+        let id = transactions.begin().await;
+        let joined = transactions
+            .joined("set", &tagged(&id))
+            .ok()
+            .unwrap()
+            .unwrap();
+        transactions.record(&joined, "set", &Ok(().into()));
+        transactions.record(&joined, "set", &Ok(().into()));
+        assert!(transactions.commit(&id).await.is_ok());
+        assert!(transactions.joined("set", &tagged(&id)).is_err());
+
+        let failed = transactions.begin().await;
+        transactions.record(&failed, "set", &Err(Error::INVALID_PARAMS));
+        let error = transactions.commit(&failed).await.err().unwrap();
+        assert_eq!(error.code, TRANSACTION_ABORTED_CODE);
+
+        let aborted = transactions.begin().await;
+        assert!(transactions.abort(&aborted).await.is_ok());
+        assert!(transactions.abort(&aborted).await.is_err());
+
+        assert_eq!(
+            *hooks.0.lock().unwrap(),
+            vec![
+                ("prepare", id.clone(), 2),
+                ("commit", id, 2),
+                ("rollback", failed, 1),
+                ("rollback", aborted, 0),
+            ]
+        );
+        let untagged = Context::from_parts(Id::Null, "set", HeaderMap::new());
+        assert_eq!(transactions.joined("set", &untagged).ok().unwrap(), None);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::{router::Method, Context, Error, RpcRouter};
+use serde_json::Value;
+use std::sync::Arc;
+
+pub(crate) type ResultTransform =
+    Arc<dyn Fn(Value, &Context) -> Result<Value, Error> + Send + Sync>;
+
+impl RpcRouter {
+    /// Pass every result of the method through `transform` right before it is
+    /// sent, e.g. to encrypt some fields by a key of the recipient found from
+    /// the context.
+    ///
+    /// Results are transformed after they are compared with the shadow and
+    /// cached for idempotency, so those see the results of the handler. An
+    /// error of `transform` is sent instead of the result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn transform_result<F>(&mut self, name: &str, transform: F) -> &mut RpcRouter
+    where
+        F: Fn(Value, &Context) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.registered(name).transform = Some(Arc::new(transform));
+        self
+    }
+}
+
+impl Method {
+    pub(crate) fn transform(
+        &self,
+        result: Result<Value, Error>,
+        ctx: &Context,
+    ) -> Result<Value, Error> {
+        match self.transform.as_ref() {
+            Some(transform) => result.and_then(|value| transform(value, ctx)),
+            None => result,
+        }
+    }
+}
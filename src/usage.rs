@@ -0,0 +1,66 @@
+use crate::{quota::QuotaUsage, Context, Error, RpcRouter, UNAUTHORIZED_CODE};
+use futures::future::FutureExt as _;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Name of the method registered by [`RpcRouter::rpc_usage`].
+///
+/// [`RpcRouter::rpc_usage`]: ./struct.RpcRouter.html#method.rpc_usage
+pub const RPC_USAGE: &str = "rpc_usage";
+
+/// Result of the `rpc_usage` method.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    /// `None` if the router has no byte quota.
+    pub byte_quota: Option<QuotaUsage>,
+}
+
+impl RpcRouter {
+    /// Register the `rpc_usage` method, which returns the [`Usage`] of the
+    /// caller.
+    ///
+    /// The caller is identified in the same way as by [`byte_quota`], and
+    /// calls without a caller are answered by `UNAUTHORIZED_CODE` error.
+    ///
+    /// The method is registered when the router is turned into a filter, so
+    /// the quota may be set after calling this.
+    ///
+    /// [`Usage`]: ./struct.Usage.html
+    /// [`byte_quota`]: #method.byte_quota
+    pub fn rpc_usage(&mut self) -> &mut RpcRouter {
+        self.report_usage = true;
+        self
+    }
+
+    pub(crate) fn register_rpc_usage(&mut self) {
+        if !self.report_usage {
+            return;
+        }
+        let quota = self.quota.clone();
+        let config = self.config.clone();
+        self.register(
+            RPC_USAGE,
+            Arc::new(move |_, ctx: Context| {
+                let (quota, config) = (quota.clone(), config.clone());
+                async move {
+                    let byte_quota = match quota.as_ref() {
+                        Some(quota) => match quota.client(&ctx) {
+                            Some(client) => {
+                                let limit =
+                                    config.as_ref().and_then(|config| config.get().byte_quota);
+                                Some(quota.usage(&client, limit).await)
+                            }
+                            None => return Err(Error::custom(UNAUTHORIZED_CODE, "Unauthorized")),
+                        },
+                        None => None,
+                    };
+                    serde_json::to_value(Usage { byte_quota }).map_err(|e| {
+                        log::error!(target: "warp_json_rpc", "Failed to serialize usage: {}", e);
+                        Error::INTERNAL_ERROR
+                    })
+                }
+                .boxed()
+            }),
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use crate::RpcRouter;
+
+/// Who a method is meant for, set by [`RpcRouter::visibility`].
+///
+/// [`RpcRouter::visibility`]: ./struct.RpcRouter.html#method.visibility
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Visibility {
+    Public,
+    /// For other services of the deployment.
+    Internal,
+    /// For operators, such as debugging methods.
+    Admin,
+}
+
+impl Default for Visibility {
+    fn default() -> Visibility {
+        Visibility::Public
+    }
+}
+
+impl RpcRouter {
+    /// Set who the method is meant for. Methods are `Public` by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not registered.
+    pub fn visibility(&mut self, name: &str, visibility: Visibility) -> &mut RpcRouter {
+        self.registered(name).visibility = visibility;
+        self
+    }
+
+    /// Serve only methods of `visibilities`; every method is served by
+    /// default.
+    ///
+    /// Other methods are rejected as unknown methods, and are neither listed
+    /// by `rpc_methods` nor documented. Together with serving clones of a
+    /// router on several listeners, this keeps e.g. admin methods off a
+    /// public listener.
+    ///
+    /// ```
+    /// # use warp_json_rpc::{Error, RpcRouter, Visibility};
+    /// let mut router = RpcRouter::new();
+    /// router
+    ///     .method("add", |(lhs, rhs): (i64, i64)| async move { Ok::<_, Error>(lhs + rhs) })
+    ///     .method("dump", |(): ()| async move { Ok::<_, Error>(()) })
+    ///     .visibility("dump", Visibility::Admin);
+    ///
+    /// let mut public = router.clone();
+    /// public.expose(&[Visibility::Public]);
+    /// let public = public.into_filter();
+    /// let admin = router.into_filter();
+    /// ```
+    pub fn expose(&mut self, visibilities: &[Visibility]) -> &mut RpcRouter {
+        self.exposed = Some(visibilities.to_vec());
+        self
+    }
+
+    pub(crate) fn exposes(&self, visibility: Visibility) -> bool {
+        match self.exposed.as_ref() {
+            Some(exposed) => exposed.contains(&visibility),
+            None => true,
+        }
+    }
+
+    /// Drop the methods which are not exposed.
+    pub(crate) fn hide_unexposed(&mut self) {
+        if self.exposed.is_none() {
+            return;
+        }
+        let mut methods = std::mem::take(&mut self.methods);
+        methods.retain(|method| self.exposes(method.visibility));
+        self.methods = methods;
+    }
+}
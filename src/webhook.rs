@@ -0,0 +1,360 @@
+//! Deliver notifications to webhook URLs, for server-to-server eventing.
+//!
+//! Each event is posted to the URLs registered for its topic as a JSON RPC
+//! notification whose method is the topic, signed by the [`Signer`] if any.
+//! Failed deliveries are retried with exponential backoff, and reported as
+//! [`DeadLetter`]s once every attempt has failed.
+//!
+//! ```
+//! use warp_json_rpc::webhook::Webhooks;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let webhooks = Webhooks::new();
+//! webhooks.register("blocks", "http://10.0.0.2/hooks/blocks".parse().unwrap());
+//! webhooks.notify("blocks", &serde_json::json!({ "number": 1 }));
+//! # }
+//! ```
+//!
+//! Only `http` URLs are supported, so `https` endpoints must be reached
+//! through a TLS terminating proxy.
+//!
+//! [`Signer`]: ../trait.Signer.html
+//! [`DeadLetter`]: ./struct.DeadLetter.html
+use crate::signing::{self, Signer, SIGNATURE};
+use http::{header::CONTENT_TYPE, HeaderValue, Method, Request, Uri};
+use hyper::{client::HttpConnector, Body, Client};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+/// An event which could not be delivered, reported to
+/// [`Webhooks::on_dead_letter`].
+///
+/// [`Webhooks::on_dead_letter`]: ./struct.Webhooks.html#method.on_dead_letter
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeadLetter {
+    pub topic: String,
+    #[serde(serialize_with = "serialize_uri")]
+    pub url: Uri,
+    pub event: Value,
+    pub attempts: u32,
+    /// Reason of the last failure.
+    pub error: String,
+}
+
+fn serialize_uri<S>(uri: &Uri, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_str(uri)
+}
+
+/// Id of a registered webhook, to unregister it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WebhookId(u64);
+
+type DeadLetterHandler = Arc<dyn Fn(DeadLetter) + Send + Sync>;
+
+/// A registry of webhooks.
+///
+/// Cloning a `Webhooks` shares the registry.
+#[derive(Clone)]
+pub struct Webhooks {
+    hooks: Arc<Mutex<BTreeMap<WebhookId, (String, Uri)>>>,
+    next_id: Arc<AtomicU64>,
+    settings: Arc<RwLock<Settings>>,
+    client: Client<HttpConnector>,
+}
+
+/// Longest interval between attempts of a delivery.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone)]
+struct Settings {
+    attempts: u32,
+    backoff: Duration,
+    signer: Option<Arc<dyn Signer>>,
+    dead_letter: DeadLetterHandler,
+}
+
+impl Default for Webhooks {
+    fn default() -> Webhooks {
+        Webhooks {
+            hooks: Arc::default(),
+            next_id: Arc::default(),
+            settings: Arc::new(RwLock::new(Settings {
+                attempts: 5,
+                backoff: Duration::from_secs(1),
+                signer: None,
+                dead_letter: Arc::new(|letter: DeadLetter| {
+                    log::error!(
+                        target: "warp_json_rpc",
+                        "Failed to deliver \"{}\" event to {} after {} attempts: {}",
+                        letter.topic,
+                        letter.url,
+                        letter.attempts,
+                        letter.error
+                    )
+                }),
+            })),
+            client: Client::new(),
+        }
+    }
+}
+
+impl Webhooks {
+    /// Webhooks attempting each delivery 5 times, 1 second apart at first and
+    /// doubling the interval after each attempt.
+    pub fn new() -> Webhooks {
+        Webhooks::default()
+    }
+
+    /// Attempt each delivery up to `attempts` times, `backoff` apart at first
+    /// and doubling the interval after each attempt, up to an hour.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `attempts` is zero.
+    pub fn retries(&self, attempts: u32, backoff: Duration) -> &Webhooks {
+        assert!(attempts > 0, "webhook deliveries need at least one attempt");
+        let mut settings = self.settings.write().unwrap();
+        settings.attempts = attempts;
+        settings.backoff = backoff;
+        self
+    }
+
+    /// Sign deliveries by `signer`, sent by the `X-Signature` header as
+    /// responses signed by [`RpcRouter::sign_responses`].
+    ///
+    /// [`RpcRouter::sign_responses`]: ../struct.RpcRouter.html#method.sign_responses
+    pub fn sign_by<S>(&self, signer: S) -> &Webhooks
+    where
+        S: Signer + 'static,
+    {
+        self.settings.write().unwrap().signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Report events which could not be delivered to `handler`, instead of
+    /// logging them.
+    pub fn on_dead_letter<F>(&self, handler: F) -> &Webhooks
+    where
+        F: Fn(DeadLetter) + Send + Sync + 'static,
+    {
+        self.settings.write().unwrap().dead_letter = Arc::new(handler);
+        self
+    }
+
+    /// Deliver events of `topic` to `url` from now on.
+    pub fn register(&self, topic: impl Into<String>, url: Uri) -> WebhookId {
+        let id = WebhookId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.hooks.lock().unwrap().insert(id, (topic.into(), url));
+        id
+    }
+
+    /// Stop delivering to the webhook, and return whether it was registered.
+    ///
+    /// Deliveries in progress are still completed.
+    pub fn unregister(&self, id: WebhookId) -> bool {
+        self.hooks.lock().unwrap().remove(&id).is_some()
+    }
+
+    /// Deliver `event` to the webhooks of `topic` in the background, and
+    /// return how many webhooks there are.
+    ///
+    /// This must be called within a `tokio` runtime.
+    pub fn notify<T>(&self, topic: &str, event: &T) -> usize
+    where
+        T: Serialize,
+    {
+        let urls = self
+            .hooks
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(hook_topic, _)| hook_topic == topic)
+            .map(|(_, url)| url.clone())
+            .collect::<Vec<_>>();
+        if urls.is_empty() {
+            return 0;
+        }
+        let event = match serde_json::to_value(event) {
+            Ok(event) => event,
+            Err(e) => {
+                log::error!(target: "warp_json_rpc", "Failed to serialize event: {}", e);
+                return 0;
+            }
+        };
+        let body = serde_json::json!({ "jsonrpc": "2.0", "method": topic, "params": event });
+        let body = bytes::Bytes::from(body.to_string());
+        let settings = self.settings.read().unwrap().clone();
+        for url in urls.iter().cloned() {
+            let delivery = Delivery {
+                client: self.client.clone(),
+                settings: settings.clone(),
+                topic: topic.to_string(),
+                url,
+                body: body.clone(),
+            };
+            tokio::spawn(delivery.run(event.clone()));
+        }
+        urls.len()
+    }
+}
+
+struct Delivery {
+    client: Client<HttpConnector>,
+    settings: Settings,
+    topic: String,
+    url: Uri,
+    body: bytes::Bytes,
+}
+
+impl Delivery {
+    async fn run(self, event: Value) {
+        let mut backoff = self.settings.backoff.min(MAX_BACKOFF);
+        let mut error = String::new();
+        for attempt in 1..=self.settings.attempts {
+            match self.attempt().await {
+                Ok(()) => return,
+                Err(e) => error = e,
+            }
+            log::debug!(
+                target: "warp_json_rpc",
+                "Attempt {} to deliver \"{}\" event to {} failed: {}",
+                attempt,
+                self.topic,
+                self.url,
+                error
+            );
+            if attempt < self.settings.attempts {
+                tokio::time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(2).min(MAX_BACKOFF);
+            }
+        }
+        (self.settings.dead_letter)(DeadLetter {
+            topic: self.topic,
+            url: self.url,
+            event,
+            attempts: self.settings.attempts,
+            error,
+        });
+    }
+
+    async fn attempt(&self) -> Result<(), String> {
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.clone())
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(signer) = self.settings.signer.as_ref() {
+            let signature = signing::signature(signer.as_ref(), &self.body);
+            let signature = HeaderValue::from_str(&signature).map_err(|e| e.to_string())?;
+            req = req.header(SIGNATURE, signature);
+        }
+        let req = req
+            .body(Body::from(self.body.clone()))
+            .map_err(|e| e.to_string())?;
+        let res = self.client.request(req).await.map_err(|e| e.to_string())?;
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("responded {}", res.status()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use warp::Filter as _;
+
+    struct Length;
+
+    impl Signer for Length {
+        fn algorithm(&self) -> &str {
+            "length"
+        }
+
+        fn sign(&self, body: &[u8]) -> Vec<u8> {
+            body.len().to_string().into_bytes()
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_until_delivered() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let route = warp::post()
+            .and(warp::header::<String>("X-Signature"))
+            .and(warp::body::json())
+            .map({
+                let received = received.clone();
+                move |signature: String, body: Value| {
+                    let mut received = received.lock().unwrap();
+                    received.push((signature, body));
+                    let status = match received.len() {
+                        1 => warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        _ => warp::http::StatusCode::OK,
+                    };
+                    warp::reply::with_status("", status)
+                }
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let webhooks = Webhooks::new();
+        webhooks
+            .retries(3, Duration::from_millis(10))
+            .sign_by(Length);
+        let id = webhooks.register("blocks", format!("http://{}/", addr).parse().unwrap());
+        assert_eq!(webhooks.notify("blocks", &1), 1);
+        assert_eq!(webhooks.notify("txs", &1), 0);
+
+        for _ in 0..100 {
+            if received.lock().unwrap().len() >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let (signature, body) = &received[1];
+        let expected = serde_json::json!({ "jsonrpc": "2.0", "method": "blocks", "params": 1 });
+        assert_eq!(body, &expected);
+        let length = expected.to_string().len().to_string();
+        assert_eq!(signature, &format!("length={}", base64::encode(length)));
+        assert!(webhooks.unregister(id));
+    }
+
+    #[tokio::test]
+    async fn report_dead_letters() {
+        // Bind and drop a listener for a port refusing connections.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let tx = Mutex::new(Some(tx));
+        let webhooks = Webhooks::new();
+        webhooks
+            .retries(2, Duration::from_millis(1))
+            .on_dead_letter(move |letter| {
+                if let Some(tx) = tx.lock().unwrap().take() {
+                    tx.send(letter).unwrap();
+                }
+            });
+        webhooks.register("blocks", format!("http://{}/", addr).parse().unwrap());
+        webhooks.notify("blocks", &1);
+
+        let letter = rx.await.unwrap();
+        assert_eq!(letter.attempts, 2);
+        assert_eq!(letter.event, Value::from(1));
+    }
+}
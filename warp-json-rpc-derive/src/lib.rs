@@ -0,0 +1,192 @@
+//! Derive macros for `warp-json-rpc`.
+//!
+//! Use them through the `derive` feature of `warp-json-rpc` instead of
+//! depending on this crate directly.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Type};
+
+/// Derive `serde::Deserialize` accepting both positional and named parameters.
+///
+/// The struct is deserialized either from an array, whose elements are
+/// assigned to fields in declaration order, or from an object keyed by field
+/// names. Trailing `Option` fields may be omitted from an array, and any
+/// `Option` field may be omitted from an object.
+///
+/// Unknown, duplicated and missing fields are reported by name.
+#[proc_macro_derive(RpcParams)]
+pub fn derive_rpc_params(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct Field {
+    ident: syn::Ident,
+    local: syn::Ident,
+    name: String,
+    ty: Type,
+    optional: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "RpcParams does not support generic structs",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input,
+                    "RpcParams requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "RpcParams can only be derived for structs",
+            ))
+        }
+    };
+    let fields = fields
+        .iter()
+        .map(|field| {
+            let ident = field.ident.clone().unwrap();
+            Field {
+                name: ident.to_string(),
+                local: format_ident!("__field_{}", ident),
+                ident,
+                ty: field.ty.clone(),
+                optional: is_option(&field.ty),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let serde = quote!(::warp_json_rpc::__private::serde);
+    let expecting = format!("an array or an object of {} parameters", ident);
+    let names = fields.iter().map(|f| &f.name).collect::<Vec<_>>();
+    let idents = fields.iter().map(|f| &f.ident).collect::<Vec<_>>();
+    let locals = fields.iter().map(|f| &f.local).collect::<Vec<_>>();
+    let len = fields.len();
+
+    let seq_fields = fields.iter().enumerate().map(|(i, f)| {
+        let Field { local, ty, .. } = f;
+        let missing = if f.optional {
+            quote!(None)
+        } else {
+            quote!(return Err(#serde::de::Error::invalid_length(#i, &self)))
+        };
+        quote! {
+            let #local = match __seq.next_element::<#ty>()? {
+                Some(__value) => __value,
+                None => #missing,
+            };
+        }
+    });
+
+    let map_slots = fields
+        .iter()
+        .map(|Field { local, ty, .. }| quote!(let mut #local: Option<#ty> = None;));
+    let map_arms = fields.iter().map(|Field { local, name, .. }| {
+        quote! {
+            #name => {
+                if #local.is_some() {
+                    return Err(#serde::de::Error::duplicate_field(#name));
+                }
+                #local = Some(__map.next_value()?);
+            }
+        }
+    });
+    let map_fields = fields.iter().map(|f| {
+        let Field { local, name, .. } = f;
+        if f.optional {
+            quote!(let #local = #local.unwrap_or(None);)
+        } else {
+            quote! {
+                let #local = match #local {
+                    Some(__value) => __value,
+                    None => return Err(#serde::de::Error::missing_field(#name)),
+                };
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl<'de> #serde::Deserialize<'de> for #ident {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: #serde::Deserializer<'de>,
+            {
+                const FIELDS: &[&str] = &[#(#names),*];
+
+                struct Visitor;
+
+                impl<'de> #serde::de::Visitor<'de> for Visitor {
+                    type Value = #ident;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                        f.write_str(#expecting)
+                    }
+
+                    fn visit_seq<A>(self, mut __seq: A) -> Result<#ident, A::Error>
+                    where
+                        A: #serde::de::SeqAccess<'de>,
+                    {
+                        #(#seq_fields)*
+                        let mut __len = #len;
+                        while __seq.next_element::<#serde::de::IgnoredAny>()?.is_some() {
+                            __len += 1;
+                        }
+                        if __len > #len {
+                            return Err(#serde::de::Error::invalid_length(__len, &self));
+                        }
+                        Ok(#ident { #(#idents: #locals),* })
+                    }
+
+                    fn visit_map<A>(self, mut __map: A) -> Result<#ident, A::Error>
+                    where
+                        A: #serde::de::MapAccess<'de>,
+                    {
+                        #(#map_slots)*
+                        while let Some(__key) = __map.next_key::<String>()? {
+                            match __key.as_str() {
+                                #(#map_arms)*
+                                __other => {
+                                    return Err(#serde::de::Error::unknown_field(__other, FIELDS));
+                                }
+                            }
+                        }
+                        #(#map_fields)*
+                        Ok(#ident { #(#idents: #locals),* })
+                    }
+                }
+
+                deserializer.deserialize_any(Visitor)
+            }
+        }
+    })
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident == "Option")
+            .unwrap_or(false),
+        _ => false,
+    }
+}
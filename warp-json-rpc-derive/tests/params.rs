@@ -0,0 +1,93 @@
+use warp_json_rpc::RpcParams;
+
+#[derive(RpcParams, PartialEq, Debug)]
+struct Params {
+    lhs: i64,
+    rhs: i64,
+    op: Option<String>,
+}
+
+fn parse(s: &str) -> Result<Params, String> {
+    serde_json::from_str::<Params>(s).map_err(|e| e.to_string())
+}
+
+#[test]
+fn positional() {
+    let expected = Params {
+        lhs: 1,
+        rhs: 2,
+        op: Some("+".to_string()),
+    };
+    assert_eq!(parse(r#"[1, 2, "+"]"#).unwrap(), expected);
+}
+
+#[test]
+fn named() {
+    let expected = Params {
+        lhs: 1,
+        rhs: 2,
+        op: Some("+".to_string()),
+    };
+    assert_eq!(
+        parse(r#"{"rhs": 2, "lhs": 1, "op": "+"}"#).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn optional_fields_may_be_omitted() {
+    let expected = Params {
+        lhs: 1,
+        rhs: 2,
+        op: None,
+    };
+    assert_eq!(parse("[1, 2]").unwrap(), expected);
+    assert_eq!(parse(r#"{"lhs": 1, "rhs": 2}"#).unwrap(), expected);
+}
+
+#[test]
+fn errors_name_the_field() {
+    assert!(parse(r#"{"lhs": 1}"#)
+        .unwrap_err()
+        .contains("missing field `rhs`"));
+    assert!(parse(r#"{"lhs": 1, "rhs": 2, "x": 3}"#)
+        .unwrap_err()
+        .contains("unknown field `x`"));
+    assert!(parse(r#"{"lhs": 1, "lhs": 2}"#)
+        .unwrap_err()
+        .contains("duplicate field `lhs`"));
+    assert!(parse("[1]").unwrap_err().contains("invalid length 1"));
+    assert!(parse(r#"[1, 2, "+", 4]"#)
+        .unwrap_err()
+        .contains("invalid length 4"));
+    assert!(parse(r#"[1, 2, "+", 4, 5, 6]"#)
+        .unwrap_err()
+        .contains("invalid length 6"));
+}
+
+#[derive(RpcParams, PartialEq, Debug)]
+struct Shadowing {
+    key: String,
+    map: u32,
+    seq: Option<bool>,
+    value: u32,
+}
+
+#[test]
+fn fields_named_like_visitor_locals() {
+    let expected = Shadowing {
+        key: "k".to_string(),
+        map: 1,
+        seq: Some(true),
+        value: 2,
+    };
+    assert_eq!(
+        serde_json::from_str::<Shadowing>(r#"["k", 1, true, 2]"#).unwrap(),
+        expected
+    );
+    assert_eq!(
+        serde_json::from_str::<Shadowing>(r#"{"seq": true, "map": 1, "key": "k", "value": 2}"#)
+            .unwrap(),
+        expected
+    );
+}